@@ -52,6 +52,7 @@ use rustc_serialize::{Decodable, Decoder};
 use sodiumoxide::crypto;
 
 use routing::routing::Routing;
+use routing::types::RoutingConfig;
 use routing::routing_client::RoutingClient;
 use routing::authority::Authority;
 use routing::NameType;
@@ -59,7 +60,7 @@ use routing::event::Event;
 use routing::data::{Data, DataRequest};
 use routing::plain_data::PlainData;
 use routing::utils::{encode, decode};
-use routing::{ExternalRequest, ExternalResponse, SignedToken};
+use routing::{ExternalRequest, ExternalResponse, SignedToken, MessageId};
 use routing::id::Id;
 use routing::public_id::PublicId;
 
@@ -103,7 +104,7 @@ struct Node {
 impl Node {
     fn new() -> Node {
         let (sender, receiver) = mpsc::channel::<Event>();
-        let routing = Routing::new(sender);
+        let routing = Routing::new(sender, RoutingConfig::default_config());
 
         Node {
             routing: routing,
@@ -130,11 +131,13 @@ impl Node {
                 Event::Request{request,
                                our_authority,
                                from_authority,
-                               response_token} => {
+                               response_token,
+                               message_id} => {
                     self.handle_request(request,
                                         our_authority,
                                         from_authority,
-                                        response_token);
+                                        response_token,
+                                        message_id);
                 },
                 Event::Connected => {
                     self.connected = true;
@@ -165,13 +168,15 @@ impl Node {
     fn handle_request(&mut self, request        : ExternalRequest,
                                  our_authority  : Authority,
                                  from_authority : Authority,
-                                 response_token : Option<SignedToken>) {
+                                 response_token : Option<SignedToken>,
+                                 message_id     : MessageId) {
         match request {
             ExternalRequest::Get(data_request, _) => {
                 self.handle_get_request(data_request,
                                         our_authority,
                                         from_authority,
-                                        response_token);
+                                        response_token,
+                                        message_id);
             },
             ExternalRequest::Put(data) => {
                 self.handle_put_request(data,
@@ -191,7 +196,8 @@ impl Node {
     fn handle_get_request(&mut self, data_request: DataRequest,
                                      our_authority: Authority,
                                      from_authority: Authority,
-                                     response_token: Option<SignedToken>) {
+                                     response_token: Option<SignedToken>,
+                                     message_id: MessageId) {
         let name = match data_request {
             DataRequest::PlainData(name) => name,
             _ => { println!("Node: Only serving plain data in this example"); return; }
@@ -206,7 +212,8 @@ impl Node {
                                   from_authority,
                                   Data::PlainData(data),
                                   data_request,
-                                  response_token);
+                                  response_token,
+                                  message_id);
     }
 
     fn handle_put_request(&mut self, data            : Data,
@@ -233,7 +240,7 @@ impl Node {
                         println!("Client ({:?}) stored {:?} bytes", client_name,
                             self.client_accounts.get(&client_name));
                         debug!("Sending: key {:?}, value {:?}", plain_data.name(), plain_data);
-                        self.routing.put_request(
+                        let _ = self.routing.put_request(
                             our_authority, Authority::NaeManager(plain_data.name()), data);
                     },
                     _ => {
@@ -385,7 +392,8 @@ impl Client {
         let id = Id::new();
         let public_id = PublicId::new(&id);
         println!("Client has set name {:?}", public_id.clone());
-        let routing_client = RoutingClient::new(event_sender, Some(id));
+        let routing_client = RoutingClient::new(event_sender, Some(id),
+                                                RoutingConfig::default_config());
 
         let (command_sender, command_receiver) = mpsc::channel::<UserCommand>();
 
@@ -466,7 +474,7 @@ impl Client {
         match event {
             Event::Response{
                 response, our_authority : _our_authority,
-                from_authority : _from_authority} => {
+                from_authority : _from_authority, message_id : _message_id} => {
                 match response {
                     ExternalResponse::Get(data, _data_request, _opt_signed_token) => {
                         let plain_data = match data {
@@ -497,7 +505,7 @@ impl Client {
     fn send_get_request(&mut self, what: String) {
         let name = Client::calculate_key_name(&what);
 
-        self.routing_client.get_request(Authority::ClientManager(name.clone()),
+        let _ = self.routing_client.get_request(Authority::ClientManager(name.clone()),
             DataRequest::PlainData(name));
     }
 
@@ -505,7 +513,7 @@ impl Client {
         let name = Client::calculate_key_name(&put_where);
         let data = encode(&(put_where, put_what)).unwrap();
 
-        self.routing_client.put_request(Authority::ClientManager(self.public_id.name()),
+        let _ = self.routing_client.put_request(Authority::ClientManager(self.public_id.name()),
             Data::PlainData(PlainData::new(name, data)));
     }
 
@@ -52,6 +52,7 @@ use rustc_serialize::{Decodable, Decoder};
 use sodiumoxide::crypto;
 
 use routing::routing::Routing;
+use routing::types::RoutingConfig;
 use routing::routing_client::RoutingClient;
 use routing::authority::Authority;
 use routing::NameType;
@@ -59,7 +60,7 @@ use routing::event::Event;
 use routing::data::{Data, DataRequest};
 use routing::plain_data::PlainData;
 use routing::utils::{encode, decode};
-use routing::{ExternalRequest, ExternalResponse, SignedToken};
+use routing::{ExternalRequest, ExternalResponse, SignedToken, MessageId};
 use routing::id::Id;
 use routing::public_id::PublicId;
 
@@ -101,7 +102,7 @@ struct Node {
 impl Node {
     fn new() -> Node {
         let (sender, receiver) = mpsc::channel::<Event>();
-        let routing = Routing::new(sender);
+        let routing = Routing::new(sender, RoutingConfig::default_config());
 
         Node {
             routing  : routing,
@@ -126,11 +127,13 @@ impl Node {
                 Event::Request{request,
                                our_authority,
                                from_authority,
-                               response_token} => {
+                               response_token,
+                               message_id} => {
                     self.handle_request(request,
                                         our_authority,
                                         from_authority,
-                                        response_token);
+                                        response_token,
+                                        message_id);
                 },
                 Event::Churn(our_close_group, cause) => {
                     self.handle_churn(our_close_group, cause);
@@ -143,13 +146,15 @@ impl Node {
     fn handle_request(&mut self, request        : ExternalRequest,
                                  our_authority  : Authority,
                                  from_authority : Authority,
-                                 response_token : Option<SignedToken>) {
+                                 response_token : Option<SignedToken>,
+                                 message_id     : MessageId) {
         match request {
             ExternalRequest::Get(data_request, _) => {
                 self.handle_get_request(data_request,
                                         our_authority,
                                         from_authority,
-                                        response_token);
+                                        response_token,
+                                        message_id);
             },
             ExternalRequest::Put(data) => {
                 self.handle_put_request(data,
@@ -169,7 +174,8 @@ impl Node {
     fn handle_get_request(&mut self, data_request: DataRequest,
                                      our_authority: Authority,
                                      from_authority: Authority,
-                                     response_token: Option<SignedToken>) {
+                                     response_token: Option<SignedToken>,
+                                     message_id: MessageId) {
         let name = match data_request {
             DataRequest::PlainData(name) => name,
             _ => { error!("Node: Only serving plain data in this example"); return; }
@@ -184,7 +190,8 @@ impl Node {
                                   from_authority,
                                   Data::PlainData(data),
                                   data_request,
-                                  response_token);
+                                  response_token,
+                                  message_id);
     }
 
     fn handle_put_request(&mut self, data            : Data,
@@ -213,7 +220,8 @@ impl Node {
         info!("Handle churn for close group size {:?}", _our_close_group.len());
         for value in self.db.values() {
             println!("CHURN {:?}", value.name());
-            self.routing.put_request(::routing::authority::Authority::NaeManager(value.name()),
+            let _ = self.routing.put_request(
+                ::routing::authority::Authority::NaeManager(value.name()),
                 ::routing::authority::Authority::NaeManager(value.name()),
                 ::routing::data::Data::PlainData(value.clone()));
         }
@@ -264,7 +272,8 @@ impl Client {
 
         let id = Id::new();
         info!("Client has set name {:?}", PublicId::new(&id));
-        let routing_client = RoutingClient::new(event_sender, Some(id));
+        let routing_client = RoutingClient::new(event_sender, Some(id),
+                                                RoutingConfig::default_config());
 
         let (command_sender, command_receiver) = mpsc::channel::<UserCommand>();
 
@@ -344,7 +353,7 @@ impl Client {
         match event {
             Event::Response{
                 response, our_authority : _our_authority,
-                from_authority : _from_authority} => {
+                from_authority : _from_authority, message_id : _message_id} => {
                 match response {
                     ExternalResponse::Get(data, _data_request, _opt_signed_token) => {
                         let plain_data = match data {
@@ -375,7 +384,7 @@ impl Client {
     fn send_get_request(&mut self, what: String) {
         let name = Client::calculate_key_name(&what);
 
-        self.routing_client.get_request(Authority::NaeManager(name.clone()),
+        let _ = self.routing_client.get_request(Authority::NaeManager(name.clone()),
             DataRequest::PlainData(name));
     }
 
@@ -383,7 +392,7 @@ impl Client {
         let name = Client::calculate_key_name(&put_where);
         let data = encode(&(put_where, put_what)).unwrap();
 
-        self.routing_client.put_request(Authority::NaeManager(name.clone()),
+        let _ = self.routing_client.put_request(Authority::NaeManager(name.clone()),
             Data::PlainData(PlainData::new(name, data)));
     }
 
@@ -27,18 +27,22 @@ pub enum Action {
     SendMessage(::messages::SignedMessage),
     //          ~~|~~~~~~~~~~
     //            | a fully signed message with a given claimant
-    SendContent(::authority::Authority, ::authority::Authority, ::messages::Content),
+    SendContent(::authority::Authority, ::authority::Authority, ::messages::Content,
+                ::messages::MessageId),
     SendConfirmationHello(::crust::Connection, ::types::Address),
-    ClientSendContent(::authority::Authority, ::messages::Content),
-    //          ~~|~~~~~~  ~~|~~~~
+    ClientSendContent(::authority::Authority, ::messages::Content, ::messages::MessageId),
+    //          ~~|~~~~~~  ~~|~~~~                ~~|~~~~~~
+    //            |          |                      | identifies the request and its responses
     //            |          | the bare content for a message to be formed
     //            | the destination authority
     // RoutingNode will form the RoutingMessage and sign it as its own identity
     Churn(::direct_messages::Churn, Vec<::crust::Connection>, ::NameType),
     SetCacheOptions(::types::CacheOptions),
+    SetRequestPolicy(::types::RequestPolicy),
     DropConnections(Vec<::crust::Connection>),
     MatchConnection(Option<(::routing_core::ExpectedConnection, Option<::crust::Connection>)>,
                     Option<(::crust::Connection, Option<::direct_messages::Hello>)>),
+    RefreshRoutingTable(::NameType),
     Rebootstrap,
     Terminate,
 }
@@ -138,7 +138,8 @@ pub fn our_authority(message: &RoutingMessage, routing_table: &RoutingTable) ->
         Content::InternalRequest(ref request) => {
             match *request {
                 InternalRequest::Connect(_) => None,
-                InternalRequest::RequestNetworkName(ref public_id) => Some(public_id.name()),
+                InternalRequest::RequestPublicIds(_) => None,
+                InternalRequest::RequestNetworkName(ref public_id, _) => Some(public_id.name()),
                 InternalRequest::CacheNetworkName(ref public_id, _) => Some(public_id.name()),
                 InternalRequest::Refresh(_, _, _)                      => {
                     let destination = message.destination();
@@ -154,6 +155,8 @@ pub fn our_authority(message: &RoutingMessage, routing_table: &RoutingTable) ->
         }
         Content::ExternalResponse(_) => None,
         Content::InternalResponse(_) => None,
+        // only sent to a single node or client, which has to be the destination
+        Content::Encrypted(_) => None,
     };
 
     let element = match element {
@@ -224,7 +227,8 @@ mod test {
     #[test]
     fn our_authority_full_routing_table() {
         let id = Id::new();
-        let mut routing_table = RoutingTable::new(&id.name());
+        let mut routing_table = RoutingTable::new(&id.name(), ::types::GROUP_SIZE,
+                                                  ::types::BUCKET_SIZE);
         let mut count : usize = 0;
         loop {
             let _ = routing_table.add_node(NodeInfo::new(
@@ -295,6 +299,7 @@ mod test {
             from_authority : Authority::Client(rand::random(), client_public_key.clone()),
             to_authority   : Authority::ClientManager(public_key_to_client_name(&client_public_key)),
             content : Content::ExternalRequest(ExternalRequest::Put(some_data.clone())),
+            message_id : ::messages::MessageId::new(),
         };
         assert_eq!(super::determine_authority(&client_manager_message,
             &routing_table,
@@ -306,6 +311,7 @@ mod test {
             from_authority : Authority::ClientManager(public_key_to_client_name(&client_public_key)),
             to_authority   : Authority::NaeManager(nae_or_client_in_our_close_group.clone()),
             content        : Content::ExternalRequest(ExternalRequest::Put(some_data.clone())),
+            message_id : ::messages::MessageId::new(),
         };
         assert_eq!(super::determine_authority(&nae_manager_message, &routing_table,
             nae_or_client_in_our_close_group).unwrap(),
@@ -316,6 +322,7 @@ mod test {
             from_authority : Authority::NaeManager(rand::random()),
             to_authority   : Authority::NodeManager(second_closest_node_in_our_close_group.id.clone()),
             content        : Content::ExternalRequest(ExternalRequest::Put(some_data.clone())),
+            message_id : ::messages::MessageId::new(),
         };
         assert_eq!(super::determine_authority(&node_manager_message,
             &routing_table, some_data.name()).unwrap(),
@@ -326,6 +333,7 @@ mod test {
             from_authority : Authority::NodeManager(our_name.clone()),
             to_authority   : Authority::ManagedNode(our_name.clone()),
             content        : Content::ExternalRequest(ExternalRequest::Put(some_data.clone())),
+            message_id : ::messages::MessageId::new(),
         };
         assert_eq!(super::determine_authority(&managed_node_message, &routing_table,
             some_data.name()).unwrap(),
@@ -339,6 +347,7 @@ mod test {
             to_authority   : Authority::NaeManager(nae_or_client_in_our_close_group.clone()),
             content        : Content::InternalRequest(::messages::InternalRequest::Refresh(0u64,
                 some_bytes.clone(), rand::random())),
+            message_id: ::messages::MessageId::new(),
         };
         assert_eq!(super::our_authority(&refresh_message, &routing_table),
             Some(Authority::NaeManager(nae_or_client_in_our_close_group.clone())));
@@ -349,6 +358,7 @@ mod test {
             to_authority   : Authority::NaeManager(nae_or_client_in_our_close_group.clone()),
             content        : Content::InternalRequest(::messages::InternalRequest::Refresh(0u64,
                 some_bytes.clone(), rand::random())),
+            message_id: ::messages::MessageId::new(),
         };
         assert!(super::our_authority(&refresh_message, &routing_table).is_none());
         // assert that this is not a valid Refresh Authority
@@ -357,6 +367,7 @@ mod test {
             to_authority   : Authority::NaeManager(nae_or_client_in_our_close_group.clone()),
             content        : Content::InternalRequest(::messages::InternalRequest::Refresh(0u64,
                 some_bytes.clone(), rand::random())),
+            message_id: ::messages::MessageId::new(),
         };
         assert!(super::our_authority(&refresh_message, &routing_table).is_none());
     }
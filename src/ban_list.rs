@@ -0,0 +1,191 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::collections::HashMap;
+
+use NameType;
+
+/// Score at which a peer gets banned.
+pub const BAN_THRESHOLD: u32 = 100;
+/// Default duration of a ban in minutes.
+pub const BAN_DURATION_MINUTES: i64 = 60;
+
+/// A peer as tracked by the ban list, either by its network name or by its endpoint.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Peer {
+    /// A node or client by its name.
+    Name(NameType),
+    /// Any peer connecting from this endpoint.
+    Endpoint(::crust::Endpoint),
+}
+
+/// Misbehaviour a peer can be penalised for.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Misbehaviour {
+    /// A message failed its signature check.
+    FailedSignature,
+    /// A message came from or for an authority that doesn't hold.
+    BadAuthority,
+    /// A Hello failed its checks.
+    InvalidHello,
+    /// A peer claimed a place in our routing table under a name that isn't its own.
+    RoutingTableSpam,
+}
+
+impl Misbehaviour {
+    /// The penalty added to the score of the peer.
+    pub fn penalty(&self) -> u32 {
+        match *self {
+            Misbehaviour::FailedSignature => 20,
+            Misbehaviour::BadAuthority => 10,
+            Misbehaviour::InvalidHello => 50,
+            Misbehaviour::RoutingTableSpam => 25,
+        }
+    }
+}
+
+/// BanList keeps a misbehaviour score per peer.  A peer whose score reaches BAN_THRESHOLD is
+/// banned for the ban duration, after which its score starts from zero again.  A score that hasn't
+/// grown for the ban duration is forgotten as well.
+pub struct BanList {
+    ban_duration: ::time::Duration,
+    scores: HashMap<Peer, (u32, ::time::SteadyTime)>,
+    banned: HashMap<Peer, ::time::SteadyTime>,
+}
+
+impl BanList {
+    /// Construct an empty ban list, banning for BAN_DURATION_MINUTES.
+    pub fn new() -> BanList {
+        BanList::with_ban_duration(::time::Duration::minutes(BAN_DURATION_MINUTES))
+    }
+
+    /// Construct an empty ban list, banning for the given duration.
+    pub fn with_ban_duration(ban_duration: ::time::Duration) -> BanList {
+        BanList { ban_duration: ban_duration, scores: HashMap::new(), banned: HashMap::new() }
+    }
+
+    /// Add the penalty for the misbehaviour to the score of the peer.  Returns true if the peer
+    /// is banned.
+    pub fn record(&mut self, peer: Peer, misbehaviour: Misbehaviour) -> bool {
+        if self.is_banned(&peer) {
+            return true;
+        };
+        let now = ::clock::now();
+        let score = match self.scores.remove(&peer) {
+            Some((score, last_seen)) if last_seen + self.ban_duration > now => score,
+            _ => 0u32,
+        } + misbehaviour.penalty();
+        debug!("{:?} of {:?} raises its score to {:?}", misbehaviour, peer, score);
+        if score >= BAN_THRESHOLD {
+            info!("Banning {:?} for {:?} minutes", peer, self.ban_duration.num_minutes());
+            let _ = self.banned.insert(peer, now + self.ban_duration);
+            return true;
+        };
+        let _ = self.scores.insert(peer, (score, now));
+        false
+    }
+
+    /// Returns true if the peer is currently banned.
+    pub fn is_banned(&mut self, peer: &Peer) -> bool {
+        self.remove_expired();
+        self.banned.contains_key(peer)
+    }
+
+    /// The banned peers with the time left on their bans.
+    pub fn banned(&mut self) -> Vec<(Peer, ::time::Duration)> {
+        self.remove_expired();
+        let now = ::clock::now();
+        self.banned.iter().map(|(peer, until)| (peer.clone(), *until - now)).collect()
+    }
+
+    /// Lift the ban on a peer and forget its score.  Returns true if the peer was banned.
+    pub fn clear(&mut self, peer: &Peer) -> bool {
+        let _ = self.scores.remove(peer);
+        self.banned.remove(peer).is_some()
+    }
+
+    /// Lift all bans and forget all scores.
+    pub fn clear_all(&mut self) {
+        self.scores.clear();
+        self.banned.clear();
+    }
+
+    fn remove_expired(&mut self) {
+        let now = ::clock::now();
+        let expired = self.banned.iter()
+            .filter(|&(_, until)| *until <= now)
+            .map(|(peer, _)| peer.clone())
+            .collect::<Vec<_>>();
+        for peer in expired {
+            let _ = self.banned.remove(&peer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand;
+    use super::{BanList, Misbehaviour, Peer};
+
+    #[test]
+    fn ban_on_threshold() {
+        let mut ban_list = BanList::new();
+        let peer = Peer::Name(rand::random());
+        let other_peer = Peer::Endpoint(::test_utils::test::random_endpoint());
+        assert!(!ban_list.record(peer.clone(), Misbehaviour::InvalidHello));
+        assert!(!ban_list.record(other_peer.clone(), Misbehaviour::BadAuthority));
+        assert!(!ban_list.is_banned(&peer));
+        assert!(ban_list.record(peer.clone(), Misbehaviour::InvalidHello));
+        assert!(ban_list.is_banned(&peer));
+        assert!(!ban_list.is_banned(&other_peer));
+
+        let banned = ban_list.banned();
+        assert_eq!(banned.len(), 1);
+        assert_eq!(banned[0].0, peer);
+
+        assert!(ban_list.clear(&peer));
+        assert!(!ban_list.is_banned(&peer));
+        assert!(!ban_list.clear(&peer));
+        // the score is forgotten with the ban
+        assert!(!ban_list.record(peer.clone(), Misbehaviour::InvalidHello));
+    }
+
+    #[test]
+    fn ban_expires() {
+        let mut ban_list = BanList::with_ban_duration(::time::Duration::milliseconds(5));
+        let peer = Peer::Name(rand::random());
+        for _ in 0..(super::BAN_THRESHOLD / Misbehaviour::FailedSignature.penalty()) {
+            let _ = ban_list.record(peer.clone(), Misbehaviour::FailedSignature);
+        }
+        assert!(ban_list.is_banned(&peer));
+        ::std::thread::sleep_ms(10);
+        assert!(!ban_list.is_banned(&peer));
+        assert!(ban_list.banned().is_empty());
+    }
+
+    #[test]
+    fn clear_all() {
+        let mut ban_list = BanList::new();
+        let peer = Peer::Name(rand::random());
+        assert!(!ban_list.record(peer.clone(), Misbehaviour::InvalidHello));
+        assert!(ban_list.record(peer.clone(), Misbehaviour::InvalidHello));
+        ban_list.clear_all();
+        assert!(!ban_list.is_banned(&peer));
+        assert!(!ban_list.record(peer, Misbehaviour::InvalidHello));
+    }
+}
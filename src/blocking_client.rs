@@ -0,0 +1,355 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread::spawn;
+
+use authority::Authority;
+use data::{Data, DataRequest};
+use error::RequestError;
+use event::Event;
+use messages::{ExternalResponse, MessageId};
+use routing_client::RoutingClient;
+
+type Outcome = Result<ExternalResponse, RequestError>;
+type PendingMap = Arc<Mutex<HashMap<MessageId, mpsc::Sender<Outcome>>>>;
+
+/// RequestHandle is returned for every request sent through a BlockingClient and resolves to
+/// the response for that request.  The request is forgotten once the handle is dropped, so a
+/// response arriving later is discarded.
+pub struct RequestHandle {
+    message_id: MessageId,
+    receiver: mpsc::Receiver<Outcome>,
+    pending: PendingMap,
+}
+
+impl RequestHandle {
+    /// The MessageId of the request.
+    pub fn message_id(&self) -> MessageId {
+        self.message_id
+    }
+
+    /// Return the outcome of the request if it is known, without blocking.
+    pub fn try_get(&self) -> Option<Outcome> {
+        match self.receiver.try_recv() {
+            Ok(outcome) => Some(outcome),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(RequestError::Terminated)),
+        }
+    }
+
+    /// Block until the outcome of the request is known, or the timeout has passed.
+    pub fn wait(self, timeout: ::time::Duration) -> Outcome {
+        let time = ::time::SteadyTime::now();
+        loop {
+            match self.try_get() {
+                Some(outcome) => return outcome,
+                None => {},
+            };
+            if time + timeout < ::time::SteadyTime::now() {
+                debug!("Timed out waiting for response to {:?}", self.message_id);
+                return Err(RequestError::Timeout);
+            }
+            ::std::thread::sleep_ms(10);
+        }
+    }
+}
+
+impl Drop for RequestHandle {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            let _ = pending.remove(&self.message_id);
+        };
+    }
+}
+
+/// BlockingClient wraps a RoutingClient and matches the events of routing to the requests they
+/// answer.  Every request either returns a RequestHandle, or blocks until its outcome is known.
+/// Put, Post and Delete requests are only answered on failure, so their blocking variants wait
+/// for the full timeout and succeed unless a failure is reported within it.
+pub struct BlockingClient {
+    routing_client: RoutingClient,
+    pending: PendingMap,
+    bootstrapped: Arc<AtomicBool>,
+    timeout: ::time::Duration,
+}
+
+impl BlockingClient {
+    /// Take over a RoutingClient together with the receiver for its events.  The events are
+    /// consumed by the BlockingClient from now on.
+    pub fn new(routing_client: RoutingClient,
+               event_receiver: mpsc::Receiver<Event>) -> BlockingClient {
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let bootstrapped = Arc::new(AtomicBool::new(false));
+        let dispatch_pending = pending.clone();
+        let dispatch_bootstrapped = bootstrapped.clone();
+        let _ = spawn(move || {
+            dispatch(event_receiver, dispatch_pending, dispatch_bootstrapped);
+        });
+
+        BlockingClient {
+            routing_client: routing_client,
+            pending: pending,
+            bootstrapped: bootstrapped,
+            timeout: ::time::Duration::seconds(30),
+        }
+    }
+
+    /// Set the timeout used by the blocking requests.
+    pub fn set_timeout(&mut self, timeout: ::time::Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Return true if the client is bootstrapped onto the network.
+    pub fn is_bootstrapped(&self) -> bool {
+        self.bootstrapped.load(Ordering::SeqCst)
+    }
+
+    /// Block until the client has bootstrapped, returns false if it timed out.
+    pub fn wait_for_bootstrap(&self) -> bool {
+        let time = ::time::SteadyTime::now();
+        while !self.is_bootstrapped() {
+            if time + self.timeout < ::time::SteadyTime::now() {
+                debug!("Timed out waiting for bootstrap");
+                return false;
+            }
+            ::std::thread::sleep_ms(10);
+        }
+        true
+    }
+
+    /// Send a Get request, the handle resolves to the Get response.
+    pub fn send_get(&mut self, location: Authority, data_request: DataRequest) -> RequestHandle {
+        let routing_client = &mut self.routing_client;
+        register(&self.pending, || routing_client.get_request(location, data_request))
+    }
+
+    /// Send a Put request, the handle only resolves if the request failed.
+    pub fn send_put(&mut self, location: Authority, data: Data) -> RequestHandle {
+        let routing_client = &self.routing_client;
+        register(&self.pending, || routing_client.put_request(location, data))
+    }
+
+    /// Send a Post request, the handle only resolves if the request failed.
+    pub fn send_post(&mut self, location: Authority, data: Data) -> RequestHandle {
+        let routing_client = &self.routing_client;
+        register(&self.pending, || routing_client.post_request(location, data))
+    }
+
+    /// Send a Delete request, the handle only resolves if the request failed.
+    pub fn send_delete(&mut self, location: Authority, data: Data) -> RequestHandle {
+        let routing_client = &self.routing_client;
+        register(&self.pending, || routing_client.delete_request(location, data))
+    }
+
+    /// Get data from the network, blocking until it arrives or the timeout has passed.
+    pub fn get(&mut self, location: Authority, data_request: DataRequest)
+            -> Result<Data, RequestError> {
+        let timeout = self.timeout;
+        match try!(self.send_get(location, data_request).wait(timeout)) {
+            ExternalResponse::Get(data, _, _) => Ok(data),
+            ExternalResponse::Put(error, _) | ExternalResponse::Post(error, _) |
+                ExternalResponse::Delete(error, _) => Err(RequestError::Response(error)),
+        }
+    }
+
+    /// Put data onto the network, blocking for the timeout unless a failure is reported.  Returns
+    /// Ok if no failure was reported within the timeout.
+    pub fn put(&mut self, location: Authority, data: Data) -> Result<(), RequestError> {
+        let handle = self.send_put(location, data);
+        self.wait_for_failure(handle)
+    }
+
+    /// Post data to the network, blocking for the timeout unless a failure is reported.  Returns
+    /// Ok if no failure was reported within the timeout.
+    pub fn post(&mut self, location: Authority, data: Data) -> Result<(), RequestError> {
+        let handle = self.send_post(location, data);
+        self.wait_for_failure(handle)
+    }
+
+    /// Delete data from the network, blocking for the timeout unless a failure is reported.
+    /// Returns Ok if no failure was reported within the timeout.
+    pub fn delete(&mut self, location: Authority, data: Data) -> Result<(), RequestError> {
+        let handle = self.send_delete(location, data);
+        self.wait_for_failure(handle)
+    }
+
+    /// Stop the underlying RoutingClient.  Pending handles resolve to RequestError::Terminated.
+    pub fn stop(&mut self) {
+        self.routing_client.stop();
+    }
+
+    fn wait_for_failure(&self, handle: RequestHandle) -> Result<(), RequestError> {
+        failure_outcome(handle.wait(self.timeout))
+    }
+}
+
+// Put, Post and Delete requests are only answered on failure, so no answer within the timeout
+// means the request succeeded.
+fn failure_outcome(outcome: Outcome) -> Result<(), RequestError> {
+    match outcome {
+        Ok(ExternalResponse::Put(error, _)) | Ok(ExternalResponse::Post(error, _)) |
+            Ok(ExternalResponse::Delete(error, _)) => Err(RequestError::Response(error)),
+        Ok(response) => Err(RequestError::UnexpectedResponse(response)),
+        Err(RequestError::Timeout) => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+// The lock is held while the request is sent, so the dispatcher can not see its response
+// before the request is registered.
+fn register<F>(pending: &PendingMap, send_request: F) -> RequestHandle
+        where F: FnOnce() -> MessageId {
+    let (sender, receiver) = mpsc::channel();
+    let mut pending_requests = pending.lock().unwrap();
+    let message_id = send_request();
+    let _ = pending_requests.insert(message_id, sender);
+    RequestHandle { message_id: message_id, receiver: receiver, pending: pending.clone() }
+}
+
+fn resolve(pending: &PendingMap, message_id: &MessageId, outcome: Outcome) {
+    match pending.lock().unwrap().remove(message_id) {
+        Some(sender) => { let _ = sender.send(outcome); },
+        None => debug!("No request pending for {:?}", message_id),
+    }
+}
+
+fn dispatch(event_receiver: mpsc::Receiver<Event>, pending: PendingMap,
+            bootstrapped: Arc<AtomicBool>) {
+    while let Ok(event) = event_receiver.recv() {
+        debug!("BlockingClient received routing event: {:?}", event);
+        match event {
+            Event::Response{ response, message_id, .. } =>
+                resolve(&pending, &message_id, Ok(response)),
+            Event::FailedRequest{ interface_error, message_id, .. } =>
+                resolve(&pending, &message_id, Err(RequestError::Interface(interface_error))),
+            Event::RequestTimeout{ message_id, .. } =>
+                resolve(&pending, &message_id, Err(RequestError::Timeout)),
+            Event::Bootstrapped => bootstrapped.store(true, Ordering::SeqCst),
+            Event::Disconnected => bootstrapped.store(false, Ordering::SeqCst),
+            Event::Terminated => break,
+            _ => {},
+        };
+    }
+    bootstrapped.store(false, Ordering::SeqCst);
+    // dropping the senders resolves all pending handles to RequestError::Terminated
+    pending.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc;
+
+    use data::{Data, DataRequest};
+    use error::{InterfaceError, RequestError};
+    use event::Event;
+    use messages::{ExternalResponse, MessageId};
+    use plain_data::PlainData;
+    use authority::Authority;
+    use NameType;
+
+    fn start_dispatch() -> (mpsc::Sender<Event>, super::PendingMap, Arc<AtomicBool>) {
+        let (event_sender, event_receiver) = mpsc::channel::<Event>();
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let bootstrapped = Arc::new(AtomicBool::new(false));
+        let dispatch_pending = pending.clone();
+        let dispatch_bootstrapped = bootstrapped.clone();
+        let _ = ::std::thread::spawn(move || {
+            super::dispatch(event_receiver, dispatch_pending, dispatch_bootstrapped);
+        });
+        (event_sender, pending, bootstrapped)
+    }
+
+    #[test]
+    fn responses_resolve_matching_handles() {
+        let (event_sender, pending, _) = start_dispatch();
+        let first_id = MessageId::new();
+        let second_id = MessageId::new();
+        let first = super::register(&pending, || first_id);
+        let second = super::register(&pending, || second_id);
+
+        let name = NameType::new([3u8; 64]);
+        let data = Data::PlainData(PlainData::new(name.clone(), vec![1u8]));
+        let response = ExternalResponse::Get(data, DataRequest::PlainData(name.clone()), None);
+        let _ = event_sender.send(Event::Response {
+            response: response.clone(),
+            our_authority: Authority::NaeManager(name.clone()),
+            from_authority: Authority::NaeManager(name.clone()),
+            message_id: second_id,
+        });
+        let _ = event_sender.send(Event::FailedRequest {
+            request: ::messages::ExternalRequest::Get(DataRequest::PlainData(name.clone()), 0u8),
+            our_authority: None,
+            location: Authority::NaeManager(name),
+            interface_error: InterfaceError::NotConnected,
+            message_id: first_id,
+        });
+
+        let timeout = ::time::Duration::seconds(5);
+        assert_eq!(second.wait(timeout), Ok(response));
+        assert_eq!(first.wait(timeout), Err(RequestError::Interface(InterfaceError::NotConnected)));
+    }
+
+    #[test]
+    fn termination_resolves_pending_handles() {
+        let (event_sender, pending, _) = start_dispatch();
+        let message_id = MessageId::new();
+        let handle = super::register(&pending, || message_id);
+        assert!(handle.try_get().is_none());
+
+        let _ = event_sender.send(Event::Terminated);
+        assert_eq!(handle.wait(::time::Duration::seconds(5)), Err(RequestError::Terminated));
+    }
+
+    #[test]
+    fn unanswered_handle_times_out() {
+        let (_event_sender, pending, _) = start_dispatch();
+        let message_id = MessageId::new();
+        let handle = super::register(&pending, || message_id);
+        assert_eq!(handle.message_id(), message_id);
+        assert_eq!(handle.wait(::time::Duration::milliseconds(20)), Err(RequestError::Timeout));
+        assert!(pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unanswered_change_succeeds() {
+        let name = NameType::new([5u8; 64]);
+        let data = Data::PlainData(PlainData::new(name.clone(), vec![1u8]));
+        let get_response = ExternalResponse::Get(data, DataRequest::PlainData(name), None);
+        assert_eq!(super::failure_outcome(Err(RequestError::Timeout)), Ok(()));
+        assert_eq!(super::failure_outcome(Err(RequestError::Terminated)),
+                   Err(RequestError::Terminated));
+        assert_eq!(super::failure_outcome(Ok(get_response.clone())),
+                   Err(RequestError::UnexpectedResponse(get_response)));
+    }
+
+    #[test]
+    fn dropped_handle_is_forgotten() {
+        let (_event_sender, pending, _) = start_dispatch();
+        let handle = super::register(&pending, || MessageId::new());
+        assert_eq!(pending.lock().unwrap().len(), 1);
+        drop(handle);
+        assert!(pending.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crust::Endpoint;
+use error::RoutingError;
+
+/// The maximum number of endpoints kept in the cache.
+pub const MAX_BOOTSTRAP_CACHE_SIZE: usize = 200;
+
+/// BootstrapCache keeps the endpoints of our routing table peers in a file, so that a restarted
+/// node can bootstrap off peers it knew before, ahead of the contacts found by the transport.
+/// The most recently recorded endpoints are kept first.
+pub struct BootstrapCache {
+    path: PathBuf,
+    endpoints: Vec<Endpoint>,
+}
+
+impl BootstrapCache {
+    /// Construct a cache on the file at the given path, reading the endpoints it already holds.
+    /// A missing or unreadable file gives an empty cache.
+    pub fn new(path: PathBuf) -> BootstrapCache {
+        let endpoints = match BootstrapCache::read_file(&path) {
+            Ok(endpoints) => endpoints,
+            Err(e) => {
+                debug!("No bootstrap cache read from {:?}: {:?}", path, e);
+                Vec::new()
+            },
+        };
+        BootstrapCache { path: path, endpoints: endpoints }
+    }
+
+    /// The cached endpoints, most recently recorded first.
+    pub fn endpoints(&self) -> Vec<Endpoint> {
+        self.endpoints.clone()
+    }
+
+    /// Record the given endpoints ahead of the ones already cached, and write the cache to its
+    /// file if it changed.
+    pub fn update(&mut self, endpoints: Vec<Endpoint>) -> Result<(), RoutingError> {
+        let mut updated = Vec::new();
+        for endpoint in endpoints.into_iter().chain(self.endpoints.iter().cloned()) {
+            if !updated.contains(&endpoint) {
+                updated.push(endpoint);
+            }
+        }
+        updated.truncate(MAX_BOOTSTRAP_CACHE_SIZE);
+        if updated == self.endpoints {
+            return Ok(())
+        };
+        self.endpoints = updated;
+        let encoded = try!(::utils::encode(&self.endpoints));
+        let mut file = try!(::std::fs::File::create(&self.path));
+        try!(file.write_all(&encoded));
+        Ok(())
+    }
+
+    fn read_file(path: &PathBuf) -> Result<Vec<Endpoint>, RoutingError> {
+        let mut file = try!(::std::fs::File::open(path));
+        let mut contents = Vec::new();
+        let _ = try!(file.read_to_end(&mut contents));
+        Ok(try!(::utils::decode::<Vec<Endpoint>>(&contents)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_utils::test::random_endpoint;
+
+    fn temp_path() -> ::std::path::PathBuf {
+        ::std::env::temp_dir().join(format!("routing_bootstrap_cache_{}",
+            ::rand::random::<u64>()))
+    }
+
+    #[test]
+    fn update_and_reload() {
+        let path = temp_path();
+        let mut cache = super::BootstrapCache::new(path.clone());
+        assert!(cache.endpoints().is_empty());
+
+        let first = random_endpoint();
+        let second = random_endpoint();
+        assert!(cache.update(vec![first.clone()]).is_ok());
+        assert!(cache.update(vec![second.clone(), first.clone()]).is_ok());
+        assert_eq!(cache.endpoints(), vec![second.clone(), first.clone()]);
+
+        let reloaded = super::BootstrapCache::new(path.clone());
+        let _ = ::std::fs::remove_file(&path);
+        assert_eq!(reloaded.endpoints(), vec![second, first]);
+    }
+
+    #[test]
+    fn keeps_most_recent_endpoints() {
+        let path = temp_path();
+        let mut cache = super::BootstrapCache::new(path.clone());
+        let old_endpoints = (0..super::MAX_BOOTSTRAP_CACHE_SIZE)
+            .map(|_| random_endpoint()).collect::<Vec<_>>();
+        assert!(cache.update(old_endpoints.clone()).is_ok());
+        let recent = random_endpoint();
+        assert!(cache.update(vec![recent.clone()]).is_ok());
+        let _ = ::std::fs::remove_file(&path);
+
+        let endpoints = cache.endpoints();
+        assert_eq!(endpoints.len(), super::MAX_BOOTSTRAP_CACHE_SIZE);
+        assert_eq!(endpoints[0], recent);
+        assert!(!endpoints.contains(&old_endpoints[super::MAX_BOOTSTRAP_CACHE_SIZE - 1]));
+    }
+}
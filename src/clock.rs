@@ -0,0 +1,105 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! The clock of the timers and timestamps of routing.  It follows the system clocks, unless a
+//! simulation drives a virtual clock on the current thread.
+
+use std::cell::Cell;
+
+use time::{Duration, SteadyTime};
+
+// The steady time and the timestamp the virtual clock started at, and the virtual milliseconds
+// elapsed since.
+#[derive(Clone, Copy)]
+struct VirtualClock {
+    start: SteadyTime,
+    start_timestamp: i64,
+    elapsed: i64,
+}
+
+thread_local!(static VIRTUAL_CLOCK: Cell<Option<VirtualClock>> = Cell::new(None));
+
+/// The current steady time.
+pub fn now() -> SteadyTime {
+    VIRTUAL_CLOCK.with(|clock| match clock.get() {
+        Some(clock) => clock.start + Duration::milliseconds(clock.elapsed),
+        None => SteadyTime::now(),
+    })
+}
+
+/// The current time in milliseconds since the epoch.
+pub fn timestamp() -> i64 {
+    VIRTUAL_CLOCK.with(|clock| match clock.get() {
+        Some(clock) => clock.start_timestamp + clock.elapsed,
+        None => system_timestamp(),
+    })
+}
+
+/// Drive the clock of the current thread virtually, starting from the system clocks.  Time then
+/// only passes through `set_virtual_elapsed`.
+pub fn start_virtual() {
+    VIRTUAL_CLOCK.with(|clock| clock.set(Some(VirtualClock {
+        start: SteadyTime::now(),
+        start_timestamp: system_timestamp(),
+        elapsed: 0,
+    })));
+}
+
+/// Set the virtual milliseconds elapsed since `start_virtual`.  Does nothing if the clock of the
+/// current thread isn't virtual.
+pub fn set_virtual_elapsed(elapsed: u64) {
+    VIRTUAL_CLOCK.with(|clock| match clock.get() {
+        Some(mut virtual_clock) => {
+            virtual_clock.elapsed = elapsed as i64;
+            clock.set(Some(virtual_clock));
+        },
+        None => {},
+    })
+}
+
+/// Follow the system clocks again on the current thread.
+pub fn stop_virtual() {
+    VIRTUAL_CLOCK.with(|clock| clock.set(None));
+}
+
+fn system_timestamp() -> i64 {
+    let now = ::time::get_time();
+    now.sec * 1000 + (now.nsec / 1_000_000) as i64
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn virtual_clock_only_moves_when_set() {
+        super::start_virtual();
+        let start = super::now();
+        let start_timestamp = super::timestamp();
+        ::std::thread::sleep_ms(5);
+        assert_eq!(super::now(), start);
+        assert_eq!(super::timestamp(), start_timestamp);
+
+        super::set_virtual_elapsed(1500u64);
+        assert_eq!(super::now() - start, ::time::Duration::milliseconds(1500));
+        assert_eq!(super::timestamp(), start_timestamp + 1500);
+
+        // other threads keep following the system clock
+        let other = ::std::thread::spawn(move || super::now() > start).join().unwrap();
+        assert!(other);
+        super::stop_virtual();
+        assert!(super::now() > start);
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use rustc_serialize::json;
+
+use error::RoutingError;
+use routing_builder::RoutingBuilder;
+use types::{CacheOptions, ExpiryDurations, RoutingConfig};
+
+/// The longest expiry duration accepted, one year in seconds.
+pub const MAX_EXPIRY_SECONDS: u64 = 365 * 24 * 60 * 60;
+
+/// ConfigFile holds the deployment settings of a node, stored as JSON.  All fields except
+/// log_filter have to be present in the file; writing ConfigFile::new() gives a complete file to
+/// start from.
+#[derive(PartialEq, Eq, Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct ConfigFile {
+    /// Socket addresses to bootstrap off, e.g. "192.168.0.1:5483".  If empty, crust finds its own
+    /// bootstrap contacts.
+    pub bootstrap_contacts: Vec<String>,
+    /// File to keep the endpoints of routing table peers in, to bootstrap off them first on the
+    /// next start.  If absent, no bootstrap cache is kept.
+    pub bootstrap_cache_file: Option<String>,
+    /// TCP ports to accept connections on.  If empty, the default ports are used.
+    pub listening_ports: Vec<u16>,
+    /// Caching behaviour of the node.
+    pub cache_options: CacheOptions,
+    /// Expiry duration of the message filter in seconds.
+    pub filter_expiry_seconds: u64,
+    /// Expiry duration of the message accumulator in seconds.
+    pub message_accumulator_expiry_seconds: u64,
+    /// Expiry duration of the refresh accumulator in seconds.
+    pub refresh_accumulator_expiry_seconds: u64,
+    /// Expiry duration of the public id cache in seconds.
+    pub public_id_cache_expiry_seconds: u64,
+    /// Leading zero bits of work a joining node has to prove for its public id, at most
+    /// MAX_ADMISSION_DIFFICULTY.  All nodes of a network should use the same difficulty.
+    pub admission_difficulty: u8,
+    /// Log filter in the format of RUST_LOG, e.g. "routing=debug".  If absent, RUST_LOG applies.
+    pub log_filter: Option<String>,
+}
+
+impl ConfigFile {
+
+    /// Construct with the settings a node uses when no configuration is given.
+    pub fn new() -> ConfigFile {
+        let expiry_durations = ExpiryDurations::default_durations();
+        ConfigFile {
+            bootstrap_contacts: Vec::new(),
+            bootstrap_cache_file: None,
+            listening_ports: Vec::new(),
+            cache_options: CacheOptions::no_caching(),
+            filter_expiry_seconds: expiry_durations.filter().num_seconds() as u64,
+            message_accumulator_expiry_seconds:
+                expiry_durations.message_accumulator().num_seconds() as u64,
+            refresh_accumulator_expiry_seconds:
+                expiry_durations.refresh_accumulator().num_seconds() as u64,
+            public_id_cache_expiry_seconds:
+                expiry_durations.public_id_cache().num_seconds() as u64,
+            admission_difficulty: RoutingConfig::default_config().admission_difficulty(),
+            log_filter: None,
+        }
+    }
+
+    /// Read the configuration from the file at the given path.
+    pub fn read_file(path: &Path) -> Result<ConfigFile, RoutingError> {
+        let mut file = try!(::std::fs::File::open(path));
+        let mut contents = String::new();
+        let _ = try!(file.read_to_string(&mut contents));
+        Ok(try!(json::decode(&contents)))
+    }
+
+    /// Write the configuration to the file at the given path, replacing its content.
+    pub fn write_file(&self, path: &Path) -> Result<(), RoutingError> {
+        let mut file = try!(::std::fs::File::create(path));
+        try!(file.write_all(format!("{}", json::as_pretty_json(self)).as_bytes()));
+        Ok(())
+    }
+
+    /// Return the expiry durations of the configuration.  Fails if one exceeds
+    /// MAX_EXPIRY_SECONDS.
+    pub fn expiry_durations(&self) -> Result<ExpiryDurations, RoutingError> {
+        Ok(ExpiryDurations::new(try!(expiry_duration(self.filter_expiry_seconds)),
+            try!(expiry_duration(self.message_accumulator_expiry_seconds)),
+            try!(expiry_duration(self.refresh_accumulator_expiry_seconds)),
+            try!(expiry_duration(self.public_id_cache_expiry_seconds))))
+    }
+
+    /// Return a RoutingBuilder with the settings of the configuration.  Fails if a bootstrap
+    /// contact is not a valid socket address, an expiry duration is too long or the admission
+    /// difficulty too high.
+    pub fn builder(&self) -> Result<RoutingBuilder, RoutingError> {
+        let mut bootstrap_endpoints = Vec::new();
+        for contact in self.bootstrap_contacts.iter() {
+            bootstrap_endpoints.push(::crust::Endpoint::Tcp(
+                try!(contact.parse::<::std::net::SocketAddr>())));
+        }
+        let listening_ports = self.listening_ports.iter()
+            .map(|port| ::crust::Port::Tcp(*port))
+            .collect::<Vec<::crust::Port>>();
+
+        let routing_config = try!(RoutingConfig::default_config()
+            .with_admission_difficulty(self.admission_difficulty));
+        let builder = RoutingBuilder::new()
+            .routing_config(routing_config)
+            .bootstrap_endpoints(bootstrap_endpoints)
+            .listening_ports(listening_ports)
+            .cache_options(self.cache_options.clone())
+            .expiry_durations(try!(self.expiry_durations()));
+        Ok(match self.bootstrap_cache_file {
+            Some(ref bootstrap_cache_file) => builder.bootstrap_cache_file(bootstrap_cache_file),
+            None => builder,
+        })
+    }
+
+    /// Initialise logging, using the log filter of the configuration if one is given and
+    /// RUST_LOG otherwise.  The environment of the process is left untouched.  Fails if a logger
+    /// has been set already.
+    pub fn init_logging(&self) -> Result<(), ::log::SetLoggerError> {
+        let mut builder = ::env_logger::LogBuilder::new();
+        match self.log_filter {
+            Some(ref log_filter) => { let _ = builder.parse(log_filter); },
+            None => match ::std::env::var("RUST_LOG") {
+                Ok(rust_log) => { let _ = builder.parse(&rust_log); },
+                Err(_) => {},
+            },
+        };
+        builder.init()
+    }
+}
+
+fn expiry_duration(seconds: u64) -> Result<::time::Duration, RoutingError> {
+    if seconds > MAX_EXPIRY_SECONDS {
+        return Err(RoutingError::InvalidConfig("expiry duration too long"));
+    };
+    Ok(::time::Duration::seconds(seconds as i64))
+}
+
+#[cfg(test)]
+mod test {
+    use types::{CacheOptions, ExpiryDurations};
+
+    #[test]
+    fn write_and_read_file() {
+        let mut config = super::ConfigFile::new();
+        config.bootstrap_contacts = vec![String::from("127.0.0.1:5483")];
+        config.bootstrap_cache_file = Some(String::from("bootstrap.cache"));
+        config.listening_ports = vec![5483u16];
+        config.cache_options = CacheOptions::with_caching(true, false, true);
+        config.filter_expiry_seconds = 60u64;
+        config.admission_difficulty = 16u8;
+        config.log_filter = Some(String::from("routing=debug"));
+
+        let path = ::std::env::temp_dir().join(format!("routing_config_{}.json",
+            ::rand::random::<u64>()));
+        assert!(config.write_file(&path).is_ok());
+        let read_config = super::ConfigFile::read_file(&path);
+        let _ = ::std::fs::remove_file(&path);
+        assert_eq!(read_config.unwrap(), config);
+    }
+
+    #[test]
+    fn expiry_durations() {
+        let config = super::ConfigFile::new();
+        assert_eq!(config.expiry_durations().unwrap(), ExpiryDurations::default_durations());
+    }
+
+    #[test]
+    fn too_long_expiry_duration() {
+        let mut config = super::ConfigFile::new();
+        config.refresh_accumulator_expiry_seconds = ::std::u64::MAX;
+        assert!(config.expiry_durations().is_err());
+        match config.builder() {
+            Err(::error::RoutingError::InvalidConfig(_)) => {},
+            _ => panic!("Expected an invalid configuration error"),
+        };
+        config.refresh_accumulator_expiry_seconds = super::MAX_EXPIRY_SECONDS;
+        assert!(config.builder().is_ok());
+    }
+
+    #[test]
+    fn too_high_admission_difficulty() {
+        let mut config = super::ConfigFile::new();
+        config.admission_difficulty = ::types::MAX_ADMISSION_DIFFICULTY + 1;
+        match config.builder() {
+            Err(::error::RoutingError::InvalidConfig(_)) => {},
+            _ => panic!("Expected an invalid configuration error"),
+        };
+        config.admission_difficulty = ::types::MAX_ADMISSION_DIFFICULTY;
+        assert!(config.builder().is_ok());
+    }
+
+    #[test]
+    fn invalid_contact() {
+        let mut config = super::ConfigFile::new();
+        assert!(config.builder().is_ok());
+        config.bootstrap_contacts = vec![String::from("not an address")];
+        match config.builder() {
+            Err(::error::RoutingError::AddrParse(_)) => {},
+            _ => panic!("Expected an address parse error"),
+        };
+    }
+}
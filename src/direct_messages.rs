@@ -21,10 +21,28 @@
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct Hello {
+    /// The newest protocol version the sender speaks.
+    pub version: u8,
+    /// The oldest protocol version the sender speaks.
+    pub min_version: u8,
     pub address: ::types::Address,
     pub public_id: ::public_id::PublicId,
     pub confirmed_you: Option<::types::Address>,
     pub expected_connection: Option<::routing_core::ExpectedConnection>,
+    /// The ephemeral key from which the session key of this connection is derived.
+    pub ephemeral_key: ::sodiumoxide::crypto::box_::PublicKey,
+}
+
+impl Hello {
+    /// Return whether both hellos announce the same peer in the same way, regardless of the
+    /// connection they were sent on.
+    pub fn matches(&self, other: &Hello) -> bool {
+        self.version == other.version && self.min_version == other.min_version &&
+            self.address == other.address &&
+            self.public_id == other.public_id &&
+            self.confirmed_you == other.confirmed_you &&
+            self.expected_connection == other.expected_connection
+    }
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -37,6 +55,9 @@ pub struct Churn {
 pub enum Content {
     Hello(Hello),
     Churn(Churn),
+    /// A liveness probe carrying a nonce, to be answered with a Pong of the same nonce.
+    Ping(u64),
+    Pong(u64),
 }
 
 
@@ -95,10 +116,13 @@ mod test {
         let public_id: ::public_id::PublicId = rand::random();
         let none_address: Option<::types::Address> = None;
         let hello = ::direct_messages::Hello {
+            version:       ::types::PROTOCOL_VERSION,
+            min_version:   ::types::MIN_PROTOCOL_VERSION,
             address:       address,
             public_id:     public_id,
             confirmed_you: none_address,
             expected_connection: None,
+            ephemeral_key: ::sodiumoxide::crypto::box_::gen_keypair().0,
         };
         let content = ::direct_messages::Content::Hello(hello);
         let key = ::sodiumoxide::crypto::sign::gen_keypair();
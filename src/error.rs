@@ -105,6 +105,71 @@ impl ::std::fmt::Display for InterfaceError {
     }
 }
 
+//------------------------------------------------------------------------------
+#[derive(PartialEq, Eq, Clone, Debug)]
+/// RequestError reports why a request made through a BlockingClient produced no response.
+pub enum RequestError {
+    /// No response arrived in time.
+    Timeout,
+    /// The request could not be sent.
+    Interface(InterfaceError),
+    /// The network responded with an error.
+    Response(ResponseError),
+    /// The network responded with a response of the wrong kind for the request.
+    UnexpectedResponse(::messages::ExternalResponse),
+    /// The client has terminated.
+    Terminated,
+}
+
+impl From<InterfaceError> for RequestError {
+    fn from(error: InterfaceError) -> RequestError {
+        RequestError::Interface(error)
+    }
+}
+
+impl From<ResponseError> for RequestError {
+    fn from(error: ResponseError) -> RequestError {
+        RequestError::Response(error)
+    }
+}
+
+impl ::std::error::Error for RequestError {
+    fn description(&self) -> &str {
+        match *self {
+            RequestError::Timeout => "Timed out",
+            RequestError::Interface(_) => "Interface error",
+            RequestError::Response(_) => "Response error",
+            RequestError::UnexpectedResponse(_) => "Unexpected response",
+            RequestError::Terminated => "Terminated",
+        }
+    }
+
+    fn cause(&self) -> Option<&::std::error::Error> {
+        match *self {
+            RequestError::Interface(ref err) => Some(err),
+            RequestError::Response(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl ::std::fmt::Display for RequestError {
+    fn fmt(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            RequestError::Timeout =>
+                ::std::fmt::Display::fmt("RequestError::Timeout", formatter),
+            RequestError::Interface(ref error) =>
+                ::std::fmt::Display::fmt(error, formatter),
+            RequestError::Response(ref error) =>
+                ::std::fmt::Display::fmt(error, formatter),
+            RequestError::UnexpectedResponse(ref response) =>
+                formatter.write_str(&format!("RequestError::UnexpectedResponse({:?})", response)),
+            RequestError::Terminated =>
+                ::std::fmt::Display::fmt("RequestError::Terminated", formatter),
+        }
+    }
+}
+
 //------------------------------------------------------------------------------
 /// ClientError.
 pub enum ClientError {
@@ -141,12 +206,28 @@ pub enum RoutingError {
     UnknownMessageType,
     /// Failed signature check
     FailedSignature,
+    /// encrypted content could not be opened with our key
+    FailedDecryption,
+    /// bytes sent or received on a connection before its session was established
+    NoSession,
+    /// session frame received before
+    ReplayedFrame,
+    /// peer announced a different session key on an established connection
+    SessionKeyChanged,
     /// Not Enough signatures
     NotEnoughSignatures,
     /// Duplicate signatures
     DuplicateSignatures,
     /// duplicate request received
     FilterCheckFailed,
+    /// message timestamp out of the accepted window, or message signature seen before
+    ReplayedMessage,
+    /// claimant sent more messages than its rate allows
+    RateExceeded,
+    /// peer or message of a protocol version we no longer understand
+    IncompatibleVersion(u8),
+    /// joining node did not prove the required work for its public id
+    InsufficientProofOfWork,
     /// failure to bootstrap off the provided endpoints
     FailedToBootstrap,
     /// unexpected empty routing table
@@ -168,6 +249,12 @@ pub enum RoutingError {
     Cbor(::cbor::CborError),
     /// invalid response
     Response(ResponseError),
+    /// configuration file could not be decoded
+    Json(::rustc_serialize::json::DecoderError),
+    /// invalid socket address
+    AddrParse(::std::net::AddrParseError),
+    /// configuration values that can not work together
+    InvalidConfig(&'static str),
 }
 
 impl From<::std::str::Utf8Error> for RoutingError {
@@ -201,6 +288,18 @@ impl From<InterfaceError> for RoutingError {
     }
 }
 
+impl From<::rustc_serialize::json::DecoderError> for RoutingError {
+    fn from(error: ::rustc_serialize::json::DecoderError) -> RoutingError {
+        RoutingError::Json(error)
+    }
+}
+
+impl From<::std::net::AddrParseError> for RoutingError {
+    fn from(error: ::std::net::AddrParseError) -> RoutingError {
+        RoutingError::AddrParse(error)
+    }
+}
+
 impl ::std::error::Error for RoutingError {
     fn description(&self) -> &str {
         match *self {
@@ -209,7 +308,15 @@ impl ::std::error::Error for RoutingError {
             RoutingError::AlreadyConnected => "Already connected",
             RoutingError::UnknownMessageType => "Invalid message type",
             RoutingError::FilterCheckFailed => "Filter check failure",
+            RoutingError::ReplayedMessage => "Stale or replayed message",
+            RoutingError::RateExceeded => "Message rate exceeded",
+            RoutingError::IncompatibleVersion(_) => "Incompatible protocol version",
+            RoutingError::InsufficientProofOfWork => "Insufficient proof of work",
             RoutingError::FailedSignature => "Signature check failure",
+            RoutingError::FailedDecryption => "Decryption failure",
+            RoutingError::NoSession => "No session established",
+            RoutingError::ReplayedFrame => "Replayed session frame",
+            RoutingError::SessionKeyChanged => "Session key changed",
             RoutingError::NotEnoughSignatures => "Not enough signatures",
             RoutingError::DuplicateSignatures => "Duplicated signatures",
             RoutingError::FailedToBootstrap => "Could not bootstrap",
@@ -222,6 +329,9 @@ impl ::std::error::Error for RoutingError {
             RoutingError::Io(_) => "I/O error",
             RoutingError::Cbor(_) => "Serialisation error",
             RoutingError::Response(_) => "Response error",
+            RoutingError::Json(_) => "Json decoding error",
+            RoutingError::AddrParse(_) => "Invalid socket address",
+            RoutingError::InvalidConfig(_) => "Invalid configuration",
         }
     }
 
@@ -231,6 +341,8 @@ impl ::std::error::Error for RoutingError {
             RoutingError::Io(ref err) => Some(err),
             // RoutingError::Cbor(ref err) => Some(err),
             RoutingError::Response(ref err) => Some(err),
+            RoutingError::Json(ref err) => Some(err),
+            RoutingError::AddrParse(ref err) => Some(err),
             _ => None,
         }
     }
@@ -249,8 +361,25 @@ impl ::std::fmt::Display for RoutingError {
                 ::std::fmt::Display::fmt("Unknown message", formatter),
             RoutingError::FilterCheckFailed =>
                 ::std::fmt::Display::fmt("Filter check failed", formatter),
+            RoutingError::ReplayedMessage =>
+                ::std::fmt::Display::fmt("Stale or replayed message", formatter),
+            RoutingError::RateExceeded =>
+                ::std::fmt::Display::fmt("Message rate exceeded", formatter),
+            RoutingError::InsufficientProofOfWork =>
+                ::std::fmt::Display::fmt("Insufficient proof of work", formatter),
+            RoutingError::IncompatibleVersion(ref version) =>
+                ::std::fmt::Display::fmt(&format!("Incompatible protocol version {}, we speak {}",
+                    version, ::types::PROTOCOL_VERSION), formatter),
             RoutingError::FailedSignature =>
                 ::std::fmt::Display::fmt("Signature check failed", formatter),
+            RoutingError::FailedDecryption =>
+                ::std::fmt::Display::fmt("Decryption failed", formatter),
+            RoutingError::NoSession =>
+                ::std::fmt::Display::fmt("No session established", formatter),
+            RoutingError::ReplayedFrame =>
+                ::std::fmt::Display::fmt("Replayed session frame", formatter),
+            RoutingError::SessionKeyChanged =>
+                ::std::fmt::Display::fmt("Session key changed", formatter),
             RoutingError::NotEnoughSignatures =>
                 ::std::fmt::Display::fmt("Not enough signatures (multi-sig)", formatter),
             RoutingError::DuplicateSignatures =>
@@ -275,6 +404,13 @@ impl ::std::fmt::Display for RoutingError {
                 ::std::fmt::Display::fmt(error, formatter),
             RoutingError::Response(ref error) =>
                 ::std::fmt::Display::fmt(error, formatter),
+            RoutingError::Json(ref error) =>
+                ::std::fmt::Display::fmt(error, formatter),
+            RoutingError::AddrParse(ref error) =>
+                ::std::fmt::Display::fmt(error, formatter),
+            RoutingError::InvalidConfig(reason) =>
+                ::std::fmt::Display::fmt(&format!("Invalid configuration: {}", reason),
+                    formatter),
         }
     }
 }
@@ -421,6 +557,10 @@ mod test {
             "Filter check failure",
             ::std::error::Error::description(& ::error::RoutingError::FilterCheckFailed)
         );
+        assert_eq!(
+            "Stale or replayed message",
+            ::std::error::Error::description(& ::error::RoutingError::ReplayedMessage)
+        );
         assert_eq!(
             "Signature check failure",
             ::std::error::Error::description(& ::error::RoutingError::FailedSignature)
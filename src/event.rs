@@ -16,7 +16,7 @@
 // relating to use of the SAFE Network Software.
 
 use authority::Authority;
-use messages::{ExternalRequest, ExternalResponse, SignedToken};
+use messages::{ExternalRequest, ExternalResponse, SignedToken, MessageId};
 use error::InterfaceError;
 
 /// An Event is received at the effective close group of B of a message flow < A | B >
@@ -32,6 +32,8 @@ pub enum Event {
         from_authority: Authority,
         /// Not set when the request came from a group.
         response_token: Option<SignedToken>, 
+        /// Identifies the request, to be passed back with the response.
+        message_id: MessageId,
     },
     /// Response.
     Response {
@@ -41,6 +43,8 @@ pub enum Event {
         our_authority: Authority,
         /// From authority.
         from_authority: Authority,
+        /// MessageId of the request this is a response to.
+        message_id: MessageId,
     },
     /// FailedRequest.
     FailedRequest {
@@ -52,6 +56,8 @@ pub enum Event {
         location: Authority,
         /// Interface error.
         interface_error: InterfaceError,
+        /// MessageId of the failed request.
+        message_id: MessageId,
     },
     /// FailedResponse.
     FailedResponse {
@@ -63,6 +69,18 @@ pub enum Event {
         location: Authority,
         /// Interface error.
         interface_error: InterfaceError,
+        /// MessageId of the failed response.
+        message_id: MessageId,
+    },
+    /// RequestTimeout reports that no response arrived for a Get request of a client, after it has
+    /// been retried as often as the RequestPolicy of the client allows.
+    RequestTimeout {
+        /// External request.
+        request: ExternalRequest,
+        /// Location the request was sent to.
+        location: Authority,
+        /// MessageId of the request.
+        message_id: MessageId,
     },
     /// Refresh reports to the user the collected accounts for a given refresh event,
     /// the arguments are type_tag:u64, authority: Authority, vector_of_bytes: Vec<Vec<u8>>
@@ -78,8 +96,15 @@ pub enum Event {
     /// also accumulated a DoRefresh indicates precisely one account routing will expect the
     /// user to do a ::routing::request_refresh for, if a matching account is held by the user.
     DoRefresh(u64, ::authority::Authority, ::NameType),
+    /// IncompatiblePeer reports that a peer introduced itself with protocol versions we have none
+    /// in common with, and has been disconnected.  Arguments are the peer's address and newest
+    /// protocol version.
+    IncompatiblePeer(::types::Address, u8),
     /// Bootstrapped.
     Bootstrapped,
+    /// None of the configured bootstrap endpoints could be reached, so the node bootstraps off
+    /// the contacts the transport finds on its own.
+    BootstrapFailed,
     /// Connected.
     Connected,
     /// Disconnected.
@@ -91,25 +116,36 @@ pub enum Event {
 impl ::std::fmt::Debug for Event {
     fn fmt(&self, formatter: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
         match self {
-            &Event::Request{ ref request, ref our_authority, ref from_authority, ref response_token } => {
+            &Event::Request{ ref request, ref our_authority, ref from_authority, ref response_token,
+                             ref message_id } => {
                 formatter.write_str(&format!("Request(request: {:?} , \
-                    our_authority: {:?} , from_authority: {:?}, response_token: {:?})",
-                    request, our_authority, from_authority, response_token))
+                    our_authority: {:?} , from_authority: {:?}, response_token: {:?} , \
+                    message_id: {:?})",
+                    request, our_authority, from_authority, response_token, message_id))
             }
-            &Event::Response{ ref response, ref our_authority, ref from_authority } => {
+            &Event::Response{ ref response, ref our_authority, ref from_authority,
+                              ref message_id } => {
                 formatter.write_str(&format!("Response(response: {:?} , \
-                    our_authority: {:?} , from_authority: {:?})",
-                    response, our_authority, from_authority))
+                    our_authority: {:?} , from_authority: {:?} , message_id: {:?})",
+                    response, our_authority, from_authority, message_id))
             }
-            &Event::FailedRequest{ ref request, ref our_authority, ref location, ref interface_error } => {
+            &Event::FailedRequest{ ref request, ref our_authority, ref location,
+                                   ref interface_error, ref message_id } => {
                 formatter.write_str(&format!("FailedRequest(request: {:?} , \
-                    our_authority: {:?} , location: {:?} , interface_error: {:?})",
-                    request, our_authority, location, interface_error))
+                    our_authority: {:?} , location: {:?} , interface_error: {:?} , \
+                    message_id: {:?})",
+                    request, our_authority, location, interface_error, message_id))
             }
-            &Event::FailedResponse{ ref response, ref our_authority, ref location, ref interface_error } => {
+            &Event::FailedResponse{ ref response, ref our_authority, ref location,
+                                    ref interface_error, ref message_id } => {
                 formatter.write_str(&format!("FailedResponse(response: {:?} , \
-                    our_authority: {:?} , location: {:?} , interface_error: {:?})",
-                    response, our_authority, location, interface_error))
+                    our_authority: {:?} , location: {:?} , interface_error: {:?} , \
+                    message_id: {:?})",
+                    response, our_authority, location, interface_error, message_id))
+            }
+            &Event::RequestTimeout{ ref request, ref location, ref message_id } => {
+                formatter.write_str(&format!("RequestTimeout(request: {:?} , location: {:?} , \
+                    message_id: {:?})", request, location, message_id))
             }
             &Event::Refresh(ref type_tag, ref target, ref payloads) => {
                 let _ = formatter.write_str(&format!("Refresh(type_tag: {:?} , target: {:?} , \
@@ -128,9 +164,16 @@ impl ::std::fmt::Debug for Event {
                 formatter.write_str(&format!("DoRefresh(type_tag: {:?} , target: {:?} , \
                     churn_node: {:?})", type_tag, target, churn_node))
             }
+            &Event::IncompatiblePeer(ref address, ref version) => {
+                formatter.write_str(&format!("IncompatiblePeer(address: {:?} , version: {:?})",
+                                             address, version))
+            }
             &Event::Bootstrapped => {
                 formatter.write_str(&format!("Bootstrapped"))
             }
+            &Event::BootstrapFailed => {
+                formatter.write_str(&format!("BootstrapFailed"))
+            }
             &Event::Connected => {
                 formatter.write_str(&format!("Connected"))
             }
@@ -15,52 +15,98 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-pub type ClaimantFilter = ::sodiumoxide::crypto::sign::Signature;
 pub type RoutingMessageFilter = ::sodiumoxide::crypto::hash::sha256::Digest;
 
+/// Messages are accepted within this many seconds of our clock, and of the newest message of
+/// their claimant.
+pub const REPLAY_WINDOW_SECONDS: i64 = 300;
+
+// At most this many claimants have the timestamp of their newest message remembered.
+const MAX_CLAIMANTS: usize = 10000;
+
 /// Filter combines a double filter.  The first layer validates that this exact message, as sent by
 /// the claimant has not been seen before.  The second layer validates that the routing message
 /// (which is content and source plus destination) is not already already resolved and as such
 /// should no longer be handled.
-#[allow(unused)]
 pub struct Filter {
-    claimant_filter: ::message_filter::MessageFilter<ClaimantFilter>,
+    window: ::time::Duration,
+    // the timestamp of the newest verified message of every claimant
+    claimants: ::lru_time_cache::LruCache<::types::Address, i64>,
+    signed_messages: ::message_filter::MessageFilter<RoutingMessageFilter>,
     message_filter: ::message_filter::MessageFilter<RoutingMessageFilter>,
-    threshold: SimpleThresholdCalculator,
 }
 
 impl Filter {
-    /// Set up a new filter with a exipry duration
+    /// Set up a new filter with a exipry duration and the default replay window.
     pub fn with_expiry_duration(duration: ::time::Duration) -> Filter {
+        Filter::with_replay_window(duration, ::time::Duration::seconds(REPLAY_WINDOW_SECONDS))
+    }
+
+    /// Set up a new filter with a exipry duration for resolved routing messages, accepting
+    /// messages within the given window.  A seen message is remembered for as long as its
+    /// timestamp can be accepted, which is up to twice the window.
+    pub fn with_replay_window(duration: ::time::Duration, window: ::time::Duration) -> Filter {
         Filter {
-            claimant_filter: ::message_filter::MessageFilter::with_expiry_duration(duration),
+            window: window,
+            claimants: ::lru_time_cache::LruCache::with_expiry_duration_and_capacity(
+                ::std::cmp::max(duration, window), MAX_CLAIMANTS),
+            signed_messages: ::message_filter::MessageFilter::with_expiry_duration(
+                ::std::cmp::max(duration, window * 2)),
             message_filter: ::message_filter::MessageFilter::with_expiry_duration(duration),
-            threshold: SimpleThresholdCalculator::new(10000u16, ::types::QUORUM_SIZE / 2 + 1 ),
         }
     }
 
-    /// Returns true if this message is to be processed.  It will check if the signature of the
-    /// message has been seen before, which filters on repeated signed messages.  If this is a new
-    /// signed message, it will store the signature to the filter.  Secondly the hash of the
-    /// contained routing message is calculated and checked.
-    pub fn check(&mut self, signed_message: &::messages::SignedMessage) -> bool {
-
-        // if the signature has been stored, we have processed this message before
-        if self.claimant_filter.check(signed_message.signature()) { return false; };
-        // add signature to filter
-        self.claimant_filter.add(signed_message.signature().clone());
+    /// Returns Ok if this message is to be processed.  A message whose timestamp is further
+    /// than the replay window from our clock, or older than the window before the newest verified
+    /// message of its claimant, is rejected as stale.  A message added before is filtered without
+    /// being reported as a replay; the same message may well arrive along several paths.
+    /// Secondly the hash of the contained routing message is calculated and checked.  The filter
+    /// is not changed, as the signature of the message has not been verified yet.
+    pub fn check(&mut self, signed_message: &::messages::SignedMessage)
+            -> Result<(), ::error::RoutingError> {
+        let window = self.window.num_milliseconds();
+        let timestamp = signed_message.timestamp();
+        if (::messages::current_timestamp() - timestamp).abs() > window {
+            return Err(::error::RoutingError::ReplayedMessage);
+        };
+        match self.claimants.get(signed_message.claimant()) {
+            Some(&newest) if timestamp < newest.saturating_sub(window) =>
+                return Err(::error::RoutingError::ReplayedMessage),
+            _ => {},
+        };
+        if self.signed_messages.check(&try!(signed_digest(signed_message))) {
+            return Err(::error::RoutingError::FilterCheckFailed);
+        };
 
         let digest = match ::utils::encode(signed_message.get_routing_message()) {
             Ok(bytes) => ::sodiumoxide::crypto::hash::sha256::hash(&bytes[..]),
-            Err(_) => return false,
+            Err(e) => return Err(::error::RoutingError::Cbor(e)),
+        };
+        if self.message_filter.check(&digest) {
+            return Err(::error::RoutingError::FilterCheckFailed);
+        };
+        Ok(())
+    }
+
+    /// Add the message, so that it is filtered when it arrives again.  Only this exact message,
+    /// body and signature, is filtered, so a forged copy can not hold back the genuine one.
+    pub fn add(&mut self, signed_message: &::messages::SignedMessage) {
+        match signed_digest(signed_message) {
+            Ok(digest) => self.signed_messages.add(digest),
+            Err(_) => {},
+        };
+    }
+
+    /// Move the replay window of the claimant up to the timestamp of the message.  Only a message
+    /// whose signature has been verified is to be recorded, or anyone could push the window of a
+    /// claimant past its genuine messages.
+    pub fn record(&mut self, signed_message: &::messages::SignedMessage) {
+        let claimant = signed_message.claimant();
+        let newest = match self.claimants.remove(claimant) {
+            Some(newest) => ::std::cmp::max(newest, signed_message.timestamp()),
+            None => signed_message.timestamp(),
         };
-        // already get the return value, but continue processing the analytics
-        let blocked = self.message_filter.check(&digest);
-        // TODO (ben 17/09/2015) the results from the threshold calculations are not yet used
-        // as such these are currently manually deactivated
-        // if signed_message.get_routing_message().from_authority.is_group() {
-        //     self.threshold.hit_message(blocked); };
-        !blocked
+        let _ = self.claimants.insert(claimant.clone(), newest);
     }
 
     /// Block adds the digest of the routing message to the message blocker.  A blocked message will
@@ -71,85 +117,16 @@ impl Filter {
             Ok(bytes) => ::sodiumoxide::crypto::hash::sha256::hash(&bytes[..]),
             Err(_) => return,
         };
-
-        // TODO (ben 17/09/2015) the results from the threshold calculations are not yet used
-        // as such these are currently manually deactivated
-        // if routing_message.from_authority.is_group()
-        //     && !self.message_filter.check(&digest) {
-        //     self.threshold.hit_uniquemessage();
-        // };
         self.message_filter.add(digest);
     }
 }
 
-#[allow(unused)]
-pub struct SimpleThresholdCalculator {
-    total_messages: u32,
-    total_blockedmessages: u32,
-    total_uniquemessages: u32,
-    blocked_percentage: RunningAverage,
-    multiplicity: RunningAverage,
-    cap: u32,
-    current_threshold: usize,
-}
-
-#[allow(unused)]
-impl SimpleThresholdCalculator {
-    /// Start a new calculator.
-    pub fn new(cap: u16, start_threshold: usize) -> SimpleThresholdCalculator {
-        SimpleThresholdCalculator {
-            total_messages: 0u32,
-            total_blockedmessages: 0u32,
-            total_uniquemessages: 0u32,
-            blocked_percentage: RunningAverage::new(10000u32),
-            multiplicity: RunningAverage::new(10000u32),
-            cap: cap as u32,
-            current_threshold: start_threshold,
-        }
-    }
-
-    /// Register a new blocked message.
-    pub fn hit_message(&mut self, blocked: bool) {
-        if blocked { self.total_blockedmessages += 1u32; };
-        self.total_messages += 1u32;
-        // let debug_average = self.total_blockedmessages as f64 / self.total_messages as f64;
-        // println!("BLOCKED {:?}% of group messages ({:?}/{:?})", (debug_average * 100f64).round(),
-        //     self.total_blockedmessages, self.total_messages);
-        if self.total_messages >= self.cap {
-            self.calculate_average();
-        }
-    }
-
-    /// Register a new unique message.
-    pub fn hit_uniquemessage(&mut self) {
-        self.total_uniquemessages += 1u32;
-        let message_multiplicity = self.total_messages as f64 / self.total_uniquemessages as f64;
-        // let debug_blocked = self.total_blockedmessages as f64 / self.total_messages as f64;
-        // let debug_threshold = message_multiplicity * debug_blocked;
-        // println!("MULTIPLICITY {:?} for group messages ({:?}/{:?})",
-        //     (message_multiplicity * 100f64).round() / 100f64,
-        //     self.total_messages, self.total_uniquemessages);
-        // println!("DEBUG THRESHOLD would be {:?} - NOT EFFECTUATED",
-        //     debug_threshold);
-        // println!("RUNNING AVERAGE BLOCKED {:?} - MULTIPLICITY {:?}",
-        //     self.blocked_percentage.get_average(),
-        //     self.multiplicity.get_average());
-    }
-
-    fn calculate_average(&mut self) {
-        let average_blocked: f64 = self.total_blockedmessages as f64
-            / self.total_messages as f64;
-        let running_average = self.blocked_percentage.add_value(average_blocked);
-
-        if self.total_uniquemessages > 0u32 {
-            let message_multiplicity = self.total_messages as f64
-                / self.total_uniquemessages as f64;
-            let running_multiplicity = self.multiplicity.add_value(message_multiplicity);
-        };
-        self.total_messages = 0u32;
-        self.total_blockedmessages = 0u32;
-        self.total_uniquemessages = 0u32;
-    }
+// The hash of the signed body and the signature of the message.
+fn signed_digest(signed_message: &::messages::SignedMessage)
+        -> Result<RoutingMessageFilter, ::error::RoutingError> {
+    let mut bytes = try!(signed_message.encoded_body());
+    bytes.extend(signed_message.signature().0.iter());
+    Ok(::sodiumoxide::crypto::hash::sha256::hash(&bytes[..]))
 }
 
 #[allow(unused)]
@@ -213,6 +190,102 @@ impl RunningAverage {
     }
 }
 
+/// TokenBucket allows a steady rate of messages per second, with bursts up to its capacity.
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: ::time::SteadyTime,
+}
+
+impl TokenBucket {
+    /// Start a full bucket.
+    pub fn new(rate: f64, capacity: f64) -> TokenBucket {
+        TokenBucket {
+            rate: rate,
+            capacity: capacity,
+            tokens: capacity,
+            last_refill: ::clock::now(),
+        }
+    }
+
+    /// Take a token for a message, returns false if the bucket is empty.
+    pub fn take(&mut self) -> bool {
+        let now = ::clock::now();
+        let elapsed = (now - self.last_refill).num_microseconds().unwrap_or(i64::max_value());
+        self.tokens = (self.tokens + self.rate * elapsed as f64 / 1e6f64).min(self.capacity);
+        self.last_refill = now;
+        if self.tokens < 1f64 {
+            return false;
+        };
+        self.tokens -= 1f64;
+        true
+    }
+}
+
+/// The verdict of the rate limiter on a received message.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Rate {
+    /// Handle the message.
+    Allow,
+    /// Drop the message.
+    Drop,
+    /// Drop the message and the connection it came on.
+    Disconnect,
+}
+
+/// RateLimiter keeps a token bucket for every connection and every claimant.  Messages beyond the
+/// rate are dropped, and a connection that keeps exceeding its rate is to be disconnected.
+pub struct RateLimiter {
+    rate_limits: ::types::RateLimits,
+    connections: ::std::collections::HashMap<::crust::Connection, (TokenBucket, u32)>,
+    claimants: ::lru_time_cache::LruCache<::types::Address, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Set up a rate limiter with the given limits that forgets idle claimants after the expiry
+    /// duration.
+    pub fn with_expiry_duration(rate_limits: ::types::RateLimits, duration: ::time::Duration)
+            -> RateLimiter {
+        RateLimiter {
+            rate_limits: rate_limits,
+            connections: ::std::collections::HashMap::new(),
+            claimants: ::lru_time_cache::LruCache::with_expiry_duration(duration),
+        }
+    }
+
+    /// Check a message received on the connection.  Every message over the rate counts as an
+    /// excess message and every message within it pays one back.
+    pub fn check_connection(&mut self, connection: &::crust::Connection) -> Rate {
+        let (rate, burst) = self.rate_limits.connection_rate();
+        let max_excess_messages = self.rate_limits.max_excess_messages();
+        let &mut (ref mut bucket, ref mut excess) = self.connections.entry(connection.clone())
+            .or_insert_with(|| (TokenBucket::new(rate as f64, burst as f64), 0u32));
+        if bucket.take() {
+            *excess = excess.saturating_sub(1);
+            return Rate::Allow;
+        };
+        *excess += 1;
+        if *excess >= max_excess_messages { Rate::Disconnect } else { Rate::Drop }
+    }
+
+    /// Returns true if a message of this claimant is within its rate.  Only a claimant whose
+    /// signature has been verified is to be charged, or anyone could exhaust its rate.
+    pub fn check_claimant(&mut self, claimant: &::types::Address) -> bool {
+        let (rate, burst) = self.rate_limits.claimant_rate();
+        let mut bucket = self.claimants.remove(claimant)
+            .unwrap_or_else(|| TokenBucket::new(rate as f64, burst as f64));
+        let allowed = bucket.take();
+        let _ = self.claimants.insert(claimant.clone(), bucket);
+        allowed
+    }
+
+    /// Forget the bucket of a connection.
+    pub fn remove(&mut self, connection: &::crust::Connection) {
+        let _ = self.connections.remove(connection);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use rand;
@@ -229,14 +302,20 @@ mod test {
             ::messages::SignedMessage::new(claimant.clone(), routing_message.clone(), &keys.1);
         let signed_message = signed_message.unwrap();
 
-        assert!(filter.check(&signed_message));
-        assert!(!filter.check(&signed_message));
+        assert!(filter.check(&signed_message).is_ok());
+        assert!(filter.check(&signed_message).is_ok());
+        filter.add(&signed_message);
+        // a duplicate is filtered quietly
+        match filter.check(&signed_message) {
+            Err(::error::RoutingError::FilterCheckFailed) => {},
+            result => panic!("Unexpected result {:?}", result),
+        }
     }
 
     #[test]
     fn filter_check_after_duration_end() {
         let duration = ::time::Duration::milliseconds(1);
-        let mut filter = super::Filter::with_expiry_duration(duration);
+        let mut filter = super::Filter::with_replay_window(duration, duration);
         let claimant = ::types::Address::Node(rand::random());
         let keys = ::sodiumoxide::crypto::sign::gen_keypair();
         let routing_message =
@@ -245,9 +324,54 @@ mod test {
             ::messages::SignedMessage::new(claimant.clone(), routing_message.clone(), &keys.1);
         let signed_message = signed_message.unwrap();
 
-        assert!(filter.check(&signed_message));
+        assert!(filter.check(&signed_message).is_ok());
+        filter.add(&signed_message);
         ::std::thread::sleep_ms(2);
-        assert!(filter.check(&signed_message));
+        // the signature has expired, but the message has gone stale with it
+        match filter.check(&signed_message) {
+            Err(::error::RoutingError::ReplayedMessage) => {},
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn filter_check_timestamp() {
+        let mut filter = super::Filter::with_replay_window(::time::Duration::seconds(3),
+                                                           ::time::Duration::seconds(3));
+        let claimant = ::types::Address::Node(rand::random());
+        let keys = ::sodiumoxide::crypto::sign::gen_keypair();
+        let routing_message =
+            ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
+        let signed = |timestamp: i64| {
+            let encoded_body = ::utils::encode(&(&::types::PROTOCOL_VERSION, &routing_message,
+                                                 &claimant, &0u8, &timestamp)).unwrap();
+            let signature = ::sodiumoxide::crypto::sign::sign_detached(&encoded_body, &keys.1);
+            ::messages::SignedMessage::with_signature(claimant.clone(), routing_message.clone(),
+                0u8, timestamp, signature).unwrap()
+        };
+        let now = ::messages::current_timestamp();
+
+        match filter.check(&signed(now - 4000)) {
+            Err(::error::RoutingError::ReplayedMessage) => {},
+            result => panic!("Unexpected result {:?}", result),
+        }
+        match filter.check(&signed(now + 4000)) {
+            Err(::error::RoutingError::ReplayedMessage) => {},
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(filter.check(&signed(now - 1000)).is_ok());
+
+        // an unverified message does not move the window of its claimant
+        assert!(filter.check(&signed(now + 2000)).is_ok());
+        assert!(filter.check(&signed(now - 1500)).is_ok());
+
+        // within our clock, but too far behind the newest verified message of the claimant
+        filter.record(&signed(now + 2000));
+        match filter.check(&signed(now - 1500)) {
+            Err(::error::RoutingError::ReplayedMessage) => {},
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(filter.check(&signed(now)).is_ok());
     }
 
     #[test]
@@ -264,7 +388,57 @@ mod test {
 
         filter.block(signed_message.get_routing_message());
 
-        assert!(!filter.check(&signed_message));
+        match filter.check(&signed_message) {
+            Err(::error::RoutingError::FilterCheckFailed) => {},
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn token_bucket_refills() {
+        let mut bucket = super::TokenBucket::new(1000f64, 2f64);
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(!bucket.take());
+        ::std::thread::sleep_ms(5);
+        assert!(bucket.take());
+    }
+
+    #[test]
+    fn rate_limiter_disconnects_flooding_connection() {
+        let rate_limits = ::types::RateLimits::new((1, 10), (1, 1), 5).unwrap();
+        let mut rate_limiter = super::RateLimiter::with_expiry_duration(rate_limits,
+            ::time::Duration::minutes(1));
+        let connection = ::test_utils::test::random_connection();
+        let other_connection = ::test_utils::test::random_connection();
+        let burst = 10usize;
+        for _ in 0..burst {
+            assert_eq!(rate_limiter.check_connection(&connection), super::Rate::Allow);
+        }
+        assert_eq!(rate_limiter.check_connection(&connection), super::Rate::Drop);
+        assert_eq!(rate_limiter.check_connection(&other_connection), super::Rate::Allow);
+        let mut rate = super::Rate::Drop;
+        for _ in 0..5 {
+            rate = rate_limiter.check_connection(&connection);
+            if rate == super::Rate::Disconnect { break; };
+        }
+        assert_eq!(rate, super::Rate::Disconnect);
+
+        rate_limiter.remove(&connection);
+        assert_eq!(rate_limiter.check_connection(&connection), super::Rate::Allow);
+    }
+
+    #[test]
+    fn rate_limiter_limits_claimant() {
+        let mut rate_limiter = super::RateLimiter::with_expiry_duration(
+            ::types::RateLimits::default_limits(), ::time::Duration::minutes(1));
+        let claimant = ::types::Address::Node(rand::random());
+        let burst = ::types::CLAIMANT_RATE.1 as usize;
+        for _ in 0..burst {
+            assert!(rate_limiter.check_claimant(&claimant));
+        }
+        assert!(!rate_limiter.check_claimant(&claimant));
+        assert!(rate_limiter.check_claimant(&::types::Address::Node(rand::random())));
     }
 
     #[test]
@@ -15,18 +15,25 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use std::io::{Read, Write};
+use std::path::Path;
+
 use sodiumoxide::crypto;
 use sodiumoxide;
+use error::RoutingError;
+use relocation::RelocationCertificate;
 use NameType;
 
 // Note: name field is initially same as original_name, this should be later overwritten by
 // relocated name provided by the network using assign_relocated_name method
 // TODO (ben 2015-04-01) : implement order based on name
 /// Id.
+#[derive(RustcEncodable, RustcDecodable)]
 pub struct Id {
     sign_keys: (crypto::sign::PublicKey, crypto::sign::SecretKey),
     encrypt_keys: (crypto::box_::PublicKey, crypto::box_::SecretKey),
     name: NameType,
+    relocation: Option<RelocationCertificate>,
 }
 
 impl Id {
@@ -40,6 +47,7 @@ impl Id {
             sign_keys: sign_keys,
             encrypt_keys: sodiumoxide::crypto::box_::gen_keypair(),
             name: name,
+            relocation: None,
         }
     }
 
@@ -59,12 +67,17 @@ impl Id {
         self.encrypt_keys.0
     }
 
+    /// Secret encryption key.
+    pub fn encrypting_private_key(&self) -> &crypto::box_::SecretKey {
+        &self.encrypt_keys.1
+    }
+
     /// Construct with given keys, (Client requirement).
     pub fn with_keys(sign_keys: (crypto::sign::PublicKey, crypto::sign::SecretKey),
                      encrypt_keys: (crypto::box_::PublicKey, crypto::box_::SecretKey))
                      -> Id {
         let name = NameType::new(crypto::hash::sha512::hash(&sign_keys.0[..]).0);
-        Id { sign_keys: sign_keys, encrypt_keys: encrypt_keys, name: name }
+        Id { sign_keys: sign_keys, encrypt_keys: encrypt_keys, name: name, relocation: None }
     }
 
     /// Original/relocated name.
@@ -86,6 +99,55 @@ impl Id {
     pub fn is_relocated(&self) -> bool {
         self.name != NameType::new(crypto::hash::sha512::hash(&self.sign_keys.0[..]).0)
     }
+
+    /// Keep the group signed certificate of our relocated name, to prove the name to our peers.
+    /// Returns false if the certificate is for another name.
+    pub fn set_relocation_certificate(&mut self, certificate: RelocationCertificate) -> bool {
+        if *certificate.relocated_name() != self.name {
+            return false;
+        }
+        self.relocation = Some(certificate);
+        true
+    }
+
+    /// The certificate of our relocated name, if the network provided one.
+    pub fn relocation_certificate(&self) -> Option<&RelocationCertificate> {
+        self.relocation.as_ref()
+    }
+
+    /// Read an Id, including its relocated name, from the file at the given path.
+    pub fn read_file(path: &Path) -> Result<Id, RoutingError> {
+        let mut file = try!(::std::fs::File::open(path));
+        let mut contents = Vec::new();
+        let _ = try!(file.read_to_end(&mut contents));
+        Ok(try!(::utils::decode::<Id>(&contents)))
+    }
+
+    /// Write the Id, including the secret keys, to the file at the given path, replacing its
+    /// content.  The file is only readable and writable by its owner.  Access can only be
+    /// restricted on unix, so on other platforms the Id is not written and an error is returned.
+    pub fn write_file(&self, path: &Path) -> Result<(), RoutingError> {
+        let encoded = try!(::utils::encode(self));
+        let mut file = try!(Id::create_restricted_file(path));
+        try!(file.write_all(&encoded));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn create_restricted_file(path: &Path) -> Result<::std::fs::File, ::std::io::Error> {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        let file = try!(::std::fs::OpenOptions::new().write(true).create(true).truncate(true)
+                            .mode(0o600).open(path));
+        // mode only applies on creation, so restrict a file that already existed as well
+        try!(file.set_permissions(::std::fs::Permissions::from_mode(0o600)));
+        Ok(file)
+    }
+
+    #[cfg(not(unix))]
+    fn create_restricted_file(_path: &Path) -> Result<::std::fs::File, ::std::io::Error> {
+        Err(::std::io::Error::new(::std::io::ErrorKind::Other,
+            "access to the secret keys can not be restricted on this platform"))
+    }
 }
 
 
@@ -151,4 +213,51 @@ mod test{
       assert_eq!(cloned_encrypting_public_key, id.encrypting_public_key().0.to_vec());
       assert_eq!(cloned_signing_private_key, id.signing_private_key().0.to_vec());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_and_read_file() {
+        let mut id = ::id::Id::new();
+        let relocated_name: ::name_type::NameType = rand::random();
+        assert!(id.assign_relocated_name(relocated_name));
+        assert!(!id.set_relocation_certificate(
+            ::relocation::RelocationCertificate::new(rand::random())));
+        let certificate = ::relocation::RelocationCertificate::new(relocated_name);
+        assert!(id.set_relocation_certificate(certificate.clone()));
+
+        let path = ::std::env::temp_dir().join(format!("routing_id_{}",
+            rand::random::<u64>()));
+        assert!(id.write_file(&path).is_ok());
+        let read_id = ::id::Id::read_file(&path);
+        let _ = ::std::fs::remove_file(&path);
+        let read_id = read_id.unwrap();
+
+        assert!(read_id.is_relocated());
+        assert_eq!(relocated_name, read_id.name());
+        assert_eq!(Some(&certificate), read_id.relocation_certificate());
+        assert_eq!(id.signing_public_key(), read_id.signing_public_key());
+        assert_eq!(&id.signing_private_key()[..], &read_id.signing_private_key()[..]);
+        assert_eq!(id.encrypting_public_key(), read_id.encrypting_public_key());
+    }
+
+    #[cfg(not(unix))]
+    #[test]
+    fn refuse_unrestricted_file() {
+        let path = ::std::env::temp_dir().join(format!("routing_id_{}",
+            rand::random::<u64>()));
+        assert!(::id::Id::new().write_file(&path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn written_file_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = ::std::env::temp_dir().join(format!("routing_id_{}",
+            rand::random::<u64>()));
+        assert!(::id::Id::new().write_file(&path).is_ok());
+        let mode = ::std::fs::metadata(&path).map(|metadata| metadata.permissions().mode());
+        let _ = ::std::fs::remove_file(&path);
+        assert_eq!(mode.unwrap() & 0o777, 0o600);
+    }
 }
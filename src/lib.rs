@@ -53,6 +53,7 @@ extern crate rand;
 extern crate rustc_serialize;
 extern crate sodiumoxide;
 extern crate time;
+extern crate env_logger;
 
 extern crate crust;
 // extern crate accumulator;
@@ -60,13 +61,19 @@ extern crate lru_time_cache;
 extern crate message_filter;
 
 mod common_bits;
+mod clock;
 mod action;
 mod filter;
+mod liveness;
 mod messages;
 mod direct_messages;
 mod name_type;
 mod routing_table;
 mod routing_node;
+mod routing_builder;
+mod bootstrap_cache;
+mod session;
+mod proof_of_work;
 mod routing_core;
 mod refresh_accumulator;
 mod message_accumulator;
@@ -77,8 +84,14 @@ mod connection_management;
 pub mod routing;
 /// Client interface to routing.
 pub mod routing_client;
+/// Blocking and handle based client interface to routing.
+pub mod blocking_client;
 /// Event provides the events the user can expect to receive from routing.
 pub mod event;
+/// Transport abstracts the connection layer routing runs on.
+pub mod transport;
+/// Configuration file for node deployments.
+pub mod config_file;
 /// Utility structs and functions used during testing.
 pub mod test_utils;
 /// Types and functions used throught the library.
@@ -102,10 +115,20 @@ pub mod immutable_data;
 pub mod plain_data;
 /// Data types used in messages.
 pub mod data;
+/// Misbehaviour scores and temporary bans of peers.
+pub mod ban_list;
+/// Group signed certificates of relocated names.
+pub mod relocation;
+/// Read-only snapshots of the routing table.
+pub mod snapshot;
 
 /// NameType is a 512bit name to address elements on the DHT network.
 pub use name_type::{NameType, closer_to_target, NAME_TYPE_LEN};
 /// Message types defined by the library.
-pub use messages::{SignedToken, ExternalRequest, ExternalResponse};
+pub use messages::{SignedToken, ExternalRequest, ExternalResponse, MessageId};
 /// Persona types recognised by the network.
 pub use authority::Authority;
+/// Settings of a routing node or client before it is started.
+pub use routing_builder::RoutingBuilder;
+/// Connection state of a routing node.
+pub use routing_core::State;
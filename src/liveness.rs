@@ -0,0 +1,179 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::collections::HashMap;
+
+use crust::Connection;
+use time::{Duration, SteadyTime};
+
+/// Seconds between two probes of the routing table contacts.
+pub const PING_INTERVAL_SECONDS: i64 = 10;
+/// Number of consecutive unanswered pings after which a contact is considered dead.
+pub const MAX_MISSED_PINGS: u32 = 3;
+
+struct Probe {
+    nonce: u64,
+    sent: SteadyTime,
+    answered: bool,
+    missed: u32,
+}
+
+/// Liveness pings every connection it is given once per interval.  A ping that has not been
+/// answered by the time of the next probe counts as missed, and a connection that misses too many
+/// pings in a row is reported as unresponsive.
+pub struct Liveness {
+    interval: Duration,
+    last_probe: SteadyTime,
+    probes: HashMap<Connection, Probe>,
+}
+
+impl Liveness {
+    /// Set up a liveness check which probes once per interval.
+    pub fn with_interval(interval: Duration) -> Liveness {
+        Liveness {
+            interval: interval,
+            last_probe: ::clock::now(),
+            probes: HashMap::new(),
+        }
+    }
+
+    /// Returns true once the interval since the last probe has passed.
+    pub fn is_due(&self) -> bool {
+        ::clock::now() - self.last_probe >= self.interval
+    }
+
+    /// Probe the given connections, forgetting about any other connection.  Returns the nonces of
+    /// the pings to send, the connections that left their last ping unanswered, and the
+    /// connections that have missed too many pings; those are forgotten as well.
+    pub fn probe(&mut self, connections: Vec<Connection>)
+            -> (Vec<(Connection, u64)>, Vec<Connection>, Vec<Connection>) {
+        let now = ::clock::now();
+        self.last_probe = now;
+        self.probes.retain(|connection, _| connections.contains(connection));
+        let mut pings = Vec::new();
+        let mut missed = Vec::new();
+        let mut unresponsive = Vec::new();
+        for connection in connections {
+            let nonce = ::rand::random::<u64>();
+            let dead = match self.probes.get_mut(&connection) {
+                Some(probe) => {
+                    if probe.answered {
+                        probe.missed = 0;
+                    } else {
+                        probe.missed += 1;
+                        missed.push(connection.clone());
+                    };
+                    probe.nonce = nonce;
+                    probe.sent = now;
+                    probe.answered = false;
+                    probe.missed >= MAX_MISSED_PINGS
+                },
+                None => false,
+            };
+            if dead {
+                let _ = self.probes.remove(&connection);
+                unresponsive.push(connection);
+                continue;
+            };
+            if !self.probes.contains_key(&connection) {
+                let _ = self.probes.insert(connection.clone(), Probe {
+                    nonce: nonce,
+                    sent: now,
+                    answered: false,
+                    missed: 0,
+                });
+            };
+            pings.push((connection, nonce));
+        }
+        (pings, missed, unresponsive)
+    }
+
+    /// Match a pong against the last ping sent on its connection.  Returns the round trip time if
+    /// it answers that ping for the first time.
+    pub fn pong(&mut self, connection: &Connection, nonce: u64) -> Option<Duration> {
+        match self.probes.get_mut(connection) {
+            Some(probe) => {
+                if probe.answered || probe.nonce != nonce {
+                    return None;
+                };
+                probe.answered = true;
+                Some(::clock::now() - probe.sent)
+            },
+            None => None,
+        }
+    }
+
+    /// Forget about a connection.
+    pub fn remove(&mut self, connection: &Connection) {
+        let _ = self.probes.remove(connection);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::Duration;
+    use test_utils::messages_util::test::random_connection;
+
+    #[test]
+    fn answered_pings_keep_connection_alive() {
+        let mut liveness = super::Liveness::with_interval(Duration::seconds(10));
+        assert!(!liveness.is_due());
+        let connection = random_connection();
+        for _ in 0..(2 * super::MAX_MISSED_PINGS) {
+            let (pings, missed, unresponsive) = liveness.probe(vec![connection.clone()]);
+            assert!(missed.is_empty());
+            assert!(unresponsive.is_empty());
+            assert_eq!(pings.len(), 1);
+            let (pinged, nonce) = pings[0].clone();
+            assert_eq!(pinged, connection);
+            assert!(liveness.pong(&connection, nonce.wrapping_add(1)).is_none());
+            assert!(liveness.pong(&connection, nonce).is_some());
+            // a pong is only counted once
+            assert!(liveness.pong(&connection, nonce).is_none());
+        }
+    }
+
+    #[test]
+    fn missed_pings_report_unresponsive_connection() {
+        let mut liveness = super::Liveness::with_interval(Duration::milliseconds(1));
+        let connection = random_connection();
+        let other_connection = random_connection();
+        let connections = vec![connection.clone(), other_connection.clone()];
+        for probe in 0..super::MAX_MISSED_PINGS {
+            let (pings, missed, unresponsive) = liveness.probe(connections.clone());
+            assert_eq!(missed.len(), if probe == 0 { 0 } else { 1 });
+            assert!(unresponsive.is_empty());
+            for (pinged, nonce) in pings {
+                if pinged == other_connection {
+                    assert!(liveness.pong(&pinged, nonce).is_some());
+                };
+            }
+        }
+        ::std::thread::sleep_ms(2);
+        assert!(liveness.is_due());
+        let (pings, missed, unresponsive) = liveness.probe(connections.clone());
+        assert_eq!(missed, vec![connection.clone()]);
+        assert_eq!(unresponsive, vec![connection.clone()]);
+        assert_eq!(pings.len(), 1);
+        assert_eq!(pings[0].0, other_connection);
+
+        // a connection that is no longer probed is forgotten
+        let (_, _, unresponsive) = liveness.probe(vec![]);
+        assert!(unresponsive.is_empty());
+        assert!(liveness.pong(&other_connection, pings[0].1).is_none());
+    }
+}
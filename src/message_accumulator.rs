@@ -16,17 +16,21 @@
 // relating to use of the SAFE Network Software.
 
 use lru_time_cache::LruCache;
-use std::collections::BTreeSet;
-use messages::RoutingMessage;
+use std::collections::BTreeMap;
+use messages::{RoutingMessage, SignedMessage};
+use types::Address;
 use NameType;
 
-type Set<K> = BTreeSet<K>;
+type Map<K, V> = BTreeMap<K, V>;
 pub type Bytes = Vec<u8>;
 
+/// MessageAccumulator acts as the sentinel for messages from a group.  It keeps the signed
+/// message of each claimant and only releases a message once the threshold of claimants agree on
+/// it and are validated; claimants failing the validation are forgotten and not counted.
 pub struct MessageAccumulator {
-    //                                       +-> Who sent it
-    //                                       |
-    requests: LruCache<RoutingMessage, Set<NameType>>,
+    //                                                   +-> Who sent it
+    //                                                   |
+    requests: LruCache<RoutingMessage, Map<NameType, SignedMessage>>,
 }
 
 impl MessageAccumulator {
@@ -35,20 +39,40 @@ impl MessageAccumulator {
         MessageAccumulator { requests: LruCache::with_expiry_duration(duration) }
     }
 
-    pub fn add_message(&mut self,
-                       threshold: usize,
-                       claimant: NameType,
-                       message: RoutingMessage)
-                       -> Option<RoutingMessage> {
+    /// Add the signed message of a node claimant for the message it carries; the message is
+    /// passed in opened, as encrypted content differs between claimants.  is_valid is asked for
+    /// all claimants of the message each time, so that a claimant can still be rejected once more
+    /// is known about it; it should verify the signature and that the claimant is in range of the
+    /// sending group.
+    pub fn add_message<F>(&mut self,
+                          threshold: usize,
+                          message: RoutingMessage,
+                          signed_message: SignedMessage,
+                          mut is_valid: F)
+                          -> Option<RoutingMessage>
+        where F: FnMut(&NameType, &SignedMessage) -> bool {
+        let claimant = match *signed_message.claimant() {
+            Address::Node(ref name) => name.clone(),
+            Address::Client(_) => return None,
+        };
         {
             if threshold <= 1 {
-                return Some(message);
+                return if is_valid(&claimant, &signed_message) { Some(message) } else { None };
             }
 
             let claimants = self.requests.entry(message.clone())
-                                         .or_insert_with(||Set::new());
-
-            claimants.insert(claimant);
+                                         .or_insert_with(||Map::new());
+
+            let _ = claimants.insert(claimant, signed_message);
+            let invalid_claimants = claimants.iter()
+                .filter(|&(name, signed_message)| !is_valid(name, signed_message))
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<NameType>>();
+            for invalid_claimant in invalid_claimants {
+                debug!("Claimant {:?} failed validation for message {:?}", invalid_claimant,
+                    message);
+                let _ = claimants.remove(&invalid_claimant);
+            }
 
             if claimants.len() < threshold {
                 return None;
@@ -67,6 +91,26 @@ impl MessageAccumulator {
 #[cfg(test)]
 mod test {
     use rand;
+    use messages::{RoutingMessage, SignedMessage};
+    use types::Address;
+
+    fn sign(claimant: ::NameType, routing_message: &RoutingMessage) -> SignedMessage {
+        let keys = ::sodiumoxide::crypto::sign::gen_keypair();
+        SignedMessage::new(Address::Node(claimant), routing_message.clone(), &keys.1).unwrap()
+    }
+
+    fn accept(_: &::NameType, _: &SignedMessage) -> bool { true }
+
+    fn add<F>(accumulator: &mut ::message_accumulator::MessageAccumulator,
+              threshold: usize,
+              claimant: ::NameType,
+              routing_message: &RoutingMessage,
+              is_valid: F)
+              -> Option<RoutingMessage>
+        where F: FnMut(&::NameType, &SignedMessage) -> bool {
+        accumulator.add_message(threshold, routing_message.clone(),
+                                sign(claimant, routing_message), is_valid)
+    }
 
     #[test]
     fn add_with_fixed_threshold() {
@@ -77,19 +121,19 @@ mod test {
         let mut accumulator = ::message_accumulator::MessageAccumulator::with_expiry_duration(
             ::time::Duration::minutes(10));
         for _ in 0..threshold - 1 {
-            assert!(accumulator.add_message(threshold.clone(), rand::random(),
-                routing_message.clone()).is_none());
+            assert!(add(&mut accumulator, threshold.clone(), rand::random(),
+                &routing_message, accept).is_none());
         }
-        assert_eq!(accumulator.add_message(threshold.clone(), rand::random(),
-            routing_message.clone()), Some(routing_message.clone()));
+        assert_eq!(add(&mut accumulator, threshold.clone(), rand::random(),
+            &routing_message, accept), Some(routing_message.clone()));
 
         // assert that the accumulator has been cleared; repeat with the same message
         for _ in 0..threshold - 1 {
-            assert!(accumulator.add_message(threshold.clone(), rand::random(),
-                routing_message.clone()).is_none());
+            assert!(add(&mut accumulator, threshold.clone(), rand::random(),
+                &routing_message, accept).is_none());
         }
-        assert_eq!(accumulator.add_message(threshold.clone(), rand::random(),
-            routing_message.clone()), Some(routing_message));
+        assert_eq!(add(&mut accumulator, threshold.clone(), rand::random(),
+            &routing_message, accept), Some(routing_message));
     }
 
     #[test]
@@ -102,16 +146,16 @@ mod test {
             ::time::Duration::minutes(10));
         for _ in 0..threshold - 1 {
             let claimant: ::NameType = rand::random();
-            assert!(accumulator.add_message(threshold.clone(), claimant.clone(),
-                routing_message.clone()).is_none());
-            assert!(accumulator.add_message(threshold.clone(), claimant.clone(),
-                routing_message.clone()).is_none());
+            assert!(add(&mut accumulator, threshold.clone(), claimant.clone(),
+                &routing_message, accept).is_none());
+            assert!(add(&mut accumulator, threshold.clone(), claimant.clone(),
+                &routing_message, accept).is_none());
         }
         let claimant: ::NameType = rand::random();
-        assert_eq!(accumulator.add_message(threshold.clone(), claimant.clone(),
-            routing_message.clone()), Some(routing_message.clone()));
-        assert!(accumulator.add_message(threshold.clone(), claimant.clone(),
-            routing_message.clone()).is_none());
+        assert_eq!(add(&mut accumulator, threshold.clone(), claimant.clone(),
+            &routing_message, accept), Some(routing_message.clone()));
+        assert!(add(&mut accumulator, threshold.clone(), claimant.clone(),
+            &routing_message, accept).is_none());
     }
 
     #[test]
@@ -126,18 +170,45 @@ mod test {
             ::time::Duration::minutes(10));
         for _ in 0..threshold - 1 {
             let claimant: ::NameType = rand::random();
-            assert!(accumulator.add_message(threshold.clone(), claimant.clone(),
-                routing_message1.clone()).is_none());
-            assert!(accumulator.add_message(threshold.clone(), claimant.clone(),
-                routing_message2.clone()).is_none());
+            assert!(add(&mut accumulator, threshold.clone(), claimant.clone(),
+                &routing_message1, accept).is_none());
+            assert!(add(&mut accumulator, threshold.clone(), claimant.clone(),
+                &routing_message2, accept).is_none());
         }
         let claimant: ::NameType = rand::random();
-        assert_eq!(accumulator.add_message(threshold.clone(), claimant.clone(),
-            routing_message1.clone()), Some(routing_message1.clone()));
-        assert!(accumulator.add_message(threshold.clone() + 1, claimant.clone(),
-            routing_message2.clone()).is_none());
+        assert_eq!(add(&mut accumulator, threshold.clone(), claimant.clone(),
+            &routing_message1, accept), Some(routing_message1.clone()));
+        assert!(add(&mut accumulator, threshold.clone() + 1, claimant.clone(),
+            &routing_message2, accept).is_none());
         // lower threshold again
-        assert_eq!(accumulator.add_message(threshold.clone(), rand::random(),
-            routing_message2.clone()), Some(routing_message2.clone()));
+        assert_eq!(add(&mut accumulator, threshold.clone(), rand::random(),
+            &routing_message2, accept), Some(routing_message2.clone()));
+    }
+
+    #[test]
+    fn invalid_claimants_are_not_counted() {
+        let threshold = 3usize;
+        let id = ::id::Id::new();
+        let routing_message = ::test_utils::messages_util::arbitrary_routing_message(
+            &id.signing_public_key(), &id.signing_private_key());
+        let mut accumulator = ::message_accumulator::MessageAccumulator::with_expiry_duration(
+            ::time::Duration::minutes(10));
+        let forger: ::NameType = rand::random();
+        let reject_forger = |claimant: &::NameType, _: &SignedMessage| *claimant != forger;
+
+        // a single node claiming several names, all of them failing validation
+        for _ in 0..threshold {
+            let forged_name: ::NameType = rand::random();
+            assert!(add(&mut accumulator, threshold.clone(), forged_name,
+                &routing_message, |_: &::NameType, _: &SignedMessage| false).is_none());
+        }
+        for _ in 0..threshold - 1 {
+            assert!(add(&mut accumulator, threshold.clone(), rand::random(),
+                &routing_message, &reject_forger).is_none());
+        }
+        assert!(add(&mut accumulator, threshold.clone(), forger.clone(),
+            &routing_message, &reject_forger).is_none());
+        assert_eq!(add(&mut accumulator, threshold.clone(), rand::random(),
+            &routing_message, &reject_forger), Some(routing_message));
     }
 }
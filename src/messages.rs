@@ -101,8 +101,9 @@ impl ExternalResponse {
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
 pub enum InternalRequest {
     Connect(ConnectRequest),
-    RequestNetworkName(::public_id::PublicId),
-    // a client can send RequestNetworkName
+    RequestNetworkName(::public_id::PublicId, u64),
+    // a client can send RequestNetworkName, with the nonce that proves the admission work
+    // for its PublicId
     CacheNetworkName(::public_id::PublicId, SignedToken),
     //               ~~|~~~~~  ~~|~~~~~~~~
     //                 |         | SignedToken contains Request::RequestNetworkName and needs to
@@ -114,6 +115,9 @@ pub enum InternalRequest {
     /// the serialised payload:Vec<u8>).  The cause of the Refresh is the NameType of the node
     /// that caused the churn event.
     Refresh(u64, Vec<u8>, ::NameType),
+    /// RequestPublicIds asks a node for the public ids it knows of the given nodes, e.g. of the
+    /// signers of a relocation certificate we can not check yet.
+    RequestPublicIds(Vec<::NameType>),
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -122,11 +126,23 @@ pub enum InternalResponse {
     // FindGroup(Vec<::public_id::PublicId>, SignedToken),
     // GetGroupKey(::std::collections::BTreeMap<
     //      ::NameType, ::sodiumoxide::crypto::sign::PublicKey>, SignedToken),
-    CacheNetworkName(::public_id::PublicId, Vec<::public_id::PublicId>, SignedToken),
-    //               ~~|~~~~~  ~~|~~~~~~~~~~  ~~|~~~~~~~~
-    //                 |         |              | the original Request::RequestNetworkName
+    CacheNetworkName(::public_id::PublicId, Vec<::public_id::PublicId>,
+                     ::relocation::RelocationSignature, SignedToken),
+    //               ~~|~~~~~  ~~|~~~~~~~~~~  ~~|~~~~~~~~~~~~~~~~~~  ~~|~~~~~~~~
+    //                 |         |              |                     | the original
+    //                 |         |              |                     | Request::RequestNetworkName
+    //                 |         |              | the signature of the responding group member
+    //                 |         |              | for the relocation certificate
     //                 |         | the group public keys to combine FindGroup in this response
     //                 | the cached PublicId in the group
+    InsufficientProofOfWork(u8, SignedToken),
+    //                      |~  ~~|~~~~~~~~
+    //                      |     | the original Request::RequestNetworkName
+    //                      | the admission difficulty the network requires
+    PublicIds(Vec<::public_id::PublicId>, SignedToken),
+    //        ~~|~~~~~~~~~~~~~~~~~~~~~~~  ~~|~~~~~~~~
+    //          |                           | the original Request::RequestPublicIds
+    //          | the public ids the responding node knows of the requested nodes
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -135,6 +151,95 @@ pub enum Content {
     InternalRequest(InternalRequest),
     ExternalResponse(ExternalResponse),
     InternalResponse(InternalResponse),
+    Encrypted(EncryptedContent),
+}
+
+impl Content {
+    /// Seal the content for the owner of recipient_key.  Only external requests and responses
+    /// are encrypted, as routing itself has to read internal content along the way; other content
+    /// is returned unchanged.
+    pub fn encrypt(&self,
+                   recipient_key: &::sodiumoxide::crypto::box_::PublicKey,
+                   our_public_key: &::sodiumoxide::crypto::box_::PublicKey,
+                   our_secret_key: &::sodiumoxide::crypto::box_::SecretKey)
+                   -> Result<Content, ::cbor::CborError> {
+        match *self {
+            Content::ExternalRequest(_) | Content::ExternalResponse(_) => {},
+            _ => return Ok(self.clone()),
+        };
+        let nonce = ::sodiumoxide::crypto::box_::gen_nonce();
+        let cipher_text = ::sodiumoxide::crypto::box_::seal(&try!(::utils::encode(self)), &nonce,
+                                                             recipient_key, our_secret_key);
+        Ok(Content::Encrypted(EncryptedContent {
+            sender_key: our_public_key.clone(),
+            nonce: nonce,
+            cipher_text: cipher_text,
+        }))
+    }
+
+    /// Open encrypted content with our secret key; content which is not encrypted is returned
+    /// unchanged.
+    pub fn decrypt(&self, our_secret_key: &::sodiumoxide::crypto::box_::SecretKey)
+            -> Result<Content, ::error::RoutingError> {
+        match *self {
+            Content::Encrypted(ref encrypted) => {
+                let plain_text = try!(::sodiumoxide::crypto::box_::open(&encrypted.cipher_text,
+                    &encrypted.nonce, &encrypted.sender_key, our_secret_key)
+                    .map_err(|_| ::error::RoutingError::FailedDecryption));
+                Ok(try!(::utils::decode::<Content>(&plain_text)))
+            },
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+/// Content sealed for a single recipient, so that only the destination and not the nodes routing
+/// the message can read it.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct EncryptedContent {
+    /// Public encryption key of the sender, needed to open the content.
+    pub sender_key: ::sodiumoxide::crypto::box_::PublicKey,
+    /// Nonce the content was sealed with.
+    pub nonce: ::sodiumoxide::crypto::box_::Nonce,
+    /// The sealed, encoded Content.
+    pub cipher_text: Vec<u8>,
+}
+
+/// MessageId identifies a request; all responses to a request carry the MessageId of the request.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, RustcEncodable, RustcDecodable)]
+pub struct MessageId(u64);
+
+impl MessageId {
+    /// A random MessageId for a request made by a single entity.
+    pub fn new() -> MessageId {
+        MessageId(::rand::random::<u64>())
+    }
+
+    /// The MessageId for a request made as our_authority.  All members of a group make the same
+    /// request independently, so for a group authority the MessageId is derived from the request
+    /// itself and from the MessageId of the request that caused it, if any; only inputs all
+    /// members have received.  Identical requests for the same cause share their MessageId.
+    pub fn for_request(our_authority: &::authority::Authority,
+                       to_authority: &::authority::Authority,
+                       content: &Content,
+                       cause: Option<MessageId>) -> MessageId {
+        if !our_authority.is_group() {
+            return MessageId::new();
+        };
+        match ::utils::encode(&(our_authority, to_authority, content, cause)) {
+            Ok(bytes) => {
+                let digest = ::sodiumoxide::crypto::hash::sha512::hash(&bytes);
+                MessageId(digest.0.iter().take(8).fold(0u64, |id, byte| (id << 8) | *byte as u64))
+            },
+            Err(_) => MessageId::new(),
+        }
+    }
+}
+
+impl ::std::fmt::Debug for MessageId {
+    fn fmt(&self, formatter: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        formatter.write_str(&format!("MessageId({:016x})", self.0))
+    }
 }
 
 /// the bare (unsigned) routing message
@@ -144,6 +249,7 @@ pub struct RoutingMessage {
     pub from_authority: ::authority::Authority,
     pub to_authority: ::authority::Authority,
     pub content: Content,
+    pub message_id: MessageId,
 }
 
 impl RoutingMessage {
@@ -182,15 +288,29 @@ impl RoutingMessage {
     }
 }
 
+/// The current time in milliseconds since the epoch, as used for message timestamps.
+pub fn current_timestamp() -> i64 {
+    ::clock::timestamp()
+}
+
 /// All messages sent / received are constructed as signed message.
 #[derive(PartialEq, Eq, Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct SignedMessage {
+    // the protocol version of the sender, covered by the signature
+    version: u8,
     body: RoutingMessage,
     claimant: ::types::Address,
     //          when signed by Client(PublicKey) the data needs to contain it as an owner
     //          when signed by a Node(NameType), Sentinel needs to validate the signature
     random_bits: u8,
+    // signed creation time in milliseconds, so that a captured message can't be replayed once
+    // the filter has forgotten its signature
+    timestamp: i64,
     signature: ::sodiumoxide::crypto::sign::Signature,
+    // a client asking for encrypted responses announces its public encryption key, signed with
+    // its signing key so that relays can not replace it
+    encrypting_key: Option<(::sodiumoxide::crypto::box_::PublicKey,
+                            ::sodiumoxide::crypto::sign::Signature)>,
 }
 
 #[allow(unused)]
@@ -203,37 +323,44 @@ impl SignedMessage {
 
         let mut rng = ::rand::thread_rng();
         let random_bits = rng.gen::<u8>();
-        let encoded_body = try!(::utils::encode(&(&message, &claimant, &random_bits)));
+        let timestamp = current_timestamp();
+        let encoded_body = try!(::utils::encode(&(&::types::PROTOCOL_VERSION, &message,
+                                                   &claimant, &random_bits, &timestamp)));
         let signature = ::sodiumoxide::crypto::sign::sign_detached(&encoded_body, private_sign_key);
 
-        Ok(SignedMessage { body: message, claimant: claimant,
-            random_bits: random_bits, signature: signature })
+        Ok(SignedMessage { version: ::types::PROTOCOL_VERSION, body: message, claimant: claimant,
+            random_bits: random_bits, timestamp: timestamp, signature: signature,
+            encrypting_key: None })
     }
 
-    /// Construct a signed message passing in the signature.
+    /// Construct a signed message passing in the signature, which has to cover our protocol
+    /// version.
     pub fn with_signature(claimant: ::types::Address,
                           message: RoutingMessage,
                           random_bits: u8,
+                          timestamp: i64,
                           signature: ::sodiumoxide::crypto::sign::Signature)
                           -> Result<SignedMessage, ::cbor::CborError> {
 
-        Ok(SignedMessage { body: message, claimant: claimant,
-              random_bits: random_bits, signature: signature })
+        Ok(SignedMessage { version: ::types::PROTOCOL_VERSION, body: message, claimant: claimant,
+              random_bits: random_bits, timestamp: timestamp, signature: signature,
+              encrypting_key: None })
     }
 
     /// Construct a signed message from a signed token.
     pub fn new_from_token(signed_token: SignedToken) -> Result<SignedMessage, ::cbor::CborError> {
-        let (message, claimant, random_bits) =
+        let (version, message, claimant, random_bits, timestamp) =
             try!(::utils::decode(&signed_token.serialised_request));
 
-        Ok(SignedMessage { body: message, claimant: claimant,
-            random_bits: random_bits, signature: signed_token.signature })
+        Ok(SignedMessage { version: version, body: message, claimant: claimant,
+            random_bits: random_bits, timestamp: timestamp, signature: signed_token.signature,
+            encrypting_key: None })
     }
 
     /// Verify the signature using the given public key.
     pub fn verify_signature(&self, public_sign_key: &::sodiumoxide::crypto::sign::PublicKey)
             -> bool {
-        let encoded_body = match ::utils::encode(&(&self.body, &self.claimant, &self.random_bits)) {
+        let encoded_body = match self.encoded_body() {
             Ok(x) => x,
             Err(_) => return false,
         };
@@ -252,9 +379,10 @@ impl SignedMessage {
         &self.signature
     }
 
-    /// Return the encoded unsigned body of the message.
+    /// Return the encoded unsigned body of the message, which includes its protocol version.
     pub fn encoded_body(&self) -> Result<Vec<u8>, ::cbor::CborError> {
-        ::utils::encode(&(&self.body, &self.claimant, &self.random_bits))
+        ::utils::encode(&(&self.version, &self.body, &self.claimant, &self.random_bits,
+                          &self.timestamp))
     }
 
     /// Return the associated signed token.
@@ -265,10 +393,46 @@ impl SignedMessage {
             })
     }
 
+    /// Return the protocol version the message was sent with.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
     /// Return the message claimant.
     pub fn claimant(&self) -> &::types::Address {
         &self.claimant
     }
+
+    /// Return the time in milliseconds since the epoch at which the claimant signed the message.
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// Announce our public encryption key, so that responses to us can be encrypted for us.
+    pub fn announce_encrypting_key(&mut self,
+            encrypting_key: &::sodiumoxide::crypto::box_::PublicKey,
+            private_sign_key: &::sodiumoxide::crypto::sign::SecretKey) {
+        let signature = ::sodiumoxide::crypto::sign::sign_detached(&encrypting_key.0,
+                                                                   private_sign_key);
+        self.encrypting_key = Some((encrypting_key.clone(), signature));
+    }
+
+    /// Return the announced public encryption key, if its signature verifies with the given
+    /// public signing key.
+    pub fn encrypting_key(&self, public_sign_key: &::sodiumoxide::crypto::sign::PublicKey)
+            -> Option<::sodiumoxide::crypto::box_::PublicKey> {
+        match self.encrypting_key {
+            Some((ref encrypting_key, ref signature)) => {
+                if ::sodiumoxide::crypto::sign::verify_detached(signature, &encrypting_key.0,
+                                                                public_sign_key) {
+                    Some(encrypting_key.clone())
+                } else {
+                    None
+                }
+            },
+            None => None,
+        }
+    }
 }
 
 
@@ -276,6 +440,34 @@ impl SignedMessage {
 mod test{
     use rand;
 
+    #[test]
+    fn message_id_for_group_request() {
+        let name: ::NameType = rand::random();
+        let group = ::authority::Authority::NaeManager(name.clone());
+        let content = super::Content::InternalRequest(
+            super::InternalRequest::Refresh(1u64, vec![1u8, 2u8], name.clone()));
+        let other_content = super::Content::InternalRequest(
+            super::InternalRequest::Refresh(1u64, vec![3u8], name.clone()));
+
+        // all members of a group derive the same MessageId for the same request
+        assert_eq!(super::MessageId::for_request(&group, &group, &content, None),
+                   super::MessageId::for_request(&group, &group, &content, None));
+        assert!(super::MessageId::for_request(&group, &group, &content, None) !=
+                super::MessageId::for_request(&group, &group, &other_content, None));
+        // the same request for another cause gets another MessageId
+        let cause = super::MessageId::new();
+        assert_eq!(super::MessageId::for_request(&group, &group, &content, Some(cause)),
+                   super::MessageId::for_request(&group, &group, &content, Some(cause)));
+        assert!(super::MessageId::for_request(&group, &group, &content, Some(cause)) !=
+                super::MessageId::for_request(&group, &group, &content,
+                                              Some(super::MessageId::new())));
+
+        // a single entity gets a fresh MessageId for every request
+        let node = ::authority::Authority::ManagedNode(name);
+        assert!(super::MessageId::for_request(&node, &group, &content, None) !=
+                super::MessageId::for_request(&node, &group, &content, None));
+    }
+
     #[test]
     fn signed_message_new() {
         let claimant = ::types::Address::Node(rand::random());
@@ -331,6 +523,18 @@ mod test{
         assert!(!signed_message.verify_signature(&keys.0));
     }
 
+    #[test]
+    fn signature_covers_version() {
+        let keys = ::sodiumoxide::crypto::sign::gen_keypair();
+        let routing_message =
+            ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
+        let mut signed_message = super::SignedMessage::new(
+            ::types::Address::Node(rand::random()), routing_message, &keys.1).unwrap();
+        assert!(signed_message.verify_signature(&keys.0));
+        signed_message.version = ::types::PROTOCOL_VERSION.wrapping_add(1);
+        assert!(!signed_message.verify_signature(&keys.0));
+    }
+
     #[test]
     fn signed_message_with_signature() {
         let claimant = ::types::Address::Node(rand::random());
@@ -338,14 +542,16 @@ mod test{
         let routing_message =
             ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
         let random_bits: u8 = rand::random();
-        let encoded_body = ::utils::encode(&(&routing_message, &claimant, &random_bits));
+        let timestamp = super::current_timestamp();
+        let encoded_body = ::utils::encode(&(&::types::PROTOCOL_VERSION, &routing_message,
+                                             &claimant, &random_bits, &timestamp));
 
         assert!(encoded_body.is_ok());
 
         let encoded_body = encoded_body.unwrap();
         let signature = ::sodiumoxide::crypto::sign::sign_detached(&encoded_body, &keys.1);
         let signed_message = super::SignedMessage::with_signature(
-                claimant.clone(), routing_message.clone(), random_bits, signature);
+                claimant.clone(), routing_message.clone(), random_bits, timestamp, signature);
 
         assert!(signed_message.is_ok());
 
@@ -376,7 +582,9 @@ mod test{
         let routing_message =
             ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
         let random_bits: u8 = rand::random();
-        let encoded_body = ::utils::encode(&(&routing_message, &claimant, &random_bits));
+        let timestamp = super::current_timestamp();
+        let encoded_body = ::utils::encode(&(&::types::PROTOCOL_VERSION, &routing_message,
+                                             &claimant, &random_bits, &timestamp));
 
         assert!(encoded_body.is_ok());
 
@@ -384,7 +592,7 @@ mod test{
         let invalid_keys = ::sodiumoxide::crypto::sign::gen_keypair();
         let signature = ::sodiumoxide::crypto::sign::sign_detached(&encoded_body, &invalid_keys.1);
         let signed_message = super::SignedMessage::with_signature(
-                claimant.clone(), routing_message.clone(), random_bits, signature);
+                claimant.clone(), routing_message.clone(), random_bits, timestamp, signature);
 
         assert!(signed_message.is_ok());
 
@@ -415,7 +623,9 @@ mod test{
         let routing_message =
             ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
         let random_bits: u8 = rand::random();
-        let encoded_body = ::utils::encode(&(&routing_message, &claimant, &random_bits));
+        let timestamp = super::current_timestamp();
+        let encoded_body = ::utils::encode(&(&::types::PROTOCOL_VERSION, &routing_message,
+                                             &claimant, &random_bits, &timestamp));
 
         assert!(encoded_body.is_ok());
 
@@ -460,7 +670,9 @@ mod test{
         let routing_message =
             ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
         let random_bits: u8 = rand::random();
-        let encoded_body = ::utils::encode(&(&routing_message, &claimant, &random_bits));
+        let timestamp = super::current_timestamp();
+        let encoded_body = ::utils::encode(&(&::types::PROTOCOL_VERSION, &routing_message,
+                                             &claimant, &random_bits, &timestamp));
 
         assert!(encoded_body.is_ok());
 
@@ -499,4 +711,55 @@ mod test{
         assert!(signed_message_as_token.is_ok());
         assert_eq!(signed_message_as_token.unwrap(), signed_token);
     }
+
+    #[test]
+    fn encrypt_and_decrypt_content() {
+        let keys = ::sodiumoxide::crypto::sign::gen_keypair();
+        let routing_message =
+            ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
+        let sender = ::sodiumoxide::crypto::box_::gen_keypair();
+        let recipient = ::sodiumoxide::crypto::box_::gen_keypair();
+
+        let encrypted = routing_message.content.encrypt(&recipient.0, &sender.0, &sender.1);
+        assert!(encrypted.is_ok());
+        let encrypted = encrypted.unwrap();
+        match encrypted {
+            super::Content::Encrypted(ref encrypted_content) =>
+                assert_eq!(encrypted_content.sender_key, sender.0),
+            _ => panic!("External content should be encrypted."),
+        };
+        assert_eq!(encrypted.decrypt(&recipient.1).unwrap(), routing_message.content);
+
+        // nobody else can open it
+        let relay = ::sodiumoxide::crypto::box_::gen_keypair();
+        match encrypted.decrypt(&relay.1) {
+            Err(::error::RoutingError::FailedDecryption) => {},
+            _ => panic!("Content should only open for the recipient."),
+        };
+
+        // internal content stays readable
+        let internal = super::Content::InternalRequest(super::InternalRequest::Refresh(
+            0u64, vec![], rand::random()));
+        assert_eq!(internal.encrypt(&recipient.0, &sender.0, &sender.1).unwrap(), internal);
+    }
+
+    #[test]
+    fn announced_encrypting_key() {
+        let keys = ::sodiumoxide::crypto::sign::gen_keypair();
+        let routing_message =
+            ::test_utils::messages_util::arbitrary_routing_message(&keys.0, &keys.1);
+        let mut signed_message = super::SignedMessage::new(::types::Address::Client(keys.0),
+            routing_message, &keys.1).unwrap();
+        assert!(signed_message.encrypting_key(&keys.0).is_none());
+
+        let encrypting_keys = ::sodiumoxide::crypto::box_::gen_keypair();
+        signed_message.announce_encrypting_key(&encrypting_keys.0, &keys.1);
+        assert_eq!(signed_message.encrypting_key(&keys.0), Some(encrypting_keys.0));
+        assert!(signed_message.verify_signature(&keys.0));
+
+        // a key announced with another signing key is not accepted
+        let other_keys = ::sodiumoxide::crypto::sign::gen_keypair();
+        signed_message.announce_encrypting_key(&encrypting_keys.0, &other_keys.1);
+        assert!(signed_message.encrypting_key(&keys.0).is_none());
+    }
 }
@@ -0,0 +1,85 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! A hashcash puzzle a joining node solves for its PublicId before the network relocates it.  The
+//! difficulty is the number of leading zero bits the sha256 hash of the public id and the nonce
+//! must have, so every extra bit doubles the expected work of each identity an attacker brings in.
+
+use public_id::PublicId;
+
+/// Find the lowest nonce that proves the given difficulty of work for the public id.  Returns
+/// None if no nonce does.
+pub fn solve(public_id: &PublicId, difficulty: u8) -> Option<u64> {
+    let mut nonce = 0u64;
+    while !verify(public_id, difficulty, nonce) {
+        nonce = match nonce.checked_add(1) {
+            Some(next_nonce) => next_nonce,
+            None => return None,
+        };
+    }
+    Some(nonce)
+}
+
+/// Returns true if the nonce proves the given difficulty of work for the public id.
+pub fn verify(public_id: &PublicId, difficulty: u8, nonce: u64) -> bool {
+    if difficulty == 0 {
+        return true;
+    };
+    match ::utils::encode(&(public_id, nonce)) {
+        Ok(bytes) => leading_zero_bits(&::sodiumoxide::crypto::hash::sha256::hash(&bytes).0) >=
+            difficulty as u32,
+        Err(_) => false,
+    }
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut zero_bits = 0u32;
+    for byte in bytes {
+        if *byte != 0 {
+            return zero_bits + byte.leading_zeros();
+        };
+        zero_bits += 8;
+    }
+    zero_bits
+}
+
+#[cfg(test)]
+mod test {
+    use rand;
+
+    #[test]
+    fn solve_and_verify() {
+        let public_id: ::public_id::PublicId = rand::random();
+        assert!(super::verify(&public_id, 0u8, rand::random()));
+
+        let nonce = super::solve(&public_id, 8u8).unwrap();
+        assert!(super::verify(&public_id, 8u8, nonce));
+        // no lower nonce solves it
+        assert!((0..nonce).all(|other_nonce| !super::verify(&public_id, 8u8, other_nonce)));
+        // and also proves any lower difficulty
+        assert!(super::verify(&public_id, 4u8, nonce));
+    }
+
+    #[test]
+    fn leading_zero_bits() {
+        assert_eq!(super::leading_zero_bits(&[0u8, 0u8]), 16);
+        assert_eq!(super::leading_zero_bits(&[0u8, 1u8, 0u8]), 15);
+        assert_eq!(super::leading_zero_bits(&[128u8]), 0);
+        assert_eq!(super::leading_zero_bits(&[0u8, 16u8]), 11);
+    }
+}
@@ -22,6 +22,7 @@ use sodiumoxide::crypto::box_;
 use NameType;
 use error::RoutingError;
 use id::Id;
+use relocation::RelocationCertificate;
 use utils;
 use std::fmt::{Debug, Formatter, Error};
 use rand;
@@ -32,6 +33,7 @@ pub struct PublicId {
     public_encrypt_key: box_::PublicKey,
     public_sign_key: sign::PublicKey,
     name: NameType,
+    relocation: Option<RelocationCertificate>,
 }
 
 impl Debug for PublicId {
@@ -48,6 +50,7 @@ impl PublicId {
             public_encrypt_key: id.encrypting_public_key().clone(),
             public_sign_key: id.signing_public_key().clone(),
             name: id.name(),
+            relocation: id.relocation_certificate().cloned(),
         }
     }
 
@@ -79,15 +82,44 @@ impl PublicId {
         self.name = relocated_name;
     }
 
+    /// Attach the group signed certificate of the relocated name.
+    pub fn set_relocation_certificate(&mut self, certificate: RelocationCertificate) {
+        self.relocation = Some(certificate);
+    }
+
+    /// Return the certificate of the relocated name, if any.
+    pub fn relocation_certificate(&self) -> Option<&RelocationCertificate> {
+        self.relocation.as_ref()
+    }
+
     /// Return public signing key.
     pub fn signing_public_key(&self) -> sign::PublicKey {
         self.public_sign_key
     }
 
+    /// Return public encryption key.
+    pub fn encrypting_public_key(&self) -> box_::PublicKey {
+        self.public_encrypt_key
+    }
+
     /// Checks if the name is updated to a relocated name.
     pub fn is_relocated(&self) -> bool {
         self.name != utils::public_key_to_client_name(&self.public_sign_key)
     }
+
+    /// Checks if the name is proven to be ours: either it is the name a first node assigns itself
+    /// from its client name, or a relocated name certified by at least quorum_size members of the
+    /// group around it.
+    pub fn verify_relocation(&self, quorum_size: usize) -> bool {
+        if self.name == utils::first_node_name(&self.client_name()) {
+            return true;
+        };
+        match self.relocation {
+            Some(ref certificate) => *certificate.relocated_name() == self.name &&
+                                     certificate.verify(&self.public_sign_key, quorum_size),
+            None => false,
+        }
+    }
 }
 
 impl rand::Rand for PublicId {
@@ -173,4 +205,35 @@ mod test {
         // is no longer relocated
         assert!(!public_id.is_relocated());
     }
+
+    #[test]
+    fn verify_relocation() {
+        let mut public_id: ::public_id::PublicId = rand::random();
+        let relocated_name: ::NameType = rand::random();
+        let relocated_key = public_id.signing_public_key();
+
+        // neither an original nor an uncertified name is proven
+        assert!(!public_id.verify_relocation(1usize));
+        public_id.assign_relocated_name(relocated_name.clone());
+        assert!(!public_id.verify_relocation(1usize));
+
+        let mut certificate = ::relocation::RelocationCertificate::new(relocated_name.clone());
+        for _ in 0..2 {
+            let signature = ::relocation::RelocationSignature::new(&relocated_key,
+                &relocated_name, &::id::Id::new()).unwrap();
+            assert!(certificate.add_signature(&relocated_key, signature));
+        }
+        public_id.set_relocation_certificate(certificate.clone());
+        assert!(public_id.verify_relocation(2usize));
+        assert!(!public_id.verify_relocation(3usize));
+
+        // the certificate does not prove any other name
+        public_id.assign_relocated_name(rand::random());
+        assert!(!public_id.verify_relocation(1usize));
+
+        // the first node name is proven by the key
+        let client_name = public_id.client_name();
+        public_id.assign_relocated_name(::utils::first_node_name(&client_name));
+        assert!(public_id.verify_relocation(2usize));
+    }
 }
@@ -0,0 +1,169 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use sodiumoxide::crypto::sign;
+
+use NameType;
+use error::RoutingError;
+use id::Id;
+
+/// Signature of a member of the close group of a relocated name over that relocation.  It binds
+/// the relocated name to the signing key of the relocated node, so it can't be reused for another
+/// node.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct RelocationSignature {
+    signer: NameType,
+    signer_key: sign::PublicKey,
+    signature: sign::Signature,
+}
+
+impl RelocationSignature {
+    /// Sign the relocation of the node with the given signing key to the relocated name, as the
+    /// node with the given Id.
+    pub fn new(relocated_key: &sign::PublicKey, relocated_name: &NameType, signer: &Id)
+               -> Result<RelocationSignature, RoutingError> {
+        let body = try!(signed_body(relocated_key, relocated_name));
+        Ok(RelocationSignature {
+            signer: signer.name(),
+            signer_key: signer.signing_public_key(),
+            signature: sign::sign_detached(&body, signer.signing_private_key()),
+        })
+    }
+
+    /// Name of the group member that signed.
+    pub fn signer(&self) -> &NameType {
+        &self.signer
+    }
+
+    /// Public signing key of the group member that signed.
+    pub fn signer_key(&self) -> &sign::PublicKey {
+        &self.signer_key
+    }
+
+    /// Verify the signature for the relocation of the node with the given signing key to the
+    /// relocated name.
+    pub fn verify(&self, relocated_key: &sign::PublicKey, relocated_name: &NameType) -> bool {
+        match signed_body(relocated_key, relocated_name) {
+            Ok(body) => sign::verify_detached(&self.signature, &body, &self.signer_key),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Certificate of a relocated name, holding the signatures of the close group members of that
+/// name which accepted the relocation.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct RelocationCertificate {
+    relocated_name: NameType,
+    signatures: Vec<RelocationSignature>,
+}
+
+impl RelocationCertificate {
+    /// Construct a certificate for the relocated name without signatures.
+    pub fn new(relocated_name: NameType) -> RelocationCertificate {
+        RelocationCertificate { relocated_name: relocated_name, signatures: Vec::new() }
+    }
+
+    /// The relocated name this certificate is for.
+    pub fn relocated_name(&self) -> &NameType {
+        &self.relocated_name
+    }
+
+    /// The signatures collected so far.
+    pub fn signatures(&self) -> &Vec<RelocationSignature> {
+        &self.signatures
+    }
+
+    /// Add the signature of a group member, if it is valid for the node with the given signing
+    /// key and no signature of that member is held yet.  Returns true if the signature was added.
+    pub fn add_signature(&mut self, relocated_key: &sign::PublicKey,
+                         signature: RelocationSignature) -> bool {
+        if !signature.verify(relocated_key, &self.relocated_name) ||
+           self.signatures.iter().any(|held| held.signer == signature.signer) {
+            return false;
+        };
+        self.signatures.push(signature);
+        true
+    }
+
+    /// Verify that at least quorum_size distinct group members, other than the relocated node
+    /// itself, signed the relocation of the node with the given signing key.
+    pub fn verify(&self, relocated_key: &sign::PublicKey, quorum_size: usize) -> bool {
+        let mut signers = Vec::<NameType>::new();
+        for signature in self.signatures.iter() {
+            if signature.signer == self.relocated_name || signers.contains(&signature.signer) ||
+               !signature.verify(relocated_key, &self.relocated_name) {
+                return false;
+            };
+            signers.push(signature.signer.clone());
+        }
+        signers.len() >= ::std::cmp::max(1usize, quorum_size)
+    }
+}
+
+fn signed_body(relocated_key: &sign::PublicKey, relocated_name: &NameType)
+               -> Result<Vec<u8>, RoutingError> {
+    Ok(try!(::utils::encode(&(relocated_key, relocated_name))))
+}
+
+#[cfg(test)]
+mod test {
+    use rand;
+    use super::{RelocationCertificate, RelocationSignature};
+
+    #[test]
+    fn quorum_of_signatures() {
+        let relocated = ::id::Id::new();
+        let relocated_key = relocated.signing_public_key();
+        let relocated_name: ::NameType = rand::random();
+        let mut certificate = RelocationCertificate::new(relocated_name.clone());
+        assert!(!certificate.verify(&relocated_key, 2usize));
+
+        let signer = ::id::Id::new();
+        let signature = RelocationSignature::new(&relocated_key, &relocated_name, &signer)
+                            .unwrap();
+        assert!(certificate.add_signature(&relocated_key, signature.clone()));
+        // a second signature of the same member is not counted
+        assert!(!certificate.add_signature(&relocated_key, signature));
+        assert!(certificate.verify(&relocated_key, 1usize));
+        assert!(!certificate.verify(&relocated_key, 2usize));
+
+        let signature = RelocationSignature::new(&relocated_key, &relocated_name,
+                                                 &::id::Id::new()).unwrap();
+        assert!(certificate.add_signature(&relocated_key, signature));
+        assert_eq!(certificate.signatures().len(), 2usize);
+        assert!(certificate.verify(&relocated_key, 2usize));
+
+        // the certificate does not hold for any other node
+        let other = ::id::Id::new();
+        assert!(!certificate.verify(&other.signing_public_key(), 1usize));
+    }
+
+    #[test]
+    fn signature_for_other_relocation() {
+        let relocated = ::id::Id::new();
+        let relocated_key = relocated.signing_public_key();
+        let relocated_name: ::NameType = rand::random();
+        let signature = RelocationSignature::new(&relocated_key, &rand::random(),
+                                                 &::id::Id::new()).unwrap();
+        assert!(!signature.verify(&relocated_key, &relocated_name));
+        let mut certificate = RelocationCertificate::new(relocated_name);
+        assert!(!certificate.add_signature(&relocated_key, signature));
+        assert!(certificate.signatures().is_empty());
+    }
+}
@@ -16,19 +16,21 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use sodiumoxide;
-use std::sync::mpsc;
-use std::thread::spawn;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
 
 use action::Action;
+use ban_list::{BanList, Peer};
 use event::Event;
 use messages::SignedToken;
-use routing_node::RoutingNode;
+use routing_builder::RoutingBuilder;
+use snapshot::RoutingTableSnapshot;
+use transport::Transport;
 use data::{Data, DataRequest};
-use types::{Bytes, CacheOptions};
+use types::{Bytes, CacheOptions, RoutingConfig};
 use error::{RoutingError, ResponseError};
 use authority::Authority;
-use messages::{ExternalRequest, ExternalResponse, InternalRequest, Content};
+use messages::{ExternalRequest, ExternalResponse, InternalRequest, Content, MessageId};
 
 type RoutingResult = Result<(), RoutingError>;
 
@@ -39,106 +41,146 @@ type RoutingResult = Result<(), RoutingError>;
 #[derive(Clone)]
 pub struct Routing {
     action_sender: mpsc::Sender<Action>,
+    ban_list: Arc<Mutex<BanList>>,
+    snapshot: Arc<Mutex<RoutingTableSnapshot>>,
 }
 
 impl Routing {
     /// Starts a new RoutingIdentity, which will also start a new RoutingNode.
     /// The RoutingNode will attempt to achieve full routing node status.
-    /// The intial Routing object will have newly generated keys
-    pub fn new(event_sender: mpsc::Sender<Event>) -> Routing {
-        sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
-
-        let (action_sender, action_receiver) = mpsc::channel::<Action>();
+    /// The intial Routing object will have newly generated keys.
+    /// The routing_config sets the group size, quorum size and threshold policy of the network.
+    pub fn new(event_sender: mpsc::Sender<Event>, routing_config: RoutingConfig) -> Routing {
+        RoutingBuilder::new().routing_config(routing_config).build(event_sender)
+    }
 
-        // start the handler for routing without a restriction to become a full node
-        let mut routing_node =
-            RoutingNode::new(action_sender.clone(), action_receiver, event_sender, false, None);
+    /// Starts a new Routing with the Id persisted in the given file, or with a new Id if the file
+    /// does not exist yet.  Once the node is assigned a network name, the relocated Id is written
+    /// to the file.  If the Id carries a relocated name, the RoutingNode resumes that name in the
+    /// network instead of being relocated again.
+    pub fn with_id_file<P: AsRef<Path>>(event_sender: mpsc::Sender<Event>,
+                                        routing_config: RoutingConfig,
+                                        path: P) -> Routing {
+        RoutingBuilder::new().routing_config(routing_config).id_file(path).build(event_sender)
+    }
 
-        let _ = spawn(move || {
-            debug!("Started routing run().");
-            routing_node.run();
-            debug!("Routing node terminated running.");
-        });
+    /// Starts a new Routing on the given transport instead of the default crust::Service.
+    /// All events generated by the transport are expected to arrive on transport_receiver.
+    pub fn with_transport<T>(event_sender: mpsc::Sender<Event>,
+                             routing_config: RoutingConfig,
+                             transport: T,
+                             transport_receiver: mpsc::Receiver<::crust::Event>) -> Routing
+        where T: Transport + Send + 'static {
+        Routing::with_builder(event_sender, RoutingBuilder::new().routing_config(routing_config),
+                              transport, transport_receiver)
+    }
 
-        Routing { action_sender: action_sender }
+    /// Starts a new Routing with the settings of the builder on the given transport.
+    /// All events generated by the transport are expected to arrive on transport_receiver.
+    pub fn with_builder<T>(event_sender: mpsc::Sender<Event>,
+                           builder: RoutingBuilder,
+                           transport: T,
+                           transport_receiver: mpsc::Receiver<::crust::Event>) -> Routing
+        where T: Transport + Send + 'static {
+        let (action_sender, ban_list, snapshot) = ::routing_builder::start_node(builder,
+            event_sender, transport, transport_receiver);
+        Routing {
+            action_sender: action_sender,
+            ban_list: ban_list,
+            snapshot: snapshot,
+        }
     }
 
     /// Send a Get message with a DataRequest to an Authority, signed with given keys.
+    /// Returns the MessageId the response will carry.
     pub fn get_request(&self, our_authority: Authority, location: Authority,
-        data_request: DataRequest) {
-        let _ = self.action_sender.send(Action::SendContent(
-                our_authority, location,
-                Content::ExternalRequest(ExternalRequest::Get(data_request, 0u8))));
+        data_request: DataRequest) -> MessageId {
+        self.send_request(our_authority, location, ExternalRequest::Get(data_request, 0u8), None)
     }
 
-    /// Add something to the network
-    pub fn put_request(&self, our_authority: Authority, location: Authority, data: Data) {
-        let _ = self.action_sender.send(Action::SendContent(
-                our_authority, location,
-                Content::ExternalRequest(ExternalRequest::Put(data))));
+    /// Add something to the network.  Returns the MessageId a response will carry.
+    pub fn put_request(&self, our_authority: Authority, location: Authority, data: Data)
+        -> MessageId {
+        self.send_request(our_authority, location, ExternalRequest::Put(data), None)
     }
 
-    /// Change something already on the network
-    pub fn post_request(&self, our_authority: Authority, location: Authority, data: Data) {
-        let _ = self.action_sender.send(Action::SendContent(
-                our_authority, location,
-                Content::ExternalRequest(ExternalRequest::Post(data))));
+    /// Change something already on the network.  Returns the MessageId a response will carry.
+    pub fn post_request(&self, our_authority: Authority, location: Authority, data: Data)
+        -> MessageId {
+        self.send_request(our_authority, location, ExternalRequest::Post(data), None)
     }
 
-    /// Remove something from the network
-    pub fn delete_request(&self, our_authority: Authority, location: Authority, data: Data) {
-        let _ = self.action_sender.send(Action::SendContent(
-                our_authority, location,
-                Content::ExternalRequest(ExternalRequest::Delete(data))));
+    /// Remove something from the network.  Returns the MessageId a response will carry.
+    pub fn delete_request(&self, our_authority: Authority, location: Authority, data: Data)
+        -> MessageId {
+        self.send_request(our_authority, location, ExternalRequest::Delete(data), None)
     }
+
+    /// Send a request as a group in reaction to the request with the MessageId cause, e.g. a Put
+    /// passed on by a ClientManager.  Identical requests made for different causes are told
+    /// apart.  Returns the MessageId a response will carry.
+    pub fn group_request(&self, our_authority: Authority, location: Authority,
+        request: ExternalRequest, cause: MessageId) -> MessageId {
+        self.send_request(our_authority, location, request, Some(cause))
+    }
+
     /// Respond to a get_request (no error can be sent)
     /// If we received the request from a group, we'll not get the signed_token.
+    /// The message_id is the one received with the request.
     pub fn get_response(&self,
                         our_authority: Authority,
                         location: Authority,
                         data: Data,
                         data_request: DataRequest,
-                        signed_token: Option<SignedToken>) {
+                        signed_token: Option<SignedToken>,
+                        message_id: MessageId) {
         let _ = self.action_sender.send(Action::SendContent(
                 our_authority, location,
                 Content::ExternalResponse(
-                    ExternalResponse::Get(data, data_request, signed_token))));
+                    ExternalResponse::Get(data, data_request, signed_token)),
+                message_id));
     }
     /// response error to a put request
     pub fn put_response(&self,
                         our_authority: Authority,
                         location: Authority,
                         response_error: ResponseError,
-                        signed_token: Option<SignedToken>) {
+                        signed_token: Option<SignedToken>,
+                        message_id: MessageId) {
         if response_error == ::error::ResponseError::Abort { return; };
         let _ = self.action_sender.send(Action::SendContent(
                 our_authority, location,
                 Content::ExternalResponse(
-                    ExternalResponse::Put(response_error, signed_token))));
+                    ExternalResponse::Put(response_error, signed_token)),
+                message_id));
     }
     /// Response error to a post request
     pub fn post_response(&self,
                         our_authority: Authority,
                          location: Authority,
                          response_error: ResponseError,
-                         signed_token: Option<SignedToken>) {
+                         signed_token: Option<SignedToken>,
+                         message_id: MessageId) {
         if response_error == ::error::ResponseError::Abort { return; };
         let _ = self.action_sender.send(Action::SendContent(
                 our_authority, location,
                 Content::ExternalResponse(
-                    ExternalResponse::Post(response_error, signed_token))));
+                    ExternalResponse::Post(response_error, signed_token)),
+                message_id));
     }
     /// response error to a delete respons
     pub fn delete_response(&self,
                            our_authority: Authority,
                            location: Authority,
                            response_error: ResponseError,
-                           signed_token: Option<SignedToken>) {
+                           signed_token: Option<SignedToken>,
+                           message_id: MessageId) {
         if response_error == ::error::ResponseError::Abort { return; };
         let _ = self.action_sender.send(Action::SendContent(
                 our_authority, location,
                 Content::ExternalResponse(ExternalResponse::Delete(response_error,
-                    signed_token))));
+                    signed_token)),
+                message_id));
     }
 
     /// Refresh the content in the close group nodes of group address content::name.
@@ -151,8 +193,10 @@ impl Routing {
             error!("refresh request (type_tag {:?}) can only be made as a group authority: {:?}",
                 type_tag, our_authority);
             return; };
+        let content = Content::InternalRequest(InternalRequest::Refresh(type_tag, content, cause));
+        let message_id = MessageId::for_request(&our_authority, &our_authority, &content, None);
         let _ = self.action_sender.send(Action::SendContent(our_authority.clone(), our_authority,
-            Content::InternalRequest(InternalRequest::Refresh(type_tag, content, cause))));
+            content, message_id));
     }
 
     /// Dynamically enable/disable caching for Data types.
@@ -161,11 +205,53 @@ impl Routing {
 
     }
 
+    /// The peers currently banned for misbehaviour, with the time left on their bans.
+    pub fn banned_peers(&self) -> Vec<(Peer, ::time::Duration)> {
+        match self.ban_list.lock() {
+            Ok(mut ban_list) => ban_list.banned(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Lift the ban on a peer and forget its misbehaviour.  Returns true if the peer was banned.
+    pub fn unban_peer(&self, peer: &Peer) -> bool {
+        match self.ban_list.lock() {
+            Ok(mut ban_list) => ban_list.clear(peer),
+            Err(_) => false,
+        }
+    }
+
+    /// Lift all bans and forget all misbehaviour.
+    pub fn clear_ban_list(&self) {
+        if let Ok(mut ban_list) = self.ban_list.lock() {
+            ban_list.clear_all();
+        };
+    }
+
+    /// A read-only copy of the routing table with our name and state, as last published by the
+    /// RoutingNode.  It lists every contact with its endpoints, connection, bucket index, probe
+    /// statistics and whether it is a member of our close group.
+    pub fn routing_table_snapshot(&self) -> RoutingTableSnapshot {
+        match self.snapshot.lock() {
+            Ok(snapshot) => snapshot.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
     /// Signal to RoutingNode that it needs to refuse new messages and handle all outstanding
     /// messages.  After handling all messages it will send an Event::Terminated to the user.
     pub fn stop(&mut self) {
         let _ = self.action_sender.send(Action::Terminate);
     }
+
+    fn send_request(&self, our_authority: Authority, location: Authority,
+        request: ExternalRequest, cause: Option<MessageId>) -> MessageId {
+        let content = Content::ExternalRequest(request);
+        let message_id = MessageId::for_request(&our_authority, &location, &content, cause);
+        let _ = self.action_sender.send(Action::SendContent(our_authority, location, content,
+            message_id));
+        message_id
+    }
 }
 
 
@@ -0,0 +1,305 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use sodiumoxide;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::spawn;
+
+use action::Action;
+use ban_list::BanList;
+use event::Event;
+use id::Id;
+use routing::Routing;
+use routing_client::RoutingClient;
+use routing_node::RoutingNode;
+use snapshot::RoutingTableSnapshot;
+use transport::Transport;
+use types::{CacheOptions, ExpiryDurations, RequestPolicy, RoutingConfig};
+
+/// RoutingBuilder collects the settings of a routing node or client before it is started.
+/// Settings which are not given fall back to the defaults used by Routing::new and
+/// RoutingClient::new.
+pub struct RoutingBuilder {
+    keys: Option<Id>,
+    id_file: Option<PathBuf>,
+    bootstrap_endpoints: Vec<::crust::Endpoint>,
+    bootstrap_cache_file: Option<PathBuf>,
+    listening_ports: Vec<::crust::Port>,
+    cache_options: CacheOptions,
+    request_policy: RequestPolicy,
+    expiry_durations: ExpiryDurations,
+    routing_config: RoutingConfig,
+    client_restriction: bool,
+    encrypt_content: bool,
+}
+
+impl RoutingBuilder {
+
+    /// Construct with the default settings.
+    pub fn new() -> RoutingBuilder {
+        RoutingBuilder {
+            keys: None,
+            id_file: None,
+            bootstrap_endpoints: Vec::new(),
+            bootstrap_cache_file: None,
+            listening_ports: Vec::new(),
+            cache_options: CacheOptions::no_caching(),
+            request_policy: RequestPolicy::default_policy(),
+            expiry_durations: ExpiryDurations::default_durations(),
+            routing_config: RoutingConfig::default_config(),
+            client_restriction: false,
+            encrypt_content: false,
+        }
+    }
+
+    /// Use the given keys instead of generating new ones.  If the Id already carries a relocated
+    /// name, a node resumes that name instead of requesting a new one; a client can not be started
+    /// with a relocated Id.
+    pub fn keys(mut self, keys: Id) -> RoutingBuilder {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Persist the Id of the node in the given file.  If the file exists and no keys are given,
+    /// the Id is loaded from it; once the node is assigned a network name, the relocated Id is
+    /// written to it, so a restarted node keeps its keys and name.
+    pub fn id_file<P: AsRef<Path>>(mut self, path: P) -> RoutingBuilder {
+        self.id_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Bootstrap by connecting to the given endpoints instead of using the contacts found by
+    /// crust.
+    pub fn bootstrap_endpoints(mut self, endpoints: Vec<::crust::Endpoint>) -> RoutingBuilder {
+        self.bootstrap_endpoints = endpoints;
+        self
+    }
+
+    /// Keep the endpoints of routing table peers in the given file.  On the next start they are
+    /// tried before the contacts found by crust.  Has no effect if bootstrap endpoints are given.
+    pub fn bootstrap_cache_file<P: AsRef<Path>>(mut self, path: P) -> RoutingBuilder {
+        self.bootstrap_cache_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Accept connections on the given ports instead of on the default ports.
+    pub fn listening_ports(mut self, ports: Vec<::crust::Port>) -> RoutingBuilder {
+        self.listening_ports = ports;
+        self
+    }
+
+    /// Set the caching behaviour of the node.
+    pub fn cache_options(mut self, cache_options: CacheOptions) -> RoutingBuilder {
+        self.cache_options = cache_options;
+        self
+    }
+
+    /// Set how long Get requests wait for a response and how often they are retried.
+    pub fn request_policy(mut self, request_policy: RequestPolicy) -> RoutingBuilder {
+        self.request_policy = request_policy;
+        self
+    }
+
+    /// Set how long the node remembers filtered messages, partially accumulated messages and the
+    /// public ids of its peers.
+    pub fn expiry_durations(mut self, expiry_durations: ExpiryDurations) -> RoutingBuilder {
+        self.expiry_durations = expiry_durations;
+        self
+    }
+
+    /// Set the group size, quorum size and threshold policy of the network.
+    pub fn routing_config(mut self, routing_config: RoutingConfig) -> RoutingBuilder {
+        self.routing_config = routing_config;
+        self
+    }
+
+    /// If set, the node will only bootstrap and not attempt to become a full routing node.
+    /// Clients are always restricted.
+    pub fn client_restriction(mut self, client_restriction: bool) -> RoutingBuilder {
+        self.client_restriction = client_restriction;
+        self
+    }
+
+    /// If set, external content sent to a single node or client is encrypted for it when its
+    /// encryption key is known, and a client asks for its responses to be encrypted, so that
+    /// relays can not read them.
+    pub fn encrypt_content(mut self, encrypt_content: bool) -> RoutingBuilder {
+        self.encrypt_content = encrypt_content;
+        self
+    }
+
+    /// Start a Routing node on the default crust transport.
+    pub fn build(self, event_sender: mpsc::Sender<Event>) -> Routing {
+        let (crust_service, crust_receiver) = ::transport::start_crust_service();
+        Routing::with_builder(event_sender, self, crust_service, crust_receiver)
+    }
+
+    /// Start a RoutingClient on the default crust transport.
+    pub fn build_client(self, event_sender: mpsc::Sender<Event>) -> RoutingClient {
+        let (crust_service, crust_receiver) = ::transport::start_crust_service();
+        RoutingClient::with_builder(event_sender, self, crust_service, crust_receiver)
+    }
+}
+
+/// Start a RoutingNode with the settings of the builder on its own thread, returns the sender
+/// for its actions, the ban list it shares and the snapshot of its routing table it publishes.
+pub fn start_node<T>(builder: RoutingBuilder,
+                     event_sender: mpsc::Sender<Event>,
+                     transport: T,
+                     transport_receiver: mpsc::Receiver<::crust::Event>)
+                     -> (mpsc::Sender<Action>, Arc<Mutex<BanList>>,
+                         Arc<Mutex<RoutingTableSnapshot>>)
+    where T: Transport + Send + 'static {
+    sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
+
+    let (action_sender, action_receiver) = mpsc::channel::<Action>();
+
+    let keys = match (builder.keys, builder.id_file.as_ref()) {
+        (Some(keys), _) => Some(keys),
+        (None, Some(path)) if path.exists() => match Id::read_file(path) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                error!("Failed to read id from {:?}: {:?}. Starting with a new id.", path, e);
+                None
+            },
+        },
+        (None, _) => None,
+    };
+
+    let mut routing_node = RoutingNode::with_transport(action_sender.clone(), action_receiver,
+        event_sender, builder.client_restriction, keys, builder.routing_config,
+        builder.bootstrap_endpoints, builder.listening_ports, transport, transport_receiver);
+    if let Some(id_file) = builder.id_file {
+        routing_node.set_id_file(id_file);
+    };
+    if let Some(bootstrap_cache_file) = builder.bootstrap_cache_file {
+        routing_node.set_bootstrap_cache_file(bootstrap_cache_file);
+    };
+    routing_node.set_cache_options(builder.cache_options);
+    routing_node.set_request_policy(builder.request_policy);
+    routing_node.set_expiry_durations(builder.expiry_durations);
+    routing_node.set_encrypt_content(builder.encrypt_content);
+    let ban_list = Arc::new(Mutex::new(BanList::new()));
+    routing_node.set_ban_list(ban_list.clone());
+    let snapshot = routing_node.snapshot();
+
+    let _ = spawn(move || {
+        debug!("Started routing run().");
+        routing_node.run();
+        debug!("Routing node terminated running.");
+    });
+
+    (action_sender, ban_list, snapshot)
+}
+
+#[cfg(test)]
+mod test {
+    use types::{CacheOptions, ExpiryDurations, RequestPolicy, RoutingConfig, ThresholdPolicy};
+
+    #[test]
+    fn defaults_and_settings() {
+        let builder = super::RoutingBuilder::new();
+        assert!(builder.keys.is_none());
+        assert!(builder.id_file.is_none());
+        assert!(builder.bootstrap_endpoints.is_empty());
+        assert!(builder.bootstrap_cache_file.is_none());
+        assert!(builder.listening_ports.is_empty());
+        assert_eq!(builder.cache_options, CacheOptions::no_caching());
+        assert_eq!(builder.request_policy, RequestPolicy::default_policy());
+        assert_eq!(builder.expiry_durations, ExpiryDurations::default_durations());
+        assert_eq!(builder.routing_config, RoutingConfig::default_config());
+        assert!(!builder.client_restriction);
+        assert!(!builder.encrypt_content);
+
+        let endpoint = ::crust::Endpoint::Tcp(::std::net::SocketAddr::V4(
+            ::std::net::SocketAddrV4::new(::std::net::Ipv4Addr::new(127, 0, 0, 1), 5483u16)));
+        let request_policy = RequestPolicy::new(::time::Duration::seconds(5), 0u32);
+        let routing_config = RoutingConfig::new(3usize, 2usize, ThresholdPolicy::Quorum).unwrap();
+        let expiry_durations = ExpiryDurations::new(::time::Duration::minutes(1),
+            ::time::Duration::minutes(2), ::time::Duration::minutes(3),
+            ::time::Duration::minutes(4));
+        let builder = super::RoutingBuilder::new()
+            .keys(::id::Id::new())
+            .id_file("routing.id")
+            .bootstrap_endpoints(vec![endpoint.clone()])
+            .bootstrap_cache_file("bootstrap.cache")
+            .listening_ports(vec![::crust::Port::Tcp(5483u16)])
+            .cache_options(CacheOptions::with_caching(true, false, false))
+            .request_policy(request_policy.clone())
+            .expiry_durations(expiry_durations.clone())
+            .routing_config(routing_config.clone())
+            .client_restriction(true)
+            .encrypt_content(true);
+        assert!(builder.keys.is_some());
+        assert_eq!(builder.id_file, Some(::std::path::PathBuf::from("routing.id")));
+        assert_eq!(builder.bootstrap_endpoints, vec![endpoint]);
+        assert_eq!(builder.bootstrap_cache_file,
+                   Some(::std::path::PathBuf::from("bootstrap.cache")));
+        assert_eq!(builder.listening_ports.len(), 1usize);
+        assert_eq!(builder.cache_options, CacheOptions::with_caching(true, false, false));
+        assert_eq!(builder.request_policy, request_policy);
+        assert_eq!(builder.expiry_durations, expiry_durations);
+        assert_eq!(builder.routing_config, routing_config);
+        assert!(builder.client_restriction);
+        assert!(builder.encrypt_content);
+    }
+
+    #[test]
+    fn bootstrap_off_given_endpoints() {
+        let network = ::test_utils::mock_network::Network::new();
+        let mut nodes = Vec::new();
+        let mut endpoints = Vec::new();
+        for _ in 0..2 {
+            let (event_sender, _event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+            let (transport, transport_receiver) = network.new_transport();
+            endpoints.push(transport.endpoint());
+            nodes.push(::routing::Routing::with_transport(event_sender,
+                RoutingConfig::default_config(), transport, transport_receiver));
+        }
+
+        // without bootstrap endpoints the mock network would connect us to the first node
+        network.hold_events();
+        let (event_sender, _event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let (transport, transport_receiver) = network.new_transport();
+        let endpoint = transport.endpoint();
+        nodes.push(::routing::Routing::with_builder(event_sender,
+            super::RoutingBuilder::new().bootstrap_endpoints(vec![endpoints[1].clone()]),
+            transport, transport_receiver));
+
+        let mut bootstrapped_to = None;
+        for _ in 0..1000 {
+            for (held_endpoint, event) in network.take_held_events() {
+                match event {
+                    ::crust::Event::OnConnect(ref connection, 0u32)
+                            if held_endpoint == endpoint && bootstrapped_to.is_none() =>
+                        bootstrapped_to = Some(connection.peer_endpoint()),
+                    _ => {},
+                };
+                network.deliver(&held_endpoint, event);
+            }
+            if bootstrapped_to.is_some() { break; };
+            ::std::thread::sleep_ms(10);
+        }
+        assert_eq!(bootstrapped_to, Some(endpoints[1].clone()));
+
+        for mut node in nodes {
+            node.stop();
+        }
+    }
+}
@@ -16,18 +16,19 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use sodiumoxide;
-use std::sync::mpsc;
-use std::thread::spawn;
+use std::sync::{mpsc, Arc, Mutex};
 
 use id::Id;
 use action::Action;
+use ban_list::{BanList, Peer};
 use event::Event;
-use routing_node::RoutingNode;
+use routing_builder::RoutingBuilder;
+use transport::Transport;
 use data::{Data, DataRequest};
 use error::RoutingError;
 use authority::Authority;
-use messages::{ExternalRequest, Content};
+use messages::{ExternalRequest, Content, MessageId};
+use types::{RequestPolicy, RoutingConfig};
 
 type RoutingResult = Result<(), RoutingError>;
 
@@ -37,6 +38,7 @@ type RoutingResult = Result<(), RoutingError>;
 #[derive(Clone)]
 pub struct RoutingClient {
     action_sender: mpsc::Sender<Action>,
+    ban_list: Arc<Mutex<BanList>>,
     get_counter: u8,
 }
 
@@ -46,53 +48,95 @@ impl RoutingClient {
     /// achieve full routing node status.
     /// If the client is started with a relocated id (ie the name has been reassigned),
     /// the core will instantely instantiate termination of the client.
-    pub fn new(event_sender: mpsc::Sender<Event>, keys: Option<Id>) -> RoutingClient {
-        sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
-
-        let (action_sender, action_receiver) = mpsc::channel::<Action>();
-
-        // start the handler for routing with a restriction to become a full node
-        let mut routing_node =
-            RoutingNode::new(action_sender.clone(), action_receiver, event_sender, true, keys);
+    /// The routing_config has to match the configuration of the network.
+    pub fn new(event_sender: mpsc::Sender<Event>, keys: Option<Id>,
+               routing_config: RoutingConfig) -> RoutingClient {
+        let builder = match keys {
+            Some(keys) => RoutingBuilder::new().keys(keys),
+            None => RoutingBuilder::new(),
+        };
+        builder.routing_config(routing_config).build_client(event_sender)
+    }
 
-        let _ = spawn(move || {
-            debug!("Started routing client run().");
-            routing_node.run();
-            debug!("Routing client node terminated running.");
-        });
+    /// Starts a new RoutingClient on the given transport instead of the default crust::Service.
+    /// All events generated by the transport are expected to arrive on transport_receiver.
+    pub fn with_transport<T>(event_sender: mpsc::Sender<Event>,
+                             keys: Option<Id>,
+                             routing_config: RoutingConfig,
+                             transport: T,
+                             transport_receiver: mpsc::Receiver<::crust::Event>) -> RoutingClient
+        where T: Transport + Send + 'static {
+        let builder = match keys {
+            Some(keys) => RoutingBuilder::new().keys(keys),
+            None => RoutingBuilder::new(),
+        };
+        RoutingClient::with_builder(event_sender, builder.routing_config(routing_config),
+                                    transport, transport_receiver)
+    }
 
-        RoutingClient { action_sender: action_sender, get_counter: 0u8 }
+    /// Starts a new RoutingClient with the settings of the builder on the given transport.  The
+    /// client restriction is always set.  All events generated by the transport are expected to
+    /// arrive on transport_receiver.
+    pub fn with_builder<T>(event_sender: mpsc::Sender<Event>,
+                           builder: RoutingBuilder,
+                           transport: T,
+                           transport_receiver: mpsc::Receiver<::crust::Event>) -> RoutingClient
+        where T: Transport + Send + 'static {
+        let (action_sender, ban_list, _) = ::routing_builder::start_node(
+            builder.client_restriction(true), event_sender, transport, transport_receiver);
+        RoutingClient { action_sender: action_sender, ban_list: ban_list, get_counter: 0u8 }
     }
 
     /// Send a Get message with a DataRequest to an Authority, signed with given keys.
-    pub fn get_request(&mut self, location: Authority, data_request: DataRequest) {
+    /// Returns the MessageId the response will carry.
+    pub fn get_request(&mut self, location: Authority, data_request: DataRequest) -> MessageId {
         self.get_counter = self.get_counter.wrapping_add(1);
-        let _ = self.action_sender.send(Action::ClientSendContent(
-                location,
-                Content::ExternalRequest(
-                    ExternalRequest::Get(data_request, self.get_counter))));
+        let get_counter = self.get_counter;
+        self.send_request(location, ExternalRequest::Get(data_request, get_counter))
     }
 
-    /// Add something to the network
-    pub fn put_request(&self, location: Authority, data: Data) {
+    /// Add something to the network.  Returns the MessageId a response will carry.
+    pub fn put_request(&self, location: Authority, data: Data) -> MessageId {
         debug!("Received put request from Client for {:?}", data);
-        let _ = self.action_sender.send(Action::ClientSendContent(
-                location,
-                Content::ExternalRequest(ExternalRequest::Put(data))));
+        self.send_request(location, ExternalRequest::Put(data))
     }
 
-    /// Change something already on the network
-    pub fn post_request(&self, location: Authority, data: Data) {
-        let _ = self.action_sender.send(Action::ClientSendContent(
-                location,
-                Content::ExternalRequest(ExternalRequest::Post(data))));
+    /// Change something already on the network.  Returns the MessageId a response will carry.
+    pub fn post_request(&self, location: Authority, data: Data) -> MessageId {
+        self.send_request(location, ExternalRequest::Post(data))
     }
 
-    /// Remove something from the network
-    pub fn delete_request(&self, location: Authority, data: Data) {
-        let _ = self.action_sender.send(Action::ClientSendContent(
-                location,
-                Content::ExternalRequest(ExternalRequest::Delete(data))));
+    /// Remove something from the network.  Returns the MessageId a response will carry.
+    pub fn delete_request(&self, location: Authority, data: Data) -> MessageId {
+        self.send_request(location, ExternalRequest::Delete(data))
+    }
+
+    /// Set how long to wait for responses to requests and how often to retry them.
+    pub fn set_request_policy(&self, request_policy: RequestPolicy) {
+        let _ = self.action_sender.send(Action::SetRequestPolicy(request_policy));
+    }
+
+    /// The peers currently banned for misbehaviour, with the time left on their bans.
+    pub fn banned_peers(&self) -> Vec<(Peer, ::time::Duration)> {
+        match self.ban_list.lock() {
+            Ok(mut ban_list) => ban_list.banned(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Lift the ban on a peer and forget its misbehaviour.  Returns true if the peer was banned.
+    pub fn unban_peer(&self, peer: &Peer) -> bool {
+        match self.ban_list.lock() {
+            Ok(mut ban_list) => ban_list.clear(peer),
+            Err(_) => false,
+        }
+    }
+
+    /// Lift all bans and forget all misbehaviour.
+    pub fn clear_ban_list(&self) {
+        if let Ok(mut ban_list) = self.ban_list.lock() {
+            ban_list.clear_all();
+        };
     }
 
     /// Signal to RoutingNode that it needs to refuse new messages and handle all outstanding
@@ -100,4 +144,11 @@ impl RoutingClient {
     pub fn stop(&mut self) {
         let _ = self.action_sender.send(Action::Terminate);
     }
+
+    fn send_request(&self, location: Authority, request: ExternalRequest) -> MessageId {
+        let message_id = MessageId::new();
+        let _ = self.action_sender.send(Action::ClientSendContent(
+                location, Content::ExternalRequest(request), message_id));
+        message_id
+    }
 }
@@ -16,9 +16,11 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::Sender;
 
 use crust;
+use lru_time_cache::LruCache;
 
 use routing_table::{RoutingTable, NodeInfo};
 use types::Address;
@@ -26,10 +28,14 @@ use authority;
 use authority::Authority;
 use id::Id;
 use public_id::PublicId;
+use relocation::RelocationCertificate;
+use snapshot::RoutingTableSnapshot;
 use NameType;
 use action::Action;
+use ban_list::{BanList, Misbehaviour, Peer};
 use event::Event;
 use messages::RoutingMessage;
+use types::RoutingConfig;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct Relay {
@@ -60,10 +66,11 @@ pub enum ConnectionName {
 /// Disconnected to Bootstrapped.  Once Bootstrapped it requires a relocated name provided by
 /// the network.  Once the name has been acquired, the state is Relocated and a routing table
 /// is initialised with this name.  Once routing connections with the network are established,
-/// the state is Connected.  Once more than ::types::GROUP_SIZE connections have been established,
+/// the state is Connected.  Once more than the configured group size of connections have been established,
 /// the state is marked as GroupConnected. If the routing connections are lost, the state returns
 /// to Disconnected and the routing table is destroyed.  If the node accepts an incoming connection
 /// while itself disconnected it can jump from Disconnected to Relocated (assigning itself a name).
+/// A node started with a persisted, relocated Id resumes that name once Bootstrapped.
 /// For a client the cycle is reduced to Disconnected and Bootstrapped.
 /// When the user calls ::stop(), the state is set to Terminated.
 #[allow(unused)]
@@ -75,14 +82,27 @@ pub enum State {
     Bootstrapped,
     /// There are only bootstrap connections, and we have received a name.
     Relocated,
-    /// There are 0 < n < GROUP_SIZE routing connections, and we have a name.
+    /// There are 0 < n < group size routing connections, and we have a name.
     Connected,
-    /// There are n >= GROUP_SIZE routing connections, and we have a name.
+    /// There are n >= group size routing connections, and we have a name.
     GroupConnected,
     /// ::stop() has been called.
     Terminated,
 }
 
+/// What checking the name a node connects under has shown.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum NameProof {
+    /// The name is certified by a quorum of signers we know.
+    Proven,
+    /// The name can not be checked with what we know yet, e.g. because we do not know its
+    /// signers.  The node may well be honest and try again later.
+    Unproven,
+    /// The proof is forged: a signature fails, or a signer signed under a key other than the one
+    /// we know for it.
+    Forged,
+}
+
 /// ExpectedConnection.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, RustcEncodable, RustcDecodable)]
 #[allow(unused)]
@@ -110,6 +130,15 @@ pub struct RoutingCore {
     // sender for signaling events and action
     event_sender: Sender<Event>,
     action_sender: Sender<Action>,
+    routing_config: RoutingConfig,
+    // the admission difficulty of our latest request for a network name
+    admission_difficulty: u8,
+    // public ids of nodes we learned of outside our routing table, such as the group that
+    // relocated us
+    public_id_cache: LruCache<NameType, PublicId>,
+    // nodes whose public ids we asked a contact for recently
+    requested_public_ids: ::utilities::ExpirationMap<NameType, ()>,
+    ban_list: Arc<Mutex<BanList>>,
 }
 
 #[allow(unused)]
@@ -117,17 +146,16 @@ impl RoutingCore {
     /// Start a RoutingCore with a new Id and the disabled RoutingTable
     pub fn new(event_sender: Sender<Event>,
                action_sender: Sender<Action>,
-               keys: Option<Id>)
+               keys: Option<Id>,
+               routing_config: RoutingConfig)
                -> RoutingCore {
         let id = match keys {
             Some(id) => id,
             None => Id::new(),
         };
-        // nodes are not persistant, and a client has no network allocated name
+        // a relocated id was persisted by a node and will resume its network name
         if id.is_relocated() {
-            error!("Core terminates routing as initialised with relocated id {:?}",
-                PublicId::new(&id));
-            let _ = action_sender.send(Action::Terminate);
+            info!("Core initialised with relocated id {:?}", PublicId::new(&id));
         };
 
         RoutingCore {
@@ -143,6 +171,13 @@ impl RoutingCore {
                 ::time::Duration::minutes(5)),
             event_sender: event_sender,
             action_sender: action_sender,
+            admission_difficulty: routing_config.admission_difficulty(),
+            public_id_cache: LruCache::with_expiry_duration(
+                ::types::ExpiryDurations::default_durations().public_id_cache()),
+            requested_public_ids: ::utilities::ExpirationMap::with_expiry_duration(
+                ::time::Duration::minutes(1)),
+            routing_config: routing_config,
+            ban_list: Arc::new(Mutex::new(BanList::new())),
         }
     }
 
@@ -151,6 +186,45 @@ impl RoutingCore {
         &self.id
     }
 
+    /// Share the ban list, so that it can be inspected and cleared by the user.
+    pub fn set_ban_list(&mut self, ban_list: Arc<Mutex<BanList>>) {
+        self.ban_list = ban_list;
+    }
+
+    /// Record misbehaviour of the peer on the connection, by its endpoint and, if we know it,
+    /// by its name.  A peer that gets banned is disconnected.
+    pub fn record_misbehaviour(&self, connection: &crust::Connection, misbehaviour: Misbehaviour) {
+        let mut peers = vec![Peer::Endpoint(connection.peer_endpoint())];
+        match self.lookup_connection(connection) {
+            Some(ConnectionName::Routing(name)) => peers.push(Peer::Name(name)),
+            Some(ConnectionName::Bootstrap(name)) => peers.push(Peer::Name(name)),
+            _ => {},
+        };
+        let mut banned = false;
+        if let Ok(mut ban_list) = self.ban_list.lock() {
+            for peer in peers {
+                banned = ban_list.record(peer, misbehaviour.clone()) || banned;
+            }
+        };
+        if banned {
+            let _ = self.action_sender.send(Action::DropConnections(vec![connection.clone()]));
+        };
+    }
+
+    /// Returns true if the peer on the connection, or the given name, is banned.
+    pub fn is_banned(&self, connection: &crust::Connection, name: &NameType) -> bool {
+        match self.ban_list.lock() {
+            Ok(mut ban_list) => ban_list.is_banned(&Peer::Endpoint(connection.peer_endpoint())) ||
+                ban_list.is_banned(&Peer::Name(name.clone())),
+            Err(_) => false,
+        }
+    }
+
+    /// Borrow the group parameters this node runs with.
+    pub fn routing_config(&self) -> &RoutingConfig {
+        &self.routing_config
+    }
+
     /// Returns Address::Node(network_given_name) or Address::Client(PublicKey) when no network name
     /// is given.
     pub fn our_address(&self) -> Address {
@@ -178,9 +252,9 @@ impl RoutingCore {
 
     /// Resets the full routing core to a disconnected state and will return a full list of all
     /// open connections to drop, if any should linger.  Resetting with persistant identity will
-    /// preserve the Id, only if it has not been relocated.
+    /// preserve the Id, including a relocated name.
     pub fn reset(&mut self, persistant: bool) -> Vec<::crust::Connection> {
-        if self.id.is_relocated() || !persistant {
+        if !persistant {
             self.id = ::id::Id::new(); };
         self.state = State::Disconnected;
         let mut open_connections = Vec::new();
@@ -237,11 +311,44 @@ impl RoutingCore {
         if !self.id.assign_relocated_name(network_name.clone()) {
             return false
         };
-        self.routing_table = Some(RoutingTable::new(&network_name));
+        self.enable_routing_table();
+        true
+    }
+
+    /// Assign the relocated name of a group signed certificate as our network name, keeping the
+    /// certificate to prove the name to our peers.  Returns false if no name can be assigned.
+    pub fn assign_relocation(&mut self, certificate: RelocationCertificate) -> bool {
+        let relocated_name = certificate.relocated_name().clone();
+        if !self.assign_network_name(&relocated_name) {
+            return false
+        };
+        self.id.set_relocation_certificate(certificate)
+    }
+
+    /// Resume the relocated name of a persisted Id as our network name, without requesting a new
+    /// name from the network.  Returns false if the Id is not relocated or a name is already
+    /// assigned.
+    pub fn resume_network_name(&mut self) -> bool {
+        match self.state {
+            State::Disconnected | State::Bootstrapped => {},
+            State::Relocated | State::Connected | State::GroupConnected
+                | State::Terminated => return false,
+        };
+        if !self.id.is_relocated() || self.routing_table.is_some() {
+            return false
+        };
+        debug!("Resuming network name {:?}", self.id.name());
+        self.enable_routing_table();
+        true
+    }
+
+    fn enable_routing_table(&mut self) {
+        let network_name = self.id.name();
+        self.routing_table = Some(RoutingTable::new(&network_name,
+            self.routing_config.group_size(), self.routing_config.bucket_size()));
         self.relay_map = Some(::utilities::ConnectionMap::new());
-        self.network_name = Some(network_name.clone());
+        self.network_name = Some(network_name);
         self.state = State::Relocated;
-        true
     }
 
     /// Currently wraps around RoutingCore::assign_network_name
@@ -304,14 +411,22 @@ impl RoutingCore {
                         let trigger_churn = routing_table
                             .address_in_our_close_group_range(&name);
                         let routing_table_count_prior = routing_table.size();
-                        routing_table.drop_node(&name);
+                        // connect to a replacement for the dropped contact, if one is known; it
+                        // is kept as a candidate until the connect request is sent
+                        match routing_table.drop_node(&name) {
+                            Some(candidate) => {
+                                let _ = self.action_sender.send(
+                                    Action::RefreshRoutingTable(candidate));
+                            },
+                            None => {},
+                        };
                         match routing_table_count_prior {
                             1usize => {
                                 error!("Routing Node has disconnected.");
                                 self.state = State::Disconnected;
                                 let _ = self.event_sender.send(Event::Disconnected);
                             },
-                            ::types::GROUP_SIZE => {
+                            count if count == self.routing_config.group_size() => {
                                 self.state = State::Connected;
                             },
                             _ => {},
@@ -417,13 +532,35 @@ impl RoutingCore {
                     connection: crust::Connection,
                     public_id: PublicId)
                     -> bool {
+        if self.is_banned(&connection, &public_id.name()) {
+            debug!("Refused banned peer {:?} on {:?}", public_id.name(), connection);
+            let _ = self.action_sender.send(Action::DropConnections(vec![connection]));
+            return false;
+        };
+        if let ConnectionName::Routing(ref routing_name) = identity {
+            let proof = if public_id.name() != *routing_name {
+                NameProof::Forged
+            } else {
+                self.verify_relocation(&public_id)
+            };
+            match proof {
+                NameProof::Proven => {},
+                NameProof::Unproven => {
+                    debug!("Refused {:?} whose name we can not check yet", public_id.name());
+                    return false;
+                },
+                NameProof::Forged => {
+                    self.record_misbehaviour(&connection, Misbehaviour::RoutingTableSpam);
+                    return false;
+                },
+            };
+        };
         let endpoint = connection.peer_endpoint();
 
         match identity {
             ConnectionName::Routing(routing_name) => {
                 match self.routing_table {
                     Some(ref mut routing_table) => {
-                        if public_id.name() != routing_name { return false; };
                         let trigger_churn = routing_table
                             .address_in_our_close_group_range(&routing_name);
                         let node_info = NodeInfo::new(public_id,
@@ -450,8 +587,8 @@ impl RoutingCore {
                                 // if we transition from zero to one routing connection
                                 info!("Routing Node has connected.");
                                 self.state = State::Connected;
-                            } else if routing_table_count_prior
-                                == ::types::GROUP_SIZE - 1usize {
+                            } else if routing_table_count_prior + 1usize
+                                == self.routing_config.group_size() {
                                 info!("Routing Node has connected to {:?} nodes.",
                                     routing_table.size());
                                 self.state = State::GroupConnected;
@@ -505,6 +642,24 @@ impl RoutingCore {
         }
     }
 
+    /// Remember a node name that didn't fit in our routing table as a replacement candidate, to
+    /// connect to once a contact in its bucket drops.  Returns true if the name is remembered.
+    pub fn add_candidate(&mut self, name: &NameType) -> bool {
+        match self.routing_table {
+            Some(ref mut routing_table) => routing_table.add_candidate(name),
+            None => false,
+        }
+    }
+
+    /// Forget a replacement candidate once we tried to connect to it.  Returns true if the name
+    /// was a candidate.
+    pub fn remove_candidate(&mut self, name: &NameType) -> bool {
+        match self.routing_table {
+            Some(ref mut routing_table) => routing_table.remove_candidate(name),
+            None => false,
+        }
+    }
+
     /// Check whether a certain identity is of interest to the core.
     /// For a Routing(NameType), the routing table will be consulted;
     /// for completeness we quote the documentation of RoutingTable::check_node below.
@@ -514,6 +669,8 @@ impl RoutingCore {
     /// For Bootstrap connections the relay map cannot be full and no routing table should exist;
     /// this logic is still under consideration [Ben 6/08/2015]
     /// For unidentified connections check_node always return true.
+    /// If the public id of a Routing peer is known, its name has to be proven by its relocation
+    /// certificate as well.
     /// Routing: "This is used to check whether it is worth while retrieving
     ///           a contact's public key from the PKI with a view to adding
     ///           the contact to our routing table.  The checking procedure is the
@@ -531,13 +688,21 @@ impl RoutingCore {
     /// 4 - if we can find a candidate for removal (a contact in a bucket with more than BUCKET_SIZE
     ///     contacts, which is also not within our close group), and if the new contact will fit in
     ///     a bucket closer to our own bucket, then we add the new contact."
-    pub fn check_node(&self, identity: &ConnectionName) -> bool {
+    pub fn check_node(&mut self, identity: &ConnectionName, public_id: Option<&PublicId>)
+            -> bool {
         match *identity {
             ConnectionName::Routing(name) => {
                 match self.state {
                     State::Disconnected => return false,
                     _ => {},
                 };
+                match public_id {
+                    Some(public_id) => if public_id.name() != name ||
+                                          self.verify_relocation(public_id) != NameProof::Proven {
+                        return false;
+                    },
+                    None => {},
+                };
                 match self.routing_table {
                     Some(ref routing_table) => routing_table.check_node(&name),
                     None => return false,
@@ -569,6 +734,134 @@ impl RoutingCore {
         }
     }
 
+    /// A node can only take a place in the routing table under a name it can prove: a relocated
+    /// name certified by a quorum of the group around it, or the name the first node of a network
+    /// gives itself, which is only accepted while our routing table is empty.  Only signers we
+    /// know, in range of the group as far as we can tell, count towards the quorum.  A node far
+    /// from us is often certified by nodes we do not know; their public ids are requested from
+    /// the contact closest to the relocated name, so that the node can be proven once they
+    /// arrive.  Only a failing signature or a signer key other than the one we know is forged.
+    pub fn verify_relocation(&mut self, public_id: &PublicId) -> NameProof {
+        if public_id.name() == ::utils::first_node_name(&public_id.client_name()) {
+            return if self.routing_table_size() == 0usize {
+                NameProof::Proven
+            } else {
+                NameProof::Unproven
+            };
+        };
+        let certificate = match public_id.relocation_certificate() {
+            Some(certificate) => certificate.clone(),
+            None => return NameProof::Unproven,
+        };
+        if certificate.signatures().is_empty() {
+            return NameProof::Unproven;
+        };
+        if *certificate.relocated_name() != public_id.name() ||
+           !certificate.verify(&public_id.signing_public_key(), 0usize) {
+            debug!("Relocation certificate of {:?} is forged", public_id);
+            return NameProof::Forged;
+        };
+        let quorum_size = ::std::cmp::max(1usize,
+            self.routing_config.relocation_quorum(self.routing_table_size() + 1));
+        let mut known_signers = 0usize;
+        let mut unknown_signers = Vec::new();
+        for signature in certificate.signatures() {
+            match self.known_public_id(signature.signer()) {
+                Some(signer) => {
+                    if signer.signing_public_key() != *signature.signer_key() {
+                        debug!("Relocation certificate of {:?} has a signer under another key",
+                            public_id);
+                        return NameProof::Forged;
+                    };
+                    if self.name_in_group_range(certificate.relocated_name(), signature.signer()) {
+                        known_signers += 1;
+                    };
+                },
+                None => unknown_signers.push(signature.signer().clone()),
+            };
+        }
+        if known_signers >= quorum_size {
+            return NameProof::Proven;
+        };
+        debug!("Relocated name of {:?} is certified by {} known signers only", public_id,
+            known_signers);
+        self.request_public_ids(certificate.relocated_name(), unknown_signers);
+        NameProof::Unproven
+    }
+
+    /// Cache the public id of a node we asked a contact for.  Returns false if we did not ask for
+    /// it, or if we know another public id for the node already.
+    pub fn cache_requested_public_id(&mut self, public_id: PublicId) -> bool {
+        if !self.requested_public_ids.contains_key(&public_id.name()) ||
+           self.known_public_id(&public_id.name()).is_some() {
+            return false;
+        };
+        self.cache_public_id(public_id);
+        true
+    }
+
+    /// Remember the public id of a node outside our routing table.
+    pub fn cache_public_id(&mut self, public_id: PublicId) {
+        let _ = self.public_id_cache.insert(public_id.name(), public_id);
+    }
+
+    /// The public id of a node, from the public id cache, our routing table or bootstrap
+    /// connections.
+    pub fn known_public_id(&mut self, name: &NameType) -> Option<PublicId> {
+        match self.public_id_cache.get(name) {
+            Some(public_id) => return Some(public_id.clone()),
+            None => {},
+        };
+        self.public_id(name)
+    }
+
+    /// Forget the cached public id of a node.
+    pub fn forget_public_id(&mut self, name: &NameType) {
+        let _ = self.public_id_cache.remove(name);
+    }
+
+    /// Keep cached public ids for the given duration, forgetting those cached so far.
+    pub fn set_public_id_cache_expiry(&mut self, expiry_duration: ::time::Duration) {
+        self.public_id_cache = LruCache::with_expiry_duration(expiry_duration);
+    }
+
+    // Ask the contact closest to the location, which is the most likely to know the nodes around
+    // it, for the public ids of the given nodes.  A node is asked for once in a while only.
+    fn request_public_ids(&mut self, location: &NameType, names: Vec<NameType>) {
+        let contact = match self.routing_table {
+            Some(ref routing_table) => routing_table.target_nodes(location).into_iter()
+                .map(|node_info| node_info.id())
+                .fold(None, |closest: Option<NameType>, name| match closest {
+                    Some(closest) => if ::closer_to_target(&name, &closest, location) {
+                        Some(name)
+                    } else {
+                        Some(closest)
+                    },
+                    None => Some(name),
+                }),
+            None => None,
+        };
+        let contact = match contact {
+            Some(contact) => contact,
+            None => return,
+        };
+        let mut unrequested = Vec::new();
+        for name in names {
+            if !self.requested_public_ids.contains_key(&name) {
+                let _ = self.requested_public_ids.insert(name.clone(), ());
+                unrequested.push(name);
+            };
+        }
+        if unrequested.is_empty() {
+            return;
+        };
+        let _ = self.action_sender.send(Action::SendContent(
+            Authority::ManagedNode(self.id.name()), Authority::ManagedNode(contact),
+            ::messages::Content::InternalRequest(
+                ::messages::InternalRequest::RequestPublicIds(unrequested)),
+            ::messages::MessageId::new()));
+    }
+
     /// Get the endpoints to send on as a node.  This will exclude the bootstrap connections
     /// we might have.  Endpoints returned here will expect us to send the message,
     /// as anything but a Client.  If to_authority is Client(_, public_key) and this client is
@@ -686,6 +979,15 @@ impl RoutingCore {
         }
     }
 
+    /// Returns true if the name can be a member of the group around the given location.  As a
+    /// client we have no routing table to place the name with, so any name is accepted.
+    pub fn name_in_group_range(&self, location: &NameType, name: &NameType) -> bool {
+        match self.routing_table {
+            Some(ref routing_table) => routing_table.is_in_group_range(location, name),
+            None => true,
+        }
+    }
+
     /// Our authority is defined by the routing message, if we are a full node;  if we are a client,
     /// this always returns Client authority (where the relay name is taken from the routing message
     /// destination)
@@ -744,6 +1046,73 @@ impl RoutingCore {
         }
     }
 
+    /// Returns the public id of a node we are connected to, either in our routing table or as
+    /// our bootstrap node.
+    pub fn public_id(&self, name: &NameType) -> Option<PublicId> {
+        match self.routing_table {
+            Some(ref rt) => match rt.public_id(name) {
+                Some(public_id) => return Some(public_id),
+                None => {},
+            },
+            None => {},
+        };
+        match self.bootstrap_map {
+            Some(ref bootstrap_map) => bootstrap_map.lookup_identity(name).0.cloned(),
+            None => None,
+        }
+    }
+
+    /// Returns the endpoints of the peers in our routing table that reliably answer liveness
+    /// probes, closest peers first.
+    pub fn reliable_routing_table_endpoints(&self) -> Vec<::crust::Endpoint> {
+        match self.routing_table {
+            Some(ref rt) => rt.reliable_endpoints(),
+            None => vec![],
+        }
+    }
+
+    /// Returns a read-only copy of our routing table, with our name and state.
+    pub fn routing_table_snapshot(&self) -> RoutingTableSnapshot {
+        RoutingTableSnapshot {
+            our_name: self.id.name(),
+            state: self.state.clone(),
+            contacts: match self.routing_table {
+                Some(ref rt) => rt.snapshot(),
+                None => vec![],
+            },
+        }
+    }
+
+    /// Returns the version of our routing table, which changes with its contacts.
+    pub fn routing_table_version(&self) -> Option<u64> {
+        self.routing_table.as_ref().map(|rt| rt.version())
+    }
+
+    /// Returns the connections to the peers in our routing table, closest peers first.
+    pub fn routing_table_connections(&self) -> Vec<::crust::Connection> {
+        match self.routing_table {
+            Some(ref rt) => rt.all_connections(),
+            None => vec![],
+        }
+    }
+
+    /// Record the round trip time measured on the connection of a peer in our routing table.
+    pub fn record_round_trip_time(&mut self, connection: &::crust::Connection,
+                                  round_trip_time: ::time::Duration) -> Option<NameType> {
+        match self.routing_table {
+            Some(ref mut rt) => rt.record_round_trip_time(connection, round_trip_time),
+            None => None,
+        }
+    }
+
+    /// Record a liveness probe left unanswered on the connection of a peer in our routing table.
+    pub fn record_missed_probe(&mut self, connection: &::crust::Connection) -> Option<NameType> {
+        match self.routing_table {
+            Some(ref mut rt) => rt.record_missed_probe(connection),
+            None => None,
+        }
+    }
+
     /// Check whether the connection can be matched against a stored ConnectRequest/ConnectResponse.
     pub fn match_expected_connection(&mut self, connection: &::crust::Connection)
             -> Option<ExpectedConnection> {
@@ -803,6 +1172,11 @@ impl RoutingCore {
     /// Check whether the connection has been accepted.
     pub fn match_unknown_connection(&mut self, connection: &::crust::Connection,
             hello: &::direct_messages::Hello) {
+        if self.is_banned(connection, &hello.public_id.name()) {
+            debug!("Refused banned peer {:?} on {:?}", hello.public_id.name(), connection);
+            let _ = self.action_sender.send(Action::DropConnections(vec![connection.clone()]));
+            return;
+        };
         match hello.confirmed_you {
             Some(ref address) => if !self.is_us(address) { return; },
             None => {},
@@ -858,7 +1232,15 @@ impl RoutingCore {
                     Some(::types::Address::Client(ref public_key)) => {
                         if self.add_peer(ConnectionName::Bootstrap(name.clone()),
                             connection.clone(), hello.public_id.clone()) {
-                            self.request_network_name(&name, &connection);
+                            if self.id.is_relocated() {
+                                // a persisted name only needs to connect back into the network
+                                if self.resume_network_name() {
+                                    let _ = self.action_sender.send(
+                                        ::action::Action::RefreshRoutingTable(name.clone()));
+                                };
+                            } else {
+                                self.request_network_name(&name, &connection);
+                            };
                         } else {
                             error!("Failed to add node {:?} as bootstrap connection on {:?}. \
                                 Dropping.", name, connection);
@@ -945,7 +1327,7 @@ impl RoutingCore {
                                     for (key, value) in self.unknown_connections.iter() {
                                         match value.0 {
                                             Some(ref value) => {
-                                                if *value == hello {
+                                                if value.matches(&hello) {
                                                     let _ = self.action_sender.send(
                                                         ::action::Action::DropConnections(
                                                             vec![*key]));
@@ -1004,7 +1386,7 @@ impl RoutingCore {
                                 for (key, value) in self.unknown_connections.iter() {
                                     match value.0 {
                                         Some(ref value) => {
-                                            if *value == hello {
+                                            if value.matches(&hello) {
                                                 primary_connection = Some(key.clone());
                                                 break;
                                             }
@@ -1125,9 +1507,10 @@ impl RoutingCore {
         }
     }
 
-    /// Add a bootstrap connection.
-    pub fn add_bootstrap_connection(&mut self, _connection: ::crust::Connection) {
-        unimplemented!();
+    /// Add a bootstrap connection.  The connection is only named once the Hello of the peer
+    /// arrives on it, at which point it is added to the bootstrap map.
+    pub fn add_bootstrap_connection(&mut self, connection: ::crust::Connection) {
+        debug!("Bootstrap connection {:?} awaits the Hello of the peer", connection);
     }
 
     /// Add an expected connection.
@@ -1166,11 +1549,50 @@ impl RoutingCore {
         }
         debug!("Will request a network name from bootstrap node {:?} on {:?}", bootstrap_name,
             bootstrap_connection);
-        let _ = self.action_sender.send(::action::Action::SendContent(
-            ::authority::Authority::Client(bootstrap_name.clone(), self.id.signing_public_key()),
-            ::authority::Authority::NaeManager(self.id.name()),
-            ::messages::Content::InternalRequest(::messages::InternalRequest::RequestNetworkName(
-                ::public_id::PublicId::new(&self.id)))));
+        let difficulty = self.admission_difficulty;
+        self.send_network_name_request(bootstrap_name, difficulty);
+    }
+
+    /// Request a network name again, with the admission work of the difficulty the network
+    /// required.  Returns false if we are not waiting for a name, have already requested it with
+    /// that much work, or the difficulty exceeds MAX_ADMISSION_DIFFICULTY.
+    pub fn retry_network_name(&mut self, bootstrap_name: &NameType, difficulty: u8) -> bool {
+        match self.state {
+            State::Bootstrapped => {},
+            _ => return false,
+        };
+        if difficulty <= self.admission_difficulty {
+            return false;
+        };
+        if difficulty > ::types::MAX_ADMISSION_DIFFICULTY {
+            error!("Refusing to solve admission work of difficulty {}", difficulty);
+            return false;
+        };
+        self.admission_difficulty = difficulty;
+        self.send_network_name_request(bootstrap_name, difficulty);
+        true
+    }
+
+    // The work can take a long time, so it is solved on its own thread, which sends the request
+    // once it has the nonce.
+    fn send_network_name_request(&self, bootstrap_name: &NameType, difficulty: u8) {
+        let public_id = ::public_id::PublicId::new(&self.id);
+        let from_authority = ::authority::Authority::Client(bootstrap_name.clone(),
+                                                            self.id.signing_public_key());
+        let to_authority = ::authority::Authority::NaeManager(self.id.name());
+        let action_sender = self.action_sender.clone();
+        let _ = ::std::thread::spawn(move || {
+            match ::proof_of_work::solve(&public_id, difficulty) {
+                Some(nonce) => {
+                    let _ = action_sender.send(::action::Action::SendContent(from_authority,
+                        to_authority,
+                        ::messages::Content::InternalRequest(
+                            ::messages::InternalRequest::RequestNetworkName(public_id, nonce)),
+                        ::messages::MessageId::new()));
+                },
+                None => error!("No nonce proves admission work of difficulty {}", difficulty),
+            }
+        });
     }
 }
 
@@ -1179,12 +1601,36 @@ mod test {
     use test_utils::test;
     use rand;
 
+    // A public id relocated to a random name, certified by quorum_size signers close to that name,
+    // whose public ids the routing core learns.
+    fn relocated_public_id(routing_core: &mut super::RoutingCore, quorum_size: usize)
+                           -> ::public_id::PublicId {
+        let mut id = ::id::Id::new();
+        let relocated_name: ::NameType = rand::random();
+        let relocated_key = id.signing_public_key();
+        let mut certificate = ::relocation::RelocationCertificate::new(relocated_name.clone());
+        for i in 0..quorum_size {
+            let mut signer = ::id::Id::new();
+            let mut signer_name = relocated_name.clone();
+            signer_name.0[::NAME_TYPE_LEN - 1] ^= (i + 1) as u8;
+            assert!(signer.assign_relocated_name(signer_name));
+            let signature = ::relocation::RelocationSignature::new(&relocated_key,
+                &relocated_name, &signer).unwrap();
+            assert!(certificate.add_signature(&relocated_key, signature));
+            routing_core.cache_public_id(::public_id::PublicId::new(&signer));
+        }
+        assert!(id.assign_relocated_name(relocated_name));
+        assert!(id.set_relocation_certificate(certificate));
+        ::public_id::PublicId::new(&id)
+    }
+
     #[test]
     fn add_peers_as_client() {
         let (event_sender, event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
         let (action_sender, action_receiver) = ::std::sync::mpsc::channel::<::action::Action>();
         let id = ::id::Id::new();
-        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id));
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id),
+            ::types::RoutingConfig::default_config());
 
         // routing core is not yet a full node, so it should not accept routing connections
         let public_id = ::public_id::PublicId::new(&::id::Id::new());
@@ -1203,18 +1649,107 @@ mod test {
         assert!(action_receiver.try_recv().is_err());
     }
 
+    #[test]
+    fn banned_peer_refused() {
+        let (event_sender, event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let (action_sender, action_receiver) = ::std::sync::mpsc::channel::<::action::Action>();
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender,
+            Some(::id::Id::new()), ::types::RoutingConfig::default_config());
+        let ban_list = ::std::sync::Arc::new(::std::sync::Mutex::new(
+            ::ban_list::BanList::new()));
+        routing_core.set_ban_list(ban_list.clone());
+
+        let public_id = ::public_id::PublicId::new(&::id::Id::new());
+        let connection = test::random_connection();
+        for _ in 0..2 {
+            let _ = ban_list.lock().unwrap().record(::ban_list::Peer::Name(public_id.name()),
+                ::ban_list::Misbehaviour::InvalidHello);
+        }
+        assert!(routing_core.is_banned(&connection, &public_id.name()));
+        let bootstrap_peer = super::ConnectionName::Bootstrap(public_id.name());
+        assert!(!routing_core.add_peer(bootstrap_peer, connection.clone(), public_id.clone()));
+        assert!(event_receiver.try_recv().is_err());
+        assert_eq!(action_receiver.try_recv(),
+                   Ok(::action::Action::DropConnections(vec![connection])));
+
+        // once cleared the peer is accepted
+        assert!(ban_list.lock().unwrap().clear(&::ban_list::Peer::Name(public_id.name())));
+        let bootstrap_peer = super::ConnectionName::Bootstrap(public_id.name());
+        assert!(routing_core.add_peer(bootstrap_peer, test::random_connection(), public_id));
+        assert_eq!(event_receiver.try_recv(), Ok(::event::Event::Bootstrapped));
+    }
+
+    #[test]
+    fn resume_relocated_id() {
+        let (event_sender, _event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let (action_sender, action_receiver) = ::std::sync::mpsc::channel::<::action::Action>();
+        let mut id = ::id::Id::new();
+        let relocated_name: ::NameType = rand::random();
+        assert!(id.assign_relocated_name(relocated_name.clone()));
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id),
+            ::types::RoutingConfig::default_config());
+        // a relocated id does not terminate routing
+        assert!(action_receiver.try_recv().is_err());
+
+        // the relocated name is taken on without requesting a new one
+        assert!(routing_core.resume_network_name());
+        assert_eq!(*routing_core.state(), super::State::Relocated);
+        assert_eq!(routing_core.our_address(), ::types::Address::Node(relocated_name.clone()));
+        assert!(!routing_core.resume_network_name());
+        assert!(!routing_core.assign_network_name(&rand::random()));
+
+        // a persistant reset keeps the relocated id
+        let _ = routing_core.reset(true);
+        assert_eq!(routing_core.id().name(), relocated_name);
+        let _ = routing_core.reset(false);
+        assert!(!routing_core.id().is_relocated());
+        assert!(!routing_core.resume_network_name());
+    }
+
+    #[test]
+    fn routing_table_snapshot() {
+        let (event_sender, _event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let (action_sender, _action_receiver) =
+            ::std::sync::mpsc::channel::<::action::Action>();
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender,
+            Some(::id::Id::new()), ::types::RoutingConfig::default_config());
+        let our_name = rand::random();
+        assert!(routing_core.assign_network_name(&our_name));
+        let snapshot = routing_core.routing_table_snapshot();
+        assert_eq!(snapshot.our_name, our_name);
+        assert!(snapshot.contacts.is_empty());
+        let version = routing_core.routing_table_version();
+        assert!(version.is_some());
+
+        let public_id = relocated_public_id(&mut routing_core, ::types::QUORUM_SIZE);
+        let connection = test::random_connection();
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        assert!(routing_core.add_peer(routing_peer, connection.clone(), public_id.clone()));
+        assert!(routing_core.routing_table_version() != version);
+        let snapshot = routing_core.routing_table_snapshot();
+        assert_eq!(snapshot.our_name, our_name);
+        assert_eq!(snapshot.state, super::State::Connected);
+        assert_eq!(snapshot.contacts.len(), 1);
+        assert_eq!(snapshot.contacts[0].name, public_id.name());
+        assert_eq!(snapshot.contacts[0].connection, Some(connection));
+        assert!(snapshot.contacts[0].is_connected());
+        assert!(snapshot.contacts[0].in_close_group);
+        assert_eq!(snapshot.close_group().len(), 1);
+    }
+
     #[test]
     fn add_peers_as_full_node() {
         let (event_sender, event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
         let (action_sender, action_receiver) = ::std::sync::mpsc::channel::<::action::Action>();
         let id = ::id::Id::new();
-        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id));
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id),
+            ::types::RoutingConfig::default_config());
 
         let our_name = rand::random();
         assert!(routing_core.assign_network_name(&our_name));
 
         // routing core is a full node, so it will accept routing connections and generate churn
-        let public_id = ::public_id::PublicId::new(&::id::Id::new());
+        let public_id = relocated_public_id(&mut routing_core, ::types::QUORUM_SIZE);
         let name = public_id.name();
         let connection = test::random_connection();
         let routing_peer = super::ConnectionName::Routing(public_id.name());
@@ -1242,7 +1777,7 @@ mod test {
 
         // now add connections until we reach group size -1 + ourselves
         for i in 1..::types::GROUP_SIZE - 1 {
-            let public_id = ::public_id::PublicId::new(&::id::Id::new());
+            let public_id = relocated_public_id(&mut routing_core, ::types::QUORUM_SIZE);
             let name = public_id.name();
             let connection = test::random_connection();
             let routing_peer = super::ConnectionName::Routing(public_id.name());
@@ -1261,7 +1796,7 @@ mod test {
         }
 
         // on reaching group size plus ourselves, core needs to signal we are connected
-        let public_id = ::public_id::PublicId::new(&::id::Id::new());
+        let public_id = relocated_public_id(&mut routing_core, ::types::QUORUM_SIZE);
         let name = public_id.name();
         let connection = test::random_connection();
         let routing_peer = super::ConnectionName::Routing(public_id.name());
@@ -1280,6 +1815,125 @@ mod test {
         assert!(action_receiver.try_recv().is_err());
     }
 
+    #[test]
+    fn uncertified_peer_refused() {
+        let (event_sender, _event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let (action_sender, action_receiver) = ::std::sync::mpsc::channel::<::action::Action>();
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender,
+            Some(::id::Id::new()), ::types::RoutingConfig::default_config());
+        assert!(routing_core.assign_network_name(&rand::random()));
+
+        // a node claiming a relocated name without a certificate is refused
+        let mut public_id = ::public_id::PublicId::new(&::id::Id::new());
+        public_id.assign_relocated_name(rand::random());
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        assert!(!routing_core.check_node(&routing_peer, Some(&public_id)));
+        assert!(!routing_core.add_peer(routing_peer, test::random_connection(), public_id));
+        assert!(action_receiver.try_recv().is_err());
+
+        // a certificate for another name does not prove the claimed name
+        let mut public_id = relocated_public_id(&mut routing_core, 1usize);
+        public_id.assign_relocated_name(rand::random());
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        assert!(!routing_core.check_node(&routing_peer, Some(&public_id)));
+
+        // a certificate signed by nodes we do not know proves nothing
+        let mut stranger_core = super::RoutingCore::new(::std::sync::mpsc::channel().0,
+            ::std::sync::mpsc::channel().0, None, ::types::RoutingConfig::default_config());
+        let public_id = relocated_public_id(&mut stranger_core, 1usize);
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        assert!(!routing_core.check_node(&routing_peer, Some(&public_id)));
+
+        // nor does one signed under a key other than the one we know for the signer
+        let public_id = relocated_public_id(&mut routing_core, 1usize);
+        let signer = public_id.relocation_certificate().unwrap().signatures()[0].signer().clone();
+        let mut impostor = ::id::Id::new();
+        assert!(impostor.assign_relocated_name(signer));
+        routing_core.cache_public_id(::public_id::PublicId::new(&impostor));
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        assert!(!routing_core.check_node(&routing_peer, Some(&public_id)));
+
+        // the name a first node gives itself is accepted while we know no other node
+        let mut first_public_id = ::public_id::PublicId::new(&::id::Id::new());
+        let first_node_name = ::utils::first_node_name(&first_public_id.client_name());
+        first_public_id.assign_relocated_name(first_node_name);
+        let first_peer = super::ConnectionName::Routing(first_public_id.name());
+        assert!(routing_core.check_node(&first_peer, Some(&first_public_id)));
+
+        // certified names are accepted
+        let public_id = relocated_public_id(&mut routing_core, 1usize);
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        assert!(routing_core.check_node(&routing_peer, Some(&public_id)));
+        assert!(routing_core.add_peer(routing_peer, test::random_connection(), public_id));
+
+        // once we know a node, nobody can claim to be the first
+        assert!(!routing_core.check_node(&first_peer, Some(&first_public_id)));
+    }
+
+    #[test]
+    fn unknown_signers_are_requested_not_charged() {
+        let (event_sender, _event_receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let (action_sender, action_receiver) = ::std::sync::mpsc::channel::<::action::Action>();
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender,
+            Some(::id::Id::new()), ::types::RoutingConfig::default_config());
+        assert!(routing_core.assign_network_name(&rand::random()));
+        let contact = relocated_public_id(&mut routing_core, 1usize);
+        let contact_name = contact.name();
+        assert!(routing_core.add_peer(super::ConnectionName::Routing(contact_name.clone()),
+            test::random_connection(), contact));
+        while action_receiver.try_recv().is_ok() {}
+
+        // a node certified by nodes we do not know is refused, but never charged for it
+        let mut stranger_core = super::RoutingCore::new(::std::sync::mpsc::channel().0,
+            ::std::sync::mpsc::channel().0, None, ::types::RoutingConfig::default_config());
+        let public_id = relocated_public_id(&mut stranger_core, 2usize);
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        let connection = test::random_connection();
+        for _ in 0..5 {
+            assert!(!routing_core.add_peer(routing_peer.clone(), connection.clone(),
+                public_id.clone()));
+        }
+        assert!(!routing_core.is_banned(&connection, &public_id.name()));
+
+        // the public ids of the signers are requested from our contact, once
+        let signers = public_id.relocation_certificate().unwrap().signatures().iter()
+            .map(|signature| signature.signer().clone()).collect::<Vec<_>>();
+        match action_receiver.try_recv() {
+            Ok(::action::Action::SendContent(::authority::Authority::ManagedNode(_),
+                    ::authority::Authority::ManagedNode(to_name),
+                    ::messages::Content::InternalRequest(
+                        ::messages::InternalRequest::RequestPublicIds(names)), _)) => {
+                assert_eq!(to_name, contact_name);
+                assert_eq!(names, signers);
+            },
+            _ => panic!("Should have requested the public ids of the signers."),
+        };
+        assert!(action_receiver.try_recv().is_err());
+
+        // only the public ids we asked for are cached, after which the node is proven
+        assert!(!routing_core.cache_requested_public_id(rand::random()));
+        for signer in signers.iter() {
+            let signer_public_id = stranger_core.known_public_id(signer).unwrap();
+            assert!(routing_core.cache_requested_public_id(signer_public_id.clone()));
+            assert!(!routing_core.cache_requested_public_id(signer_public_id));
+        }
+        assert!(routing_core.add_peer(routing_peer, connection, public_id));
+
+        // a node claiming a signer we know under another key is charged until banned
+        let public_id = relocated_public_id(&mut routing_core, 2usize);
+        let signer = public_id.relocation_certificate().unwrap().signatures()[0].signer().clone();
+        let mut impostor = ::id::Id::new();
+        assert!(impostor.assign_relocated_name(signer));
+        routing_core.cache_public_id(::public_id::PublicId::new(&impostor));
+        let routing_peer = super::ConnectionName::Routing(public_id.name());
+        let connection = test::random_connection();
+        for _ in 0..4 {
+            assert!(!routing_core.add_peer(routing_peer.clone(), connection.clone(),
+                public_id.clone()));
+        }
+        assert!(routing_core.is_banned(&connection, &public_id.name()));
+    }
+
     #[test]
     fn match_on_expected_connection() {
         let (event_sender, _) = ::std::sync::mpsc::channel::<::event::Event>();
@@ -1287,7 +1941,8 @@ mod test {
         let id = ::id::Id::new();
         let peer_id = ::id::Id::new();
         let peer_id_signing_private_key = peer_id.signing_private_key().clone();
-        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id));
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id),
+            ::types::RoutingConfig::default_config());
 
         assert!(routing_core.assign_network_name(&rand::random()));
 
@@ -1313,6 +1968,7 @@ mod test {
             to_authority: ::authority::Authority::ManagedNode(public_id.name()),
             content: ::messages::Content::InternalRequest(
                 ::messages::InternalRequest::Connect(connect_request)),
+            message_id: ::messages::MessageId::new(),
         };
         let signed_message = ::messages::SignedMessage::new(
             ::types::Address::Node(peer_public_id.name()), routing_message,
@@ -1329,10 +1985,13 @@ mod test {
         let peer_expected_connection = super::ExpectedConnection::Response(
                 connect_response.clone(), signed_token);
         let hello = ::direct_messages::Hello {
+            version: ::types::PROTOCOL_VERSION,
+            min_version: ::types::MIN_PROTOCOL_VERSION,
             address: ::types::Address::Node(peer_public_id.name()),
             public_id: peer_public_id.clone(),
             confirmed_you: None,
             expected_connection: Some(peer_expected_connection.clone()),
+            ephemeral_key: ::sodiumoxide::crypto::box_::gen_keypair().0,
         };
 
         let _ = routing_core.add_expected_connection(expected_connection.clone());
@@ -1352,7 +2011,8 @@ mod test {
         let id = ::id::Id::new();
         let peer_id = ::id::Id::new();
         let peer_id_signing_private_key = peer_id.signing_private_key().clone();
-        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id));
+        let mut routing_core = super::RoutingCore::new(event_sender, action_sender, Some(id),
+            ::types::RoutingConfig::default_config());
 
         assert!(routing_core.assign_network_name(&rand::random()));
 
@@ -1378,6 +2038,7 @@ mod test {
             to_authority: ::authority::Authority::ManagedNode(public_id.name()),
             content: ::messages::Content::InternalRequest(
                 ::messages::InternalRequest::Connect(connect_request)),
+            message_id: ::messages::MessageId::new(),
         };
         let signed_message = ::messages::SignedMessage::new(
             ::types::Address::Node(peer_public_id.name()), routing_message,
@@ -1394,10 +2055,13 @@ mod test {
         let peer_expected_connection = super::ExpectedConnection::Response(
                 connect_response.clone(), signed_token);
         let hello = ::direct_messages::Hello {
+            version: ::types::PROTOCOL_VERSION,
+            min_version: ::types::MIN_PROTOCOL_VERSION,
             address: ::types::Address::Node(peer_public_id.name()),
             public_id: peer_public_id.clone(),
             confirmed_you: None,
             expected_connection: Some(peer_expected_connection.clone()),
+            ephemeral_key: ::sodiumoxide::crypto::box_::gen_keypair().0,
         };
 
         let _ = routing_core.add_expected_connection(expected_connection.clone());
@@ -17,225 +17,517 @@
 // relating to use of the SAFE Network Software.
 
 use sodiumoxide::crypto;
-use std::cmp::min;
 
 use lru_time_cache::LruCache;
 
 use action::Action;
 use event::Event;
 use NameType;
-use routing_core::{RoutingCore, ConnectionName};
+use routing_core::{RoutingCore, ConnectionName, NameProof};
 use id::Id;
 use public_id::PublicId;
-use types;
-use types::{Bytes, Address, CacheOptions};
-use utils::{encode, decode};
+use types::{Bytes, Address, CacheOptions, ExpiryDurations, RequestPolicy, RoutingConfig};
+use utilities::ExpirationMap;
+use utils::decode;
 use utils;
 use data::{Data, DataRequest};
 use authority::{Authority, our_authority};
 
 use messages::{RoutingMessage, SignedMessage, SignedToken, ConnectRequest, ConnectResponse,
-               Content, ExternalRequest, ExternalResponse, InternalRequest, InternalResponse};
+               Content, ExternalRequest, ExternalResponse, InternalRequest, InternalResponse,
+               MessageId};
 
 use error::{RoutingError, InterfaceError};
+use transport::Transport;
 
 
 type RoutingResult = Result<(), RoutingError>;
 
+// Milliseconds before the first retry to connect to the bootstrap endpoints, doubled on every
+// further retry.
+const BOOTSTRAP_RETRY_MILLISECONDS: i64 = 500;
+// Attempts to connect to the bootstrap endpoints before falling back to the transport's own
+// bootstrap.
+const MAX_BOOTSTRAP_ATTEMPTS: u32 = 5;
+
+// A request of a client that has not been answered yet.
+#[derive(Clone)]
+struct PendingRequest {
+    location: Authority,
+    request: ExternalRequest,
+    attempt: u32,
+}
+
+/// The outcome of polling a RoutingNode once.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Poll {
+    /// There was no action and no transport event to handle.
+    Idle,
+    /// An action or a transport event has been handled.
+    Busy,
+    /// The node has terminated.
+    Terminated,
+}
+
 /// Routing Node
-pub struct RoutingNode {
-    // for CRUST
-    crust_receiver: ::std::sync::mpsc::Receiver<::crust::Event>,
-    crust_service: ::crust::Service,
+pub struct RoutingNode<T: Transport = ::crust::Service> {
+    // for the transport layer
+    transport_receiver: ::std::sync::mpsc::Receiver<::crust::Event>,
+    transport: T,
     accepting_on: Vec<::crust::Endpoint>,
+    bootstrap_endpoints: Vec<::crust::Endpoint>,
+    bootstrap_cache: Option<::bootstrap_cache::BootstrapCache>,
+    // failed attempts to connect to the bootstrap endpoints, and when the next one is due
+    bootstrap_attempts: u32,
+    bootstrap_retry: Option<::time::SteadyTime>,
     connection_counter: u32,
     // for RoutingNode
     client_restriction: bool,
+    persistant_id: bool,
+    id_file: Option<::std::path::PathBuf>,
     action_sender: ::std::sync::mpsc::Sender<Action>,
     action_receiver: ::std::sync::mpsc::Receiver<Action>,
     event_sender: ::std::sync::mpsc::Sender<Event>,
     filter: ::filter::Filter,
+    rate_limiter: ::filter::RateLimiter,
+    liveness: ::liveness::Liveness,
+    // the published copy of our routing table and the version it was taken of
+    snapshot: ::std::sync::Arc<::std::sync::Mutex<::snapshot::RoutingTableSnapshot>>,
+    snapshot_version: (Option<u64>, ::routing_core::State, NameType),
     connection_filter: ::message_filter::MessageFilter<::NameType>,
+    sessions: ::session::Sessions,
+    // the certificate of our relocated name, while the signatures of the group are collected
+    relocation: Option<::relocation::RelocationCertificate>,
     core: RoutingCore,
-    public_id_cache: LruCache<NameType, PublicId>,
+    encrypt_content: bool,
+    encrypting_keys: LruCache<crypto::sign::PublicKey, crypto::box_::PublicKey>,
     accumulator: ::message_accumulator::MessageAccumulator,
     refresh_accumulator: ::refresh_accumulator::RefreshAccumulator,
+    expiry_durations: ExpiryDurations,
     cache_options: CacheOptions,
     data_cache: Option<LruCache<NameType, Data>>,
+    request_policy: RequestPolicy,
+    pending_requests: ExpirationMap<MessageId, PendingRequest>,
 }
 
-impl RoutingNode {
+impl RoutingNode<::crust::Service> {
+    /// Start a RoutingNode on the default crust transport.
     pub fn new(action_sender: ::std::sync::mpsc::Sender<Action>,
                action_receiver: ::std::sync::mpsc::Receiver<Action>,
                event_sender: ::std::sync::mpsc::Sender<Event>,
                client_restriction: bool,
-               keys: Option<Id>)
-               -> RoutingNode {
+               keys: Option<Id>,
+               routing_config: RoutingConfig)
+               -> RoutingNode<::crust::Service> {
+
+        let (crust_service, crust_receiver) = ::transport::start_crust_service();
+        RoutingNode::with_transport(action_sender, action_receiver, event_sender,
+            client_restriction, keys, routing_config, vec![], vec![], crust_service,
+            crust_receiver)
+    }
+}
 
-        let (crust_sender, crust_receiver) = ::std::sync::mpsc::channel::<::crust::Event>();
-        let mut crust_service = match ::crust::Service::new(crust_sender) {
-            Ok(service) => service,
-            Err(what) => panic!(format!("Unable to start crust::Service {}", what)),
+impl<T: Transport> RoutingNode<T> {
+    /// Start a RoutingNode on the given transport.  All events of the transport are expected to
+    /// arrive on transport_receiver.  Without bootstrap_endpoints the transport's own bootstrap
+    /// contacts are used, without listening_ports the transport's default ports.
+    pub fn with_transport(action_sender: ::std::sync::mpsc::Sender<Action>,
+                          action_receiver: ::std::sync::mpsc::Receiver<Action>,
+                          event_sender: ::std::sync::mpsc::Sender<Event>,
+                          client_restriction: bool,
+                          keys: Option<Id>,
+                          routing_config: RoutingConfig,
+                          bootstrap_endpoints: Vec<::crust::Endpoint>,
+                          listening_ports: Vec<::crust::Port>,
+                          mut transport: T,
+                          transport_receiver: ::std::sync::mpsc::Receiver<::crust::Event>)
+                          -> RoutingNode<T> {
+
+        let accepting_on = if listening_ports.is_empty() {
+            transport.start_acceptors()
+        } else {
+            listening_ports.into_iter()
+                .flat_map(|port| transport.start_accepting(port))
+                .collect()
         };
 
-        let accepting_on = crust_service.start_default_acceptors().into_iter()
-                           .filter_map(|ep|ep.ok())
-                           .flat_map(::crust::ifaddrs_if_unspecified)
-                           .collect();
-
         // The above command will give us only internal endpoints on which
         // we're accepting. The next command will try to contact an IGD device
         // and create external mapping to those endpoints. The result
         // shall be returned async through the ExternalEndpoints event.
-        crust_service.get_external_endpoints();
+        transport.get_external_endpoints();
 
-        let core = RoutingCore::new(event_sender.clone(), action_sender.clone(), keys);
+        let core = RoutingCore::new(event_sender.clone(), action_sender.clone(), keys,
+            routing_config);
         info!("RoutingNode {:?} listens on {:?}", core.our_address(), accepting_on);
+        // a client has no network allocated name
+        let persistant_id = core.id().is_relocated();
+        if persistant_id && client_restriction {
+            error!("RoutingNode terminates as client initialised with relocated id {:?}",
+                PublicId::new(core.id()));
+            let _ = action_sender.send(Action::Terminate);
+        };
+        let expiry_durations = ExpiryDurations::default_durations();
+        let snapshot = core.routing_table_snapshot();
+        let snapshot_version = (core.routing_table_version(), snapshot.state.clone(),
+                                snapshot.our_name.clone());
 
         RoutingNode {
-            crust_receiver: crust_receiver,
-            crust_service: crust_service,
+            transport_receiver: transport_receiver,
+            transport: transport,
             accepting_on: accepting_on,
+            bootstrap_endpoints: bootstrap_endpoints,
+            bootstrap_cache: None,
+            bootstrap_attempts: 0u32,
+            bootstrap_retry: None,
             // connection counter starts at 1, 0 is reserved for bootstrapping
             connection_counter: 1u32,
             client_restriction: client_restriction,
+            persistant_id: persistant_id,
+            id_file: None,
             action_sender: action_sender.clone(),
             action_receiver: action_receiver,
             event_sender: event_sender.clone(),
-            filter: ::filter::Filter::with_expiry_duration(::time::Duration::minutes(20)),
+            filter: ::filter::Filter::with_expiry_duration(expiry_durations.filter()),
+            rate_limiter: ::filter::RateLimiter::with_expiry_duration(
+                core.routing_config().rate_limits().clone(), expiry_durations.filter()),
+            liveness: ::liveness::Liveness::with_interval(
+                ::time::Duration::seconds(::liveness::PING_INTERVAL_SECONDS)),
+            snapshot: ::std::sync::Arc::new(::std::sync::Mutex::new(snapshot)),
+            snapshot_version: snapshot_version,
             connection_filter: ::message_filter::MessageFilter::with_expiry_duration(
                 ::time::Duration::seconds(20)),
+            sessions: ::session::Sessions::new(),
+            relocation: None,
             core: core,
-            public_id_cache: LruCache::with_expiry_duration(::time::Duration::minutes(10)),
+            encrypt_content: false,
+            encrypting_keys: LruCache::with_expiry_duration(expiry_durations.public_id_cache()),
             accumulator: ::message_accumulator::MessageAccumulator::with_expiry_duration(
-                ::time::Duration::minutes(5)),
+                expiry_durations.message_accumulator()),
             refresh_accumulator: ::refresh_accumulator::RefreshAccumulator::with_expiry_duration(
-                ::time::Duration::minutes(5), event_sender),
+                expiry_durations.refresh_accumulator(), event_sender),
+            expiry_durations: expiry_durations,
             cache_options: CacheOptions::no_caching(),
             data_cache: None,
+            request_policy: RequestPolicy::default_policy(),
+            pending_requests: ExpirationMap::with_expiry_duration(
+                RequestPolicy::default_policy().timeout()),
         }
     }
 
     pub fn run(&mut self) {
-        self.crust_service.bootstrap(0u32);
+        self.bootstrap();
         debug!("run: RoutingNode started running and started crust bootstrapping.");
         loop {
-            match self.action_receiver.try_recv() {
-                Err(_) => {}
-                Ok(Action::SendMessage(signed_message)) => {
-                    ignore(self.message_received(signed_message));
-                }
-                Ok(Action::SendContent(our_authority, to_authority, content)) => {
-                    let _ = self.send_content(our_authority, to_authority, content);
-                },
-                Ok(Action::SendConfirmationHello(connection, address)) => {
-                    let _ = self.send_hello(connection, Some(address), None);
-                },
-                Ok(Action::ClientSendContent(to_authority, content)) => {
-                    debug!("ClientSendContent received for {:?}", content);
-                    let _ = self.client_send_content(to_authority, content);
-                },
-                Ok(Action::Churn(our_close_group, targets, cause)) => {
-                    let _ = self.generate_churn(our_close_group, targets, cause);
-                },
-                Ok(Action::SetCacheOptions(cache_options)) => {
-                    self.set_cache_options(cache_options);
-                },
-                Ok(Action::DropConnections(connections)) => {
-                    self.drop_connections(connections);
-                },
-                Ok(Action::MatchConnection(expected_connection, unknown_connection)) => {
-                    self.match_connection(expected_connection, unknown_connection);
-                },
-                Ok(Action::Rebootstrap) => {
-                    self.reset();
-                    ::std::thread::sleep_ms(100);
-                    self.crust_service.bootstrap(0u32);
-                },
-                Ok(Action::Terminate) => {
-                    debug!("routing node terminated");
-                    let _ = self.event_sender.send(Event::Terminated);
-                    self.crust_service.stop();
-                    break;
-                }
+            match self.poll() {
+                Poll::Terminated => break,
+                Poll::Busy | Poll::Idle => ::std::thread::sleep_ms(1),
             };
-            match self.crust_receiver.try_recv() {
-                Err(_) => {
-                    // FIXME (ben 16/08/2015) other reasons could induce an error
-                    // main error assumed now to be no new crust events
-                    // break;
-                }
-                Ok(::crust::Event::NewMessage(connection, bytes)) => {
-                    match decode::<SignedMessage>(&bytes) {
-                        Ok(message) => {
-                            // handle SignedMessage for any identified connection
-                            match self.core.lookup_connection(&connection) {
-                                Some(ConnectionName::Unidentified(_, _)) => debug!("message
-                                        from unidentified connection {:?}", connection),
-                                None => debug!("message from unknown connection {:?}",
-                                    connection),
-                                _ => ignore(self.message_received(message)),
-                            };
-                        }
-                        // The message received is not a Signed Routing Message,
-                        // expect it to be an Hello message to identify a connection
-                        Err(_) => {
-                            match decode::<::direct_messages::DirectMessage>(&bytes) {
-                                Ok(direct_message) => self.direct_message_received(
-                                        direct_message, connection),
-                                    _ => error!("Unparsable message received on {:?}", connection),
-                            };
-                        }
-                    };
-                }
-                Ok(::crust::Event::OnConnect(connection, _response_token)) => {
-                    self.handle_on_connect(connection);
-                }
-                Ok(::crust::Event::OnAccept(connection)) => {
-                    self.handle_on_accept(connection);
-                }
-                Ok(::crust::Event::OnRendezvousConnect(_connection, _response_token)) => {
-                    unimplemented!()
+        }
+    }
+
+    /// Handle at most one action and one transport event, then run the timers.  The node polls
+    /// itself when it runs; a simulation instead bootstraps the node and polls it directly.
+    pub fn poll(&mut self) -> Poll {
+        let mut poll = Poll::Idle;
+        match self.action_receiver.try_recv() {
+            Err(_) => {},
+            Ok(action) => {
+                if !self.handle_action(action) {
+                    return Poll::Terminated;
+                };
+                poll = Poll::Busy;
+            },
+        };
+        // FIXME (ben 16/08/2015) other reasons than no new crust events could induce an error
+        match self.transport_receiver.try_recv() {
+            Err(_) => {},
+            Ok(event) => {
+                self.handle_transport_event(event);
+                poll = Poll::Busy;
+            },
+        };
+        self.handle_request_timeouts();
+        self.handle_bootstrap_timeout();
+        self.probe_liveness();
+        self.publish_snapshot();
+        poll
+    }
+
+    // Returns false once the action has terminated the node.
+    fn handle_action(&mut self, action: Action) -> bool {
+        match action {
+            Action::SendMessage(signed_message) => {
+                ignore(self.message_received(signed_message));
+            }
+            Action::SendContent(our_authority, to_authority, content, message_id) => {
+                let _ = self.send_content(our_authority, to_authority, content, message_id);
+            },
+            Action::SendConfirmationHello(connection, address) => {
+                let _ = self.send_hello(connection, Some(address), None);
+            },
+            Action::ClientSendContent(to_authority, content, message_id) => {
+                debug!("ClientSendContent received for {:?}", content);
+                let _ = self.client_send_content(to_authority, content, message_id, 0u32);
+            },
+            Action::Churn(our_close_group, targets, cause) => {
+                let _ = self.generate_churn(our_close_group, targets, cause);
+                self.update_bootstrap_cache();
+            },
+            Action::SetCacheOptions(cache_options) => {
+                self.set_cache_options(cache_options);
+            },
+            Action::SetRequestPolicy(request_policy) => {
+                self.set_request_policy(request_policy);
+            },
+            Action::DropConnections(connections) => {
+                self.drop_connections(connections);
+            },
+            Action::MatchConnection(expected_connection, unknown_connection) => {
+                self.match_connection(expected_connection, unknown_connection);
+            },
+            Action::RefreshRoutingTable(name) => {
+                self.refresh_routing_table(&name);
+            },
+            Action::Rebootstrap => {
+                self.reset();
+                ::std::thread::sleep_ms(100);
+                self.bootstrap();
+            },
+            Action::Terminate => {
+                debug!("routing node terminated");
+                self.update_bootstrap_cache();
+                let _ = self.event_sender.send(Event::Terminated);
+                self.transport.stop();
+                return false;
+            }
+        };
+        true
+    }
+
+    fn handle_transport_event(&mut self, event: ::crust::Event) {
+        match event {
+            ::crust::Event::NewMessage(connection, bytes) => {
+                self.bytes_received(connection, bytes);
+            }
+            ::crust::Event::OnConnect(connection, _response_token) => {
+                self.handle_on_connect(connection);
+            }
+            ::crust::Event::OnAccept(connection) => {
+                self.handle_on_accept(connection);
+            }
+            ::crust::Event::OnRendezvousConnect(_connection, _response_token) => {
+                unimplemented!()
+            }
+            ::crust::Event::LostConnection(connection) => {
+                self.handle_lost_connection(connection);
+            }
+            ::crust::Event::BootstrapFinished => {
+                match self.core.state() {
+                    &::routing_core::State::Disconnected => {
+                        self.reset();
+                        ::std::thread::sleep_ms(100);
+                        self.bootstrap();
+                    },
+                    _ => {},
+                };
+            }
+            ::crust::Event::ExternalEndpoints(external_endpoints) => {
+                for external_endpoint in external_endpoints {
+                    self.accepting_on.push(external_endpoint);
                 }
-                Ok(::crust::Event::LostConnection(connection)) => {
-                    self.handle_lost_connection(connection);
+            }
+            _ => {}
+        };
+    }
+
+    /// Decrypt the bytes received on a connection and handle them as a SignedMessage, or
+    /// otherwise as a DirectMessage.
+    fn bytes_received(&mut self, connection: ::crust::Connection, bytes: Bytes) {
+        match self.rate_limiter.check_connection(&connection) {
+            ::filter::Rate::Allow => {},
+            ::filter::Rate::Drop => return,
+            ::filter::Rate::Disconnect => {
+                error!("Dropping connection {:?} which keeps exceeding its rate", connection);
+                let _ = self.action_sender.send(Action::DropConnections(vec![connection]));
+                return;
+            },
+        };
+        let (sealed, bytes) = match self.sessions.open(&connection, &bytes) {
+            Ok(bytes) => (true, bytes),
+            // only the Hello setting up the session is sent unsealed
+            Err(RoutingError::NoSession) => (false, bytes),
+            Err(e) => {
+                error!("Dropping connection {:?} which failed its session check: {:?}",
+                    connection, e);
+                let _ = self.action_sender.send(Action::DropConnections(vec![connection]));
+                return;
+            },
+        };
+        // check the protocol version before decoding the message itself
+        let bytes = match ::utils::decode_envelope(&bytes) {
+            Ok((version, bytes)) => {
+                if ::types::is_compatible_version(version) {
+                    Some(bytes)
+                } else {
+                    error!("Dropped message on {:?}: {}", connection,
+                        RoutingError::IncompatibleVersion(version));
+                    None
                 }
-                Ok(::crust::Event::BootstrapFinished) => {
-                    match self.core.state() {
-                        &::routing_core::State::Disconnected => {
-                            self.reset();
-                            ::std::thread::sleep_ms(100);
-                            self.crust_service.bootstrap(0u32);
-                        },
-                        _ => {},
+            },
+            Err(e) => {
+                error!("Unparsable message received on {:?}: {:?}", connection, e);
+                None
+            },
+        };
+        let bytes = match (sealed, bytes) {
+            (true, Some(bytes)) => bytes,
+            (true, None) => return,
+            (false, bytes) => {
+                let direct_message = bytes.and_then(|bytes|
+                    decode::<::direct_messages::DirectMessage>(&bytes).ok());
+                if let Some(direct_message) = direct_message {
+                    let is_hello = match *direct_message.content() {
+                        ::direct_messages::Content::Hello(_) => true,
+                        _ => false,
                     };
+                    if is_hello {
+                        return self.direct_message_received(direct_message, connection);
+                    };
+                };
+                error!("Dropping connection {:?} which sent unsealed bytes", connection);
+                let _ = self.action_sender.send(Action::DropConnections(vec![connection]));
+                return;
+            },
+        };
+        match decode::<SignedMessage>(&bytes) {
+            Ok(message) => {
+                // handle SignedMessage for any identified connection
+                match self.core.lookup_connection(&connection) {
+                    Some(ConnectionName::Unidentified(_, _)) => debug!("message
+                            from unidentified connection {:?}", connection),
+                    None => debug!("message from unknown connection {:?}",
+                        connection),
+                    Some(connection_name) => {
+                        // a relay passes on messages it can not check, so only the originator
+                        // of a message is charged for it
+                        let from_originator = is_originator(&connection_name, message.claimant());
+                        match self.message_received(message) {
+                            Err(RoutingError::FailedSignature) if from_originator =>
+                                self.core.record_misbehaviour(
+                                    &connection, ::ban_list::Misbehaviour::FailedSignature),
+                            Err(RoutingError::BadAuthority) if from_originator =>
+                                self.core.record_misbehaviour(
+                                    &connection, ::ban_list::Misbehaviour::BadAuthority),
+                            _ => {},
+                        }
+                    },
+                };
+            }
+            // The message received is not a Signed Routing Message,
+            // expect it to be a DirectMessage on this connection
+            Err(_) => {
+                match decode::<::direct_messages::DirectMessage>(&bytes) {
+                    Ok(direct_message) => self.direct_message_received(
+                            direct_message, connection),
+                        _ => error!("Unparsable message received on {:?}", connection),
+                };
+            }
+        };
+    }
+
+    /// bootstrap connects to the configured bootstrap endpoints, or lets the transport find its
+    /// own bootstrap contacts if none are configured.  In the latter case the endpoints of the
+    /// bootstrap cache are tried first.
+    pub fn bootstrap(&mut self) {
+        if !self.bootstrap_endpoints.is_empty() {
+            self.bootstrap_attempts = 0u32;
+            return self.connect_to_bootstrap_endpoints();
+        };
+        let cached_endpoints = match self.bootstrap_cache {
+            Some(ref bootstrap_cache) => bootstrap_cache.endpoints(),
+            None => vec![],
+        };
+        if cached_endpoints.is_empty() {
+            return self.transport.bootstrap(0u32);
+        };
+        // the transport only bootstraps if none of the cached endpoints answers in time
+        debug!("Bootstrapping off {:?} cached endpoints.", cached_endpoints.len());
+        self.transport.connect(0u32, cached_endpoints);
+        self.bootstrap_retry = Some(::clock::now() +
+                                    ::time::Duration::milliseconds(BOOTSTRAP_RETRY_MILLISECONDS));
+    }
+
+    // Connecting to the bootstrap endpoints only reports success, so a retry is scheduled in
+    // case none of them answers.
+    fn connect_to_bootstrap_endpoints(&mut self) {
+        self.transport.connect(0u32, self.bootstrap_endpoints.clone());
+        let backoff = BOOTSTRAP_RETRY_MILLISECONDS << self.bootstrap_attempts;
+        self.bootstrap_retry = Some(::clock::now() + ::time::Duration::milliseconds(backoff));
+    }
+
+    // Retry connecting to the bootstrap endpoints while we are disconnected, backing off each
+    // time.  Once all attempts failed, the user is told and the transport bootstraps on its own.
+    // If the cached endpoints did not answer, the transport bootstraps right away.
+    fn handle_bootstrap_timeout(&mut self) {
+        match self.bootstrap_retry {
+            Some(retry) if retry <= ::clock::now() => self.bootstrap_retry = None,
+            _ => return,
+        };
+        match *self.core.state() {
+            ::routing_core::State::Disconnected => {},
+            _ => return,
+        };
+        if self.bootstrap_endpoints.is_empty() {
+            debug!("No answer from the cached endpoints, bootstrapping off the transport");
+            return self.transport.bootstrap(0u32);
+        };
+        self.bootstrap_attempts += 1;
+        if self.bootstrap_attempts < MAX_BOOTSTRAP_ATTEMPTS {
+            debug!("No answer from the bootstrap endpoints, retrying (attempt {:?})",
+                self.bootstrap_attempts + 1);
+            self.connect_to_bootstrap_endpoints();
+        } else {
+            error!("Failed to connect to any of the bootstrap endpoints {:?}, falling back to \
+                the transport's bootstrap", self.bootstrap_endpoints);
+            self.send_to_user(Event::BootstrapFailed);
+            self.transport.bootstrap(0u32);
+        };
+    }
+
+    /// Record the endpoints of our reliable routing table peers in the bootstrap cache, if one is
+    /// set.
+    fn update_bootstrap_cache(&mut self) {
+        let endpoints = self.core.reliable_routing_table_endpoints();
+        if endpoints.is_empty() {
+            return;
+        };
+        match self.bootstrap_cache {
+            Some(ref mut bootstrap_cache) => {
+                match bootstrap_cache.update(endpoints) {
+                    Ok(()) => {},
+                    Err(e) => error!("Failed to write bootstrap cache: {:?}", e),
                 }
-                Ok(::crust::Event::ExternalEndpoints(external_endpoints)) => {
-                    for external_endpoint in external_endpoints {
-                        self.accepting_on.push(external_endpoint);
-                    }
-                }
-                _ => {}
-            };
-            ::std::thread::sleep_ms(1);
+            },
+            None => {},
         }
     }
 
     /// reset keeps the persistant state, but drops all connections
     /// and restarts the cycle from disconnected.
     fn reset(&mut self) {
-          let open_connections = self.core.reset(self.client_restriction);
+          let open_connections = self.core.reset(self.client_restriction || self.persistant_id);
           for connection in open_connections {
-              self.crust_service.drop_node(connection);
+              self.transport.drop_node(connection);
           }
-          self.filter = ::filter::Filter::with_expiry_duration(::time::Duration::minutes(20));
+          self.sessions.clear();
+          self.liveness = ::liveness::Liveness::with_interval(
+              ::time::Duration::seconds(::liveness::PING_INTERVAL_SECONDS));
+          self.relocation = None;
           self.connection_filter = ::message_filter::MessageFilter::with_expiry_duration(
               ::time::Duration::seconds(20));
-          self.public_id_cache = LruCache::with_expiry_duration(::time::Duration::minutes(10));
-          self.accumulator = ::message_accumulator::MessageAccumulator::with_expiry_duration(
-              ::time::Duration::minutes(5));
-          self.refresh_accumulator = ::refresh_accumulator::RefreshAccumulator
-              ::with_expiry_duration(::time::Duration::minutes(5), self.event_sender.clone());
+          let preserve_expiry_durations = self.expiry_durations.clone();
+          self.set_expiry_durations(preserve_expiry_durations);
           self.data_cache = None;
           let preserve_cache_options = self.cache_options.clone();
           self.set_cache_options(preserve_cache_options);
@@ -252,7 +544,7 @@ impl RoutingNode {
             &::routing_core::State::Bootstrapped => {
                 // We're bootstrapped at our side but haven't received hello response and relocated,
                 // so drop this connection.
-                self.crust_service.drop_node(connection);
+                self.transport.drop_node(connection);
                 return;
             },
             // We have at least one connection, so continue unless terminate has been received.
@@ -261,7 +553,7 @@ impl RoutingNode {
             &::routing_core::State::GroupConnected => {},
             &::routing_core::State::Terminated => {
                 // Terminate has been called don't act on any further events.
-                self.crust_service.drop_node(connection);
+                self.transport.drop_node(connection);
                 return;
             },
         };
@@ -278,19 +570,21 @@ impl RoutingNode {
     fn handle_on_accept(&mut self, connection: ::crust::Connection) {
         match self.core.state() {
             &::routing_core::State::Disconnected => {
-                let assigned_name = NameType::new(crypto::hash::sha512::hash(
-                    &self.core.id().name().0).0);
-                self.core.assign_name(&assigned_name);
+                if !self.core.resume_network_name() {
+                    let assigned_name = utils::first_node_name(&self.core.id().name());
+                    let _ = self.core.assign_name(&assigned_name);
+                    self.write_id_file();
+                };
             },
             &::routing_core::State::Bootstrapped => {
-                self.crust_service.drop_node(connection);
+                self.transport.drop_node(connection);
                 return;
             },
             &::routing_core::State::Relocated => {},
             &::routing_core::State::Connected => {},
             &::routing_core::State::GroupConnected => {},
             &::routing_core::State::Terminated => {
-                self.crust_service.drop_node(connection);
+                self.transport.drop_node(connection);
                 return;
             },
         };
@@ -301,6 +595,9 @@ impl RoutingNode {
     /// When CRUST reports a lost connection, ensure we remove the endpoint anywhere
     fn handle_lost_connection(&mut self, connection: ::crust::Connection) {
         debug!("Lost connection on {:?}", connection);
+        self.sessions.remove(&connection);
+        self.rate_limiter.remove(&connection);
+        self.liveness.remove(&connection);
         let connection_name = self.core.lookup_connection(&connection);
         if connection_name.is_some() {
             self.core.drop_peer(&connection_name.unwrap());
@@ -316,19 +613,29 @@ impl RoutingNode {
                   -> RoutingResult {
         debug!("Saying hello I am {:?} on {:?}, confirming {:?}", self.core.our_address(),
             connection, confirmed_address);
+        let (ephemeral_key, new_key) = self.sessions.ephemeral_key(&connection);
         let direct_message = match ::direct_messages::DirectMessage::new(
                 ::direct_messages::Content::Hello( ::direct_messages::Hello {
+                    version: ::types::PROTOCOL_VERSION,
+                    min_version: ::types::MIN_PROTOCOL_VERSION,
                     address: self.core.our_address(),
                     public_id: PublicId::new(self.core.id()),
                     confirmed_you: confirmed_address,
-                    expected_connection: expected_connection
+                    expected_connection: expected_connection,
+                    ephemeral_key: ephemeral_key,
                 }), self.core.id().signing_private_key()) {
             Ok(x) => x,
             Err(e) => return Err(RoutingError::Cbor(e)),
         };
-        let bytes = try!(::utils::encode(&direct_message));
-        self.crust_service.send(connection, bytes);
-        Ok(())
+        let bytes = try!(::utils::encode_envelope(&direct_message));
+        // The peer needs this hello to derive the session key, so a new key goes out as is.
+        if new_key {
+            self.transport.send(connection.clone(), bytes);
+            self.send_queued(&connection);
+            Ok(())
+        } else {
+            self.send_bytes(connection, bytes)
+        }
     }
 
     fn handle_hello(&mut self, connection: ::crust::Connection, hello: &::direct_messages::Hello) {
@@ -344,10 +651,12 @@ impl RoutingNode {
     /// no relay-messages enter the SAFE network here.
     fn message_received(&mut self, signed_message: SignedMessage) -> RoutingResult {
 
+        if !::types::is_compatible_version(signed_message.version()) {
+            return Err(RoutingError::IncompatibleVersion(signed_message.version()));
+        };
         // filter check, should just return quietly
-        if !self.filter.check(&signed_message) {
-            return Err(RoutingError::FilterCheckFailed);
-        }
+        try!(self.filter.check(&signed_message));
+        self.filter.add(&signed_message);
 
         let message = signed_message.get_routing_message().clone();
 
@@ -357,7 +666,8 @@ impl RoutingNode {
         match self.handle_cache_get(&message) {
             Some(content) => {
                 return self.send_content(
-                    Authority::ManagedNode(self.core.id().name()), message.source(), content);
+                    Authority::ManagedNode(self.core.id().name()), message.source(), content,
+                    message.message_id);
             },
             None => {}
         }
@@ -370,6 +680,16 @@ impl RoutingNode {
             };
         };
 
+        // A joining node only learns the admission difficulty from its relay, which refuses to
+        // pass on a request for a network name without enough work.
+        match try!(self.admission_refusal(&signed_message)) {
+            Some(refusal) => {
+                ignore(self.send(refusal));
+                return Err(RoutingError::InsufficientProofOfWork);
+            },
+            None => {},
+        };
+
         // Forward
         if self.core.is_connected_node() {
             ignore(self.send(signed_message.clone()));
@@ -395,30 +715,40 @@ impl RoutingNode {
         }
 
         // Accumulate message
-        let (message, opt_token) = match self.accumulate(&signed_message) {
+        let (message, opt_token) = match try!(self.accumulate(&signed_message)) {
             Some((message, opt_token)) => (message, opt_token),
             None => return Err(::error::RoutingError::NotEnoughSignatures),
         };
 
         let message_backup = message.clone();
+        let message_id = message.message_id;
         let result = match message.content {
             Content::InternalRequest(request) => {
                 match request {
-                    InternalRequest::RequestNetworkName(_) => {
+                    InternalRequest::RequestNetworkName(_, _) => {
                         match opt_token {
                             Some(response_token) => self.handle_request_network_name(request,
-                                message.from_authority, message.to_authority, response_token),
+                                message.from_authority, message.to_authority, response_token,
+                                message_id),
                             None => return Err(RoutingError::UnknownMessageType),
                         }
                     }
                     InternalRequest::CacheNetworkName(_, _) => {
                         self.handle_cache_network_name(request, message.from_authority,
-                            message.to_authority)
+                            message.to_authority, message_id)
                     }
                     InternalRequest::Connect(_) => {
                         match opt_token {
                             Some(response_token) => self.handle_connect_request(request,
-                                message.from_authority, message.to_authority, response_token),
+                                message.from_authority, message.to_authority, response_token,
+                                message_id),
+                            None => return Err(RoutingError::UnknownMessageType),
+                        }
+                    }
+                    InternalRequest::RequestPublicIds(_) => {
+                        match opt_token {
+                            Some(response_token) => self.handle_request_public_ids(request,
+                                message.from_authority, response_token, message_id),
                             None => return Err(RoutingError::UnknownMessageType),
                         }
                     }
@@ -442,7 +772,7 @@ impl RoutingNode {
             }
             Content::InternalResponse(response) => {
                 match response {
-                    InternalResponse::CacheNetworkName(_, _, _) => {
+                    InternalResponse::CacheNetworkName(_, _, _, _) => {
                         self.handle_cache_network_name_response(response, message.from_authority,
                             message.to_authority)
                     }
@@ -450,6 +780,13 @@ impl RoutingNode {
                         self.handle_connect_response(response, message.from_authority,
                             message.to_authority)
                     }
+                    InternalResponse::InsufficientProofOfWork(_, _) => {
+                        self.handle_insufficient_proof_of_work(response, message.from_authority,
+                            message.to_authority)
+                    }
+                    InternalResponse::PublicIds(_, _) => {
+                        self.handle_public_ids(response, message.from_authority)
+                    }
                 }
             }
             Content::ExternalRequest(request) => {
@@ -458,13 +795,16 @@ impl RoutingNode {
                     our_authority  : message.to_authority,
                     from_authority : message.from_authority,
                     response_token : opt_token,
+                    message_id     : message_id,
                 });
                 Ok(())
             }
             Content::ExternalResponse(response) => {
                 self.handle_external_response(response, message.to_authority,
-                    message.from_authority)
+                    message.from_authority, message_id)
             }
+            // content is opened in accumulate, so it was sealed twice
+            Content::Encrypted(_) => Err(RoutingError::UnknownMessageType),
         };
 
 
@@ -483,23 +823,55 @@ impl RoutingNode {
 
     fn accumulate(&mut self,
                   signed_message: &SignedMessage)
-                  -> Option<(RoutingMessage, Option<SignedToken>)> {
-        let message = signed_message.get_routing_message().clone();
+                  -> Result<Option<(RoutingMessage, Option<SignedToken>)>, RoutingError> {
+        let mut message = signed_message.get_routing_message().clone();
+
+        match claimant_public_key(&mut self.core, signed_message) {
+            Some(public_key) => {
+                if !signed_message.verify_signature(&public_key) {
+                    error!("Signature of claimant {:?} failed, message {:?} is dropped.",
+                        signed_message.claimant(), message);
+                    return Err(RoutingError::FailedSignature);
+                };
+            },
+            None => {
+                debug!("No verifiable public id for claimant {:?}, message {:?} is dropped.",
+                    signed_message.claimant(), message);
+                return Err(RoutingError::FailedSignature);
+            },
+        };
+        self.filter.record(signed_message);
+        // only a verified claimant is charged, so no one can spend the rate of another
+        if !self.rate_limiter.check_claimant(signed_message.claimant()) {
+            debug!("Dropped message of {:?} over its rate", signed_message.claimant());
+            return Err(RoutingError::RateExceeded);
+        };
+
+        // remember the key of a client asking for encrypted responses, and open content sealed
+        // for us
+        match *signed_message.claimant() {
+            Address::Client(ref public_key) => match signed_message.encrypting_key(public_key) {
+                Some(encrypting_key) => {
+                    let _ = self.encrypting_keys.insert(public_key.clone(), encrypting_key);
+                },
+                None => {},
+            },
+            Address::Node(_) => {},
+        };
+        message.content = try!(message.content.decrypt(self.core.id().encrypting_private_key()));
 
         if !message.from_authority.is_group() {
             debug!("Message from {:?}, returning with SignedToken",
                 message.from_authority);
-            // TODO: If not from a group, then use client's public key to check
-            // the signature.
             let token = match signed_message.as_token() {
                 Ok(token) => token,
                 Err(_) => {
                     error!("Failed to generate signed token, message {:?} is dropped.",
                       message);
-                    return None;
+                    return Ok(None);
                 }
             };
-            return Some((message, Some(token)));
+            return Ok(Some((message, Some(token))));
         }
 
         let skip_accumulator = match message.content {
@@ -513,7 +885,7 @@ impl RoutingNode {
             },
             Content::InternalResponse(ref response) => {
                 match *response {
-                    InternalResponse::CacheNetworkName(_,_,_) => true,
+                    InternalResponse::CacheNetworkName(_, _, _, _) => true,
                     _ => false,
                 }
             },
@@ -522,7 +894,7 @@ impl RoutingNode {
 
         if skip_accumulator {
             debug!("Skipping accumulator for message {:?}", message);
-            return Some((message, None));
+            return Ok(Some((message, None)));
         }
 
         let threshold = self.group_threshold();
@@ -533,12 +905,27 @@ impl RoutingNode {
             Address::Client(_) => {
                 error!("Claimant is a Client, but passed into accumulator for a group, dropping.");
                 // debug_assert!(false);
-                return None;
+                return Ok(None);
             }
         };
 
+        // Every claimant held by the accumulator is checked again before the message is released:
+        // its public id has to be known to us, it has to be in range of the sending group and its
+        // signature has to verify.  Names we do not know are never counted towards the quorum.
         debug!("Adding message from {:?} to accumulator", claimant);
-        self.accumulator.add_message(threshold, claimant, message).map(|msg| (msg, None))
+        let group_location = message.from_authority.get_location().clone();
+        let core = &mut self.core;
+        Ok(self.accumulator.add_message(threshold, message, signed_message.clone(),
+            |claimant, claimed_message| {
+                if !core.name_in_group_range(&group_location, claimant) {
+                    return false;
+                };
+                match core.known_public_id(claimant) {
+                    Some(public_id) =>
+                        claimed_message.verify_signature(&public_id.signing_public_key()),
+                    None => false,
+                }
+            }).map(|msg| (msg, None)))
     }
 
     // ---- Direct Messages -----------------------------------------------------------------------
@@ -552,7 +939,29 @@ impl RoutingNode {
                 if !direct_message.verify_signature(&hello.public_id.signing_public_key()) {
                     error!("DirectMessage::Hello failed signature verification on {:?}",
                         connection);
-                    self.crust_service.drop_node(connection); };
+                    self.core.record_misbehaviour(&connection,
+                                                  ::ban_list::Misbehaviour::InvalidHello);
+                    self.transport.drop_node(connection);
+                    return;
+                };
+                if ::types::negotiate_version(hello.version, hello.min_version).is_none() {
+                    error!("DirectMessage::Hello from {:?} on {:?}: {}", hello.address,
+                        connection, RoutingError::IncompatibleVersion(hello.version));
+                    self.send_to_user(Event::IncompatiblePeer(hello.address.clone(),
+                                                              hello.version));
+                    self.drop_connections(vec![connection]);
+                    return;
+                };
+                if let Err(e) = self.sessions.set_peer_key(&connection,
+                                                           hello.ephemeral_key.clone()) {
+                    error!("DirectMessage::Hello changed the session key on {:?}: {:?}",
+                        connection, e);
+                    self.core.record_misbehaviour(&connection,
+                                                  ::ban_list::Misbehaviour::InvalidHello);
+                    self.drop_connections(vec![connection]);
+                    return;
+                };
+                self.send_queued(&connection);
                 let _ = self.handle_hello(connection, hello);
             },
             &::direct_messages::Content::Churn(ref his_close_group) => {
@@ -560,9 +969,78 @@ impl RoutingNode {
                 // from our routing table.
                 self.handle_churn(his_close_group);
             },
+            &::direct_messages::Content::Ping(nonce) => {
+                ignore(self.send_direct_content(connection,
+                                                ::direct_messages::Content::Pong(nonce)));
+            },
+            &::direct_messages::Content::Pong(nonce) => {
+                match self.liveness.pong(&connection, nonce) {
+                    Some(round_trip_time) => {
+                        let _ = self.core.record_round_trip_time(&connection, round_trip_time);
+                    },
+                    None => debug!("Unexpected pong on {:?}", connection),
+                };
+            },
         };
     }
 
+    fn send_direct_content(&mut self, connection: ::crust::Connection,
+                           content: ::direct_messages::Content) -> RoutingResult {
+        let direct_message = try!(::direct_messages::DirectMessage::new(content,
+            self.core.id().signing_private_key()));
+        let bytes = try!(::utils::encode_envelope(&direct_message));
+        self.send_bytes(connection, bytes)
+    }
+
+    // ---- Snapshot ------------------------------------------------------------------------------
+
+    /// Share the published snapshot of our routing table, so that it can be read by the user.
+    pub fn snapshot(&self)
+            -> ::std::sync::Arc<::std::sync::Mutex<::snapshot::RoutingTableSnapshot>> {
+        self.snapshot.clone()
+    }
+
+    /// Publish a new snapshot of our routing table if it, our name or our state has changed.
+    fn publish_snapshot(&mut self) {
+        let version = (self.core.routing_table_version(), self.core.state().clone(),
+                       self.core.id().name());
+        if version == self.snapshot_version {
+            return;
+        };
+        let snapshot = self.core.routing_table_snapshot();
+        match self.snapshot.lock() {
+            Ok(mut published) => *published = snapshot,
+            Err(_) => return,
+        };
+        self.snapshot_version = version;
+    }
+
+    // ---- Liveness ------------------------------------------------------------------------------
+
+    /// Ping the peers in our routing table once per interval and drop those that stopped
+    /// answering, so that half-open connections don't linger in our close group.
+    fn probe_liveness(&mut self) {
+        if !self.liveness.is_due() {
+            return;
+        };
+        let connections = self.core.routing_table_connections();
+        let (pings, missed, unresponsive) = self.liveness.probe(connections);
+        for connection in missed {
+            let _ = self.core.record_missed_probe(&connection);
+        }
+        for connection in unresponsive {
+            info!("Dropping unresponsive peer on {:?}", connection);
+            match self.core.lookup_connection(&connection) {
+                Some(connection_name) => self.core.drop_peer(&connection_name),
+                None => {},
+            };
+            self.drop_connections(vec![connection]);
+        }
+        for (connection, nonce) in pings {
+            ignore(self.send_direct_content(connection, ::direct_messages::Content::Ping(nonce)));
+        }
+    }
+
     // ---- Churn ---------------------------------------------------------------------------------
 
     fn generate_churn(&mut self, churn: ::direct_messages::Churn, target: Vec<::crust::Connection>,
@@ -577,9 +1055,9 @@ impl RoutingNode {
                 Ok(x) => x,
                 Err(e) => return Err(RoutingError::Cbor(e)),
             };
-        let bytes = try!(::utils::encode(&direct_message));
+        let bytes = try!(::utils::encode_envelope(&direct_message));
         for endpoint in target {
-            self.crust_service.send(endpoint, bytes.clone());
+            ignore(self.send_bytes(endpoint, bytes.clone()));
         }
         // notify the user
         let _ = self.event_sender.send(::event::Event::Churn(churn.close_group, cause));
@@ -595,15 +1073,17 @@ impl RoutingNode {
 
     // ---- Request Network Name ------------------------------------------------------------------
 
-    fn handle_request_network_name(&self, request: InternalRequest,
+    fn handle_request_network_name(&mut self, request: InternalRequest,
                                    from_authority: Authority,
                                    to_authority: Authority,
-                                   response_token: SignedToken)
+                                   response_token: SignedToken,
+                                   message_id: MessageId)
                                    -> RoutingResult {
         match request {
-            InternalRequest::RequestNetworkName(public_id) => {
+            InternalRequest::RequestNetworkName(public_id, nonce) => {
                 match (&from_authority, &to_authority) {
                     (&Authority::Client(_, _), &Authority::NaeManager(_)) => {
+                        try!(self.check_admission_work(&public_id, nonce));
                         let mut network_public_id = public_id.clone();
                         match self.core.our_close_group() {
                             Some(close_group) => {
@@ -618,6 +1098,7 @@ impl RoutingNode {
                                     content: Content::InternalRequest(
                                         InternalRequest::CacheNetworkName(network_public_id,
                                         response_token)),
+                                    message_id: message_id,
                                 };
                                 match SignedMessage::new(Address::Node(self.core.id().name()),
                                                          routing_message,
@@ -640,7 +1121,8 @@ impl RoutingNode {
     fn handle_cache_network_name(&mut self,
                                  request: InternalRequest,
                                  from_authority: Authority,
-                                 to_authority: Authority)
+                                 to_authority: Authority,
+                                 message_id: MessageId)
                                  -> RoutingResult {
         match request {
             InternalRequest::CacheNetworkName(network_public_id, response_token) => {
@@ -648,8 +1130,16 @@ impl RoutingNode {
                     (Authority::NaeManager(_), &Authority::NaeManager(_)) => {
                         let request_network_name = try!(SignedMessage::new_from_token(
                             response_token.clone()));
-                        let _ = self.public_id_cache.insert(network_public_id.name(),
-                            network_public_id.clone());
+                        match request_network_name.get_routing_message().content {
+                            Content::InternalRequest(InternalRequest::RequestNetworkName(
+                                    ref public_id, nonce)) =>
+                                try!(self.check_admission_work(public_id, nonce)),
+                            _ => return Err(RoutingError::UnknownMessageType),
+                        };
+                        self.core.cache_public_id(network_public_id.clone());
+                        let signature = try!(::relocation::RelocationSignature::new(
+                            &network_public_id.signing_public_key(), &network_public_id.name(),
+                            self.core.id()));
                         match self.core.our_close_group_with_public_ids() {
                             Some(close_group) => {
                                 debug!("Network request to accept name {:?},
@@ -661,7 +1151,8 @@ impl RoutingNode {
                                     to_authority: request_network_name.get_routing_message().source(),
                                     content: Content::InternalResponse(
                                         InternalResponse::CacheNetworkName(network_public_id,
-                                        close_group, response_token)),
+                                        close_group, signature, response_token)),
+                                    message_id: message_id,
                                 };
                                 match SignedMessage::new(Address::Node(self.core.id().name()),
                                                          routing_message,
@@ -681,6 +1172,88 @@ impl RoutingNode {
         }
     }
 
+    /// A joining node is only relocated if it proved the admission work for its public id.
+    fn check_admission_work(&self, public_id: &PublicId, nonce: u64) -> RoutingResult {
+        let difficulty = self.core.routing_config().admission_difficulty();
+        if !::proof_of_work::verify(public_id, difficulty, nonce) {
+            debug!("Refused to relocate {:?} without proof of work", public_id.name());
+            return Err(RoutingError::InsufficientProofOfWork);
+        };
+        Ok(())
+    }
+
+    /// The response of a relay to a request for a network name from a client it relays for, if
+    /// the request lacks the admission work.
+    fn admission_refusal(&self, signed_message: &SignedMessage)
+            -> Result<Option<SignedMessage>, RoutingError> {
+        let message = signed_message.get_routing_message();
+        let (public_id, nonce) = match message.content {
+            Content::InternalRequest(InternalRequest::RequestNetworkName(ref public_id, nonce)) =>
+                (public_id, nonce),
+            _ => return Ok(None),
+        };
+        let our_name = self.core.id().name();
+        match message.from_authority {
+            Authority::Client(ref relay_name, _) if *relay_name == our_name => {},
+            _ => return Ok(None),
+        };
+        let difficulty = self.core.routing_config().admission_difficulty();
+        if ::proof_of_work::verify(public_id, difficulty, nonce) {
+            return Ok(None);
+        };
+        debug!("Refused to pass on a request for a network name of {:?} without proof of work",
+            public_id.name());
+        let routing_message = RoutingMessage {
+            from_authority: Authority::ManagedNode(our_name.clone()),
+            to_authority: message.from_authority.clone(),
+            content: Content::InternalResponse(InternalResponse::InsufficientProofOfWork(
+                difficulty, try!(signed_message.as_token()))),
+            message_id: message.message_id.clone(),
+        };
+        Ok(Some(try!(SignedMessage::new(Address::Node(our_name), routing_message,
+                                        self.core.id().signing_private_key()))))
+    }
+
+    fn handle_insufficient_proof_of_work(&mut self, response: InternalResponse,
+            from_authority: Authority, to_authority: Authority) -> RoutingResult {
+        if self.client_restriction {
+            return Ok(())
+        };
+        match response {
+            InternalResponse::InsufficientProofOfWork(difficulty, signed_token) => {
+                if !signed_token.verify_signature(&self.core.id().signing_public_key()) {
+                    return Err(RoutingError::FailedSignature)
+                };
+                let request = try!(SignedMessage::new_from_token(signed_token));
+                match request.get_routing_message().content {
+                    Content::InternalRequest(InternalRequest::RequestNetworkName(
+                            ref original_public_id, _)) => {
+                        if *original_public_id != PublicId::new(self.core.id()) {
+                            return Err(RoutingError::BadAuthority);
+                        };
+                    },
+                    _ => return Err(RoutingError::UnknownMessageType),
+                };
+                // only our relay answers for the network
+                let relay_name = match (from_authority, to_authority) {
+                    (Authority::ManagedNode(relay_name), Authority::Client(bootstrap_name, _)) => {
+                        if relay_name != bootstrap_name {
+                            return Err(RoutingError::BadAuthority);
+                        };
+                        relay_name
+                    },
+                    _ => return Err(RoutingError::BadAuthority),
+                };
+                if self.core.retry_network_name(&relay_name, difficulty) {
+                    debug!("Solving admission work of difficulty {} for a network name",
+                        difficulty);
+                };
+                Ok(())
+            },
+            _ => return Err(RoutingError::BadAuthority),
+        }
+    }
+
     fn handle_cache_network_name_response(&mut self, response: InternalResponse,
             _from_authority: Authority, _to_authority: Authority) -> RoutingResult {
         // An additional blockage on acting to restrict RoutingNode from becoming a full node
@@ -688,14 +1261,15 @@ impl RoutingNode {
             return Ok(())
         };
         match response {
-            InternalResponse::CacheNetworkName(network_public_id, group, signed_token) => {
+            InternalResponse::CacheNetworkName(network_public_id, group, signature,
+                                               signed_token) => {
                 if !signed_token.verify_signature(&self.core.id().signing_public_key()) {
                     return Err(RoutingError::FailedSignature)
                 };
                 let request = try!(SignedMessage::new_from_token(signed_token));
                 match request.get_routing_message().content {
                     Content::InternalRequest(InternalRequest::RequestNetworkName(
-                            ref original_public_id)) => {
+                            ref original_public_id, _)) => {
                         let mut our_public_id = PublicId::new(self.core.id());
                         if &our_public_id != original_public_id {
                             return Err(RoutingError::BadAuthority);
@@ -704,13 +1278,43 @@ impl RoutingNode {
                         if our_public_id != network_public_id {
                             return Err(RoutingError::BadAuthority);
                         };
-                        let _ = self.core.assign_network_name(&network_public_id.name());
+                        // only a member of the responding group can sign for our name
+                        if !group.iter().any(|member| member.name() == *signature.signer() &&
+                                member.signing_public_key() == *signature.signer_key()) {
+                            return Err(RoutingError::BadAuthority);
+                        };
+                        let relocated_name = network_public_id.name();
+                        let our_key = our_public_id.signing_public_key();
+                        let mut certificate = match self.relocation.take() {
+                            Some(certificate) => {
+                                if *certificate.relocated_name() == relocated_name {
+                                    certificate
+                                } else {
+                                    ::relocation::RelocationCertificate::new(relocated_name)
+                                }
+                            },
+                            None => ::relocation::RelocationCertificate::new(relocated_name),
+                        };
+                        if !certificate.add_signature(&our_key, signature) {
+                            return Err(RoutingError::FailedSignature);
+                        };
+                        // the name is taken once a quorum of the group certified it, counted the
+                        // way our peers will count it
+                        let quorum_size = self.core.routing_config().relocation_quorum(group.len());
+                        if !certificate.verify(&our_key, quorum_size) {
+                            self.relocation = Some(certificate);
+                            return Ok(());
+                        };
+                        if self.core.assign_relocation(certificate) {
+                            self.write_id_file();
+                        };
                         debug!("Assigned network name {:?} and our address now is {:?}",
                             network_public_id.name(), self.core.our_address());
+                        // the group that certified us is who we know in the network, so we can
+                        // check the certificates of the peers it introduces
                         for peer in group {
-                            // TODO (ben 12/08/2015) self.public_id_cache.insert()
-                            // or hold off till RFC on removing public_id_cache
                             self.refresh_routing_table(&peer.name());
+                            self.core.cache_public_id(peer);
                         }
                         Ok(())
                     }
@@ -729,8 +1333,12 @@ impl RoutingNode {
     /// all re-occurances of this name, and block a new connect request
     fn refresh_routing_table(&mut self, from_node: &NameType) {
         if !self.connection_filter.check(from_node) {
-            if self.core.check_node(&ConnectionName::Routing(from_node.clone())) {
-                ignore(self.send_connect_request(from_node));
+            if self.core.check_node(&ConnectionName::Routing(from_node.clone()), None) {
+                if self.send_connect_request(from_node).is_ok() {
+                    let _ = self.core.remove_candidate(from_node);
+                };
+            } else {
+                let _ = self.core.add_candidate(from_node);
             }
             self.connection_filter.add(from_node.clone());
         }
@@ -769,6 +1377,7 @@ impl RoutingNode {
                     requester_fob: PublicId::new(self.core.id()),
                 }
             )),
+            message_id: MessageId::new(),
         };
 
         match SignedMessage::new(address, routing_message, self.core.id().signing_private_key()) {
@@ -783,21 +1392,21 @@ impl RoutingNode {
                               request: InternalRequest,
                               from_authority: Authority,
                               _to_authority: Authority,
-                              response_token: SignedToken)
+                              response_token: SignedToken,
+                              message_id: MessageId)
                               -> RoutingResult {
         debug!("Handle ConnectRequest");
         match request {
             InternalRequest::Connect(connect_request) => {
-                if !connect_request.requester_fob.is_relocated() {
-                    return Err(RoutingError::RejectedPublicId);
-                };
                 // First verify that the message is correctly self-signed.
                 if !response_token.verify_signature(
                         &connect_request.requester_fob.signing_public_key()) {
                     return Err(RoutingError::FailedSignature);
                 };
+                // The requester has to prove its relocated name as well.
                 if !self.core.check_node(&ConnectionName::Routing(
-                        connect_request.requester_fob.name())) {
+                        connect_request.requester_fob.name()),
+                        Some(&connect_request.requester_fob)) {
                     return Err(RoutingError::RefusedFromRoutingTable);
                 };
 
@@ -817,6 +1426,7 @@ impl RoutingNode {
                             external_endpoints: vec![],
                             receiver_fob: PublicId::new(self.core.id()),
                         }, response_token)),
+                    message_id: message_id,
                 };
                 match SignedMessage::new(Address::Node(self.core.id().name()),
                                          routing_message,
@@ -854,7 +1464,7 @@ impl RoutingNode {
                 }
                 // Are we already connected (returns false), or still interested?
                 if !self.core.check_node(&ConnectionName::Routing(
-                    connect_response.receiver_fob.name())) {
+                    connect_response.receiver_fob.name()), Some(&connect_response.receiver_fob)) {
                     return Err(RoutingError::RefusedFromRoutingTable);
                 };
 
@@ -876,12 +1486,15 @@ impl RoutingNode {
         self.connection_counter = self.connection_counter.wrapping_add(1u32);
         if self.connection_counter == 0u32 { self.connection_counter == 1u32; };
         debug!("connect: requesting crust connect to {:?}", endpoints);
-        self.crust_service.connect(new_response_token, endpoints.clone());
+        self.transport.connect(new_response_token, endpoints.clone());
     }
 
     fn drop_connections(&mut self, connections: Vec<::crust::Connection>) {
         for connection in connections {
-            self.crust_service.drop_node(connection);
+            self.sessions.remove(&connection);
+            self.rate_limiter.remove(&connection);
+            self.liveness.remove(&connection);
+            self.transport.drop_node(connection);
         }
     }
 
@@ -902,8 +1515,83 @@ impl RoutingNode {
        self.core.match_connection(expected_connection, unknown_connection)
     }
 
+    // ---- Public Ids -----------------------------------------------------------------------------
+
+    fn handle_request_public_ids(&mut self, request: InternalRequest,
+                                 from_authority: Authority,
+                                 response_token: SignedToken,
+                                 message_id: MessageId)
+                                 -> RoutingResult {
+        let names = match request {
+            InternalRequest::RequestPublicIds(names) => names,
+            _ => return Err(RoutingError::BadAuthority),
+        };
+        match from_authority {
+            Authority::ManagedNode(_) => {},
+            _ => return Err(RoutingError::BadAuthority),
+        };
+        let mut public_ids = Vec::new();
+        for name in names.iter().take(self.core.routing_config().group_size()) {
+            match self.core.known_public_id(name) {
+                Some(public_id) => public_ids.push(public_id),
+                None => {},
+            };
+        }
+        let our_authority = Authority::ManagedNode(self.core.id().name());
+        self.send_content(our_authority, from_authority,
+            Content::InternalResponse(InternalResponse::PublicIds(public_ids, response_token)),
+            message_id)
+    }
+
+    fn handle_public_ids(&mut self, response: InternalResponse, from_authority: Authority)
+            -> RoutingResult {
+        let (public_ids, signed_token) = match response {
+            InternalResponse::PublicIds(public_ids, signed_token) => (public_ids, signed_token),
+            _ => return Err(RoutingError::BadAuthority),
+        };
+        if !signed_token.verify_signature(&self.core.id().signing_public_key()) {
+            return Err(RoutingError::FailedSignature);
+        };
+        // only a node we are connected to is trusted to vouch for the public ids of others
+        match from_authority {
+            Authority::ManagedNode(ref name) if self.core.public_id(name).is_some() => {},
+            _ => return Err(RoutingError::BadAuthority),
+        };
+        for public_id in public_ids {
+            let _ = self.core.cache_requested_public_id(public_id);
+        }
+        Ok(())
+    }
+
     // ----- Send Functions -----------------------------------------------------------------------
 
+    /// Encrypt external content for a single destination whose encryption key we know.  A client
+    /// which announced its key always gets its content encrypted; content for a managed node is
+    /// only encrypted if we are set to encrypt content.  Content that should be encrypted but can
+    /// not be is never sent in the clear.
+    fn seal_content(&mut self, to_authority: &Authority, content: Content)
+            -> Result<Content, RoutingError> {
+        let recipient_key = match *to_authority {
+            Authority::Client(_, ref public_key) => self.encrypting_keys.get(public_key).cloned(),
+            Authority::ManagedNode(ref name) if self.encrypt_content => {
+                self.core.known_public_id(name)
+                    .map(|public_id| public_id.encrypting_public_key())
+            },
+            _ => None,
+        };
+        match recipient_key {
+            Some(recipient_key) => {
+                content.encrypt(&recipient_key, &self.core.id().encrypting_public_key(),
+                                self.core.id().encrypting_private_key())
+                       .map_err(|e| {
+                           error!("Failed to encrypt content for {:?}: {:?}", to_authority, e);
+                           RoutingError::Cbor(e)
+                       })
+            },
+            None => Ok(content),
+        }
+    }
+
     fn send_to_user(&self, event: Event) {
         debug!("Send to user event {:?}", event);
         if self.event_sender.send(event).is_err() {
@@ -912,13 +1600,15 @@ impl RoutingNode {
         }
     }
 
-    fn send_content(&self, our_authority: Authority, to_authority: Authority,
-        content: Content) -> RoutingResult {
+    fn send_content(&mut self, our_authority: Authority, to_authority: Authority,
+        content: Content, message_id: MessageId) -> RoutingResult {
         if self.core.is_connected_node() {
+            let content = try!(self.seal_content(&to_authority, content));
             let routing_message = RoutingMessage {
                 from_authority: our_authority,
                 to_authority: to_authority,
                 content: content,
+                message_id: message_id,
             };
             match SignedMessage::new(Address::Node(self.core.id().name()),
                                      routing_message,
@@ -933,14 +1623,16 @@ impl RoutingNode {
                         request: external_request,
                         our_authority: Some(our_authority),
                         location: to_authority,
-                        interface_error: InterfaceError::NotConnected });
+                        interface_error: InterfaceError::NotConnected,
+                        message_id: message_id });
                 }
                 Content::ExternalResponse(external_response) => {
                     self.send_to_user(Event::FailedResponse {
                         response: external_response,
                         our_authority: Some(our_authority),
                         location: to_authority,
-                        interface_error: InterfaceError::NotConnected });
+                        interface_error: InterfaceError::NotConnected,
+                        message_id: message_id });
                 }
                 // FIXME (ben 24/08/2015) InternalRequest::Refresh can pass here on failure
                 _ => error!("InternalRequest/Response was sent back to user {:?}", content),
@@ -949,7 +1641,8 @@ impl RoutingNode {
         Ok(())
     }
 
-    fn client_send_content(&self, to_authority: Authority, content: Content) -> RoutingResult {
+    fn client_send_content(&mut self, to_authority: Authority, content: Content,
+        message_id: MessageId, attempt: u32) -> RoutingResult {
         if self.core.is_connected_node() ||
             self.core.has_bootstrap_endpoints() {
             // FIXME (ben 14/08/2015) we need a proper function to retrieve a bootstrap_name
@@ -957,16 +1650,33 @@ impl RoutingNode {
                 Some(name) => name,
                 None => return Err(RoutingError::NotBootstrapped),
             };
+            let sealed_content = try!(self.seal_content(&to_authority, content.clone()));
+            // only a Get is always answered, other requests are only answered on failure
+            if let Content::ExternalRequest(ref request @ ExternalRequest::Get(_, _)) = content {
+                let _ = self.pending_requests.insert(message_id, PendingRequest {
+                    location: to_authority.clone(),
+                    request: request.clone(),
+                    attempt: attempt,
+                });
+            };
             let routing_message = RoutingMessage {
                 from_authority: Authority::Client(bootstrap_name,
                                                   self.core.id().signing_public_key()),
                 to_authority: to_authority,
-                content: content,
+                content: sealed_content,
+                message_id: message_id,
             };
             match SignedMessage::new(Address::Client(self.core.id().signing_public_key()),
                                      routing_message,
                                      self.core.id().signing_private_key()) {
-                Ok(signed_message) => ignore(self.send(signed_message)),
+                Ok(mut signed_message) => {
+                    if self.encrypt_content {
+                        signed_message.announce_encrypting_key(
+                            &self.core.id().encrypting_public_key(),
+                            self.core.id().signing_private_key());
+                    };
+                    ignore(self.send(signed_message))
+                },
                 // FIXME (ben 24/08/2015) find an elegant way to give the message back to user
                 Err(e) => return Err(RoutingError::Cbor(e)),
             };
@@ -977,14 +1687,16 @@ impl RoutingNode {
                         request: external_request,
                         our_authority: None,
                         location: to_authority,
-                        interface_error: InterfaceError::NotConnected });
+                        interface_error: InterfaceError::NotConnected,
+                        message_id: message_id });
                 }
                 Content::ExternalResponse(external_response) => {
                     self.send_to_user(Event::FailedResponse {
                         response: external_response,
                         our_authority: None,
                         location: to_authority,
-                        interface_error: InterfaceError::NotConnected });
+                        interface_error: InterfaceError::NotConnected,
+                        message_id: message_id });
                 }
                 _ => error!("InternalRequest/Response was sent back to user {:?}", content),
             }
@@ -998,9 +1710,9 @@ impl RoutingNode {
     /// 3. if the destination is in range for us, then send it to all our close group nodes
     /// 4. if all the above failed, try sending it over all available bootstrap connections
     /// 5. finally, if we are a node and the message concerns us, queue it for processing later.
-    fn send(&self, signed_message: SignedMessage) -> RoutingResult {
+    fn send(&mut self, signed_message: SignedMessage) -> RoutingResult {
         let destination = signed_message.get_routing_message().destination();
-        let bytes = try!(encode(&signed_message));
+        let bytes = try!(::utils::encode_envelope(&signed_message));
         // query the routing table for parallel or swarm
         let connections = self.core.target_connections(&destination);
         if !connections.is_empty() {
@@ -1008,7 +1720,7 @@ impl RoutingNode {
                 signed_message.get_routing_message().content, connections.len());
             for connection in connections {
                 // TODO(ben 10/08/2015) drop endpoints that fail to send
-                self.crust_service.send(connection, bytes.clone());
+                ignore(self.send_bytes(connection, bytes.clone()));
             }
         }
 
@@ -1019,7 +1731,7 @@ impl RoutingNode {
                 // the name of the bootstrap connection we're sending it on.  Although this might
                 // open a window for attacking a node, in v0.3.* we can leave this unresolved.
                 for connection in bootstrap_connections {
-                    self.crust_service.send(connection.clone(), bytes.clone());
+                    ignore(self.send_bytes(connection.clone(), bytes.clone()));
                     debug!("Sent {:?} to bootstrap connection {:?}",
                         signed_message.get_routing_message().content,
                         connection);
@@ -1040,12 +1752,33 @@ impl RoutingNode {
         Ok(())
     }
 
+    /// Send the bytes on a connection encrypted by its session.  Until the session is established
+    /// the bytes are queued.
+    fn send_bytes(&mut self, connection: ::crust::Connection, bytes: Bytes) -> RoutingResult {
+        match try!(self.sessions.seal(&connection, bytes)) {
+            Some(sealed) => self.transport.send(connection, sealed),
+            None => debug!("Queued bytes for {:?} until its session is established", connection),
+        };
+        Ok(())
+    }
+
+    /// Send the bytes queued on a connection while its session was set up.
+    fn send_queued(&mut self, connection: &::crust::Connection) {
+        match self.sessions.take_queued(connection) {
+            Ok(frames) => for frame in frames {
+                self.transport.send(connection.clone(), frame);
+            },
+            Err(e) => error!("Failed to seal the bytes queued for {:?}: {:?}", connection, e),
+        }
+    }
+
     // ----- Message Handlers that return to the event channel ------------------------------------
 
     fn handle_external_response(&mut self,
                                 response: ExternalResponse,
                                 to_authority: Authority,
-                                from_authority: Authority)
+                                from_authority: Authority,
+                                message_id: MessageId)
                                 -> RoutingResult {
 
         // Request token is only set if it came from a non-group entity.
@@ -1060,10 +1793,12 @@ impl RoutingNode {
             };
         };
 
+        let _ = self.pending_requests.remove(&message_id);
         self.send_to_user(Event::Response {
             response       : response,
             our_authority  : to_authority,
             from_authority : from_authority,
+            message_id     : message_id,
         });
 
         Ok(())
@@ -1090,7 +1825,7 @@ impl RoutingNode {
     // ------ FIXME -------------------------------------------------------------------------------
 
     fn group_threshold(&self) -> usize {
-        min(types::QUORUM_SIZE, (self.core.routing_table_size() as f32 * 0.8) as usize)
+        self.core.routing_config().group_threshold(self.core.routing_table_size())
     }
 
     fn get_a_bootstrap_name(&self) -> Option<NameType> {
@@ -1106,9 +1841,97 @@ impl RoutingNode {
         }
     }
 
+    // ------ Request timeouts --------------------------------------------------------------------
+
+    pub fn set_request_policy(&mut self, request_policy: RequestPolicy) {
+        // requests already pending wait for the new timeout from now on
+        let mut pending_requests = ExpirationMap::with_expiry_duration(request_policy.timeout());
+        for (message_id, &(ref pending_request, _)) in self.pending_requests.iter() {
+            let _ = pending_requests.insert(message_id.clone(), pending_request.clone());
+        }
+        self.pending_requests = pending_requests;
+        self.request_policy = request_policy;
+    }
+
+    /// Retry the requests of a client that have not been answered in time, or report them to the
+    /// user with Event::RequestTimeout when all retries have been used up.
+    fn handle_request_timeouts(&mut self) {
+        for (message_id, pending_request) in self.pending_requests.remove_expired() {
+            if pending_request.attempt < self.request_policy.retries() {
+                debug!("Retrying request {:?} (attempt {:?})", message_id,
+                    pending_request.attempt + 1);
+                ignore(self.client_send_content(pending_request.location,
+                    Content::ExternalRequest(retried_request(pending_request.request)),
+                    message_id, pending_request.attempt + 1));
+            } else {
+                debug!("Request {:?} timed out", message_id);
+                self.send_to_user(Event::RequestTimeout {
+                    request: pending_request.request,
+                    location: pending_request.location,
+                    message_id: message_id,
+                });
+            }
+        }
+    }
+
+    // ------ Expiry durations --------------------------------------------------------------------
+
+    /// Set the expiry durations and restart the filter, accumulators and public id cache with
+    /// them; their current content is dropped.
+    pub fn set_expiry_durations(&mut self, expiry_durations: ExpiryDurations) {
+        self.filter = ::filter::Filter::with_expiry_duration(expiry_durations.filter());
+        self.rate_limiter = ::filter::RateLimiter::with_expiry_duration(
+            self.core.routing_config().rate_limits().clone(), expiry_durations.filter());
+        self.core.set_public_id_cache_expiry(expiry_durations.public_id_cache());
+        self.encrypting_keys = LruCache::with_expiry_duration(expiry_durations.public_id_cache());
+        self.accumulator = ::message_accumulator::MessageAccumulator::with_expiry_duration(
+            expiry_durations.message_accumulator());
+        self.refresh_accumulator = ::refresh_accumulator::RefreshAccumulator
+            ::with_expiry_duration(expiry_durations.refresh_accumulator(),
+                                   self.event_sender.clone());
+        self.expiry_durations = expiry_durations;
+    }
+
+    /// Share the ban list of the core, so that it can be inspected and cleared by the user.
+    pub fn set_ban_list(&mut self,
+                        ban_list: ::std::sync::Arc<::std::sync::Mutex<::ban_list::BanList>>) {
+        self.core.set_ban_list(ban_list);
+    }
+
+    /// Encrypt the external content we send to single nodes and clients, and ask for the
+    /// responses to our client requests to be encrypted for us.
+    pub fn set_encrypt_content(&mut self, encrypt_content: bool) {
+        self.encrypt_content = encrypt_content;
+    }
+
+    /// Keep the endpoints of our routing table peers in the given file and bootstrap off them
+    /// first on the next start.
+    pub fn set_bootstrap_cache_file(&mut self, path: ::std::path::PathBuf) {
+        self.bootstrap_cache = Some(::bootstrap_cache::BootstrapCache::new(path));
+    }
+
+    /// Persist our Id in the given file once we are assigned a network name, and keep the Id
+    /// when the node is reset.
+    pub fn set_id_file(&mut self, id_file: ::std::path::PathBuf) {
+        self.id_file = Some(id_file);
+        self.persistant_id = true;
+    }
+
+    fn write_id_file(&self) {
+        match self.id_file {
+            Some(ref id_file) => {
+                match self.core.id().write_file(id_file) {
+                    Ok(()) => debug!("Wrote id {:?} to {:?}", self.core.id().name(), id_file),
+                    Err(e) => error!("Failed to write id to {:?}: {:?}", id_file, e),
+                }
+            },
+            None => {},
+        }
+    }
+
     // ------ Cache handling ----------------------------------------------------------------------
 
-    fn set_cache_options(&mut self, cache_options: CacheOptions) {
+    pub fn set_cache_options(&mut self, cache_options: CacheOptions) {
         self.cache_options.set_cache_options(cache_options);
         if self.cache_options.caching_enabled() {
             match self.data_cache {
@@ -1243,6 +2066,57 @@ impl RoutingNode {
 fn ignore<R, E>(_result: Result<R, E>) {
 }
 
+/// True if the peer on a connection is the claimant of a message it sent, rather than a node
+/// passing the message on.
+fn is_originator(connection_name: &ConnectionName, claimant: &Address) -> bool {
+    match (connection_name, claimant) {
+        (&ConnectionName::Routing(ref name), &Address::Node(ref claimant_name)) |
+        (&ConnectionName::Bootstrap(ref name), &Address::Node(ref claimant_name)) =>
+            name == claimant_name,
+        (&ConnectionName::Relay(ref address), claimant) => address == claimant,
+        _ => false,
+    }
+}
+
+/// The public signing key of the claimant of a message.  A client claims with its own key.
+/// For a node the key is taken from the public id cache, our routing table or bootstrap
+/// connection, or from the public id a connect request or response carries for itself if its
+/// relocated name is certified.  Returns None if the node can not be verified.
+fn claimant_public_key(core: &mut RoutingCore, signed_message: &SignedMessage)
+                       -> Option<crypto::sign::PublicKey> {
+    let name = match *signed_message.claimant() {
+        Address::Client(public_key) => return Some(public_key),
+        Address::Node(ref name) => name.clone(),
+    };
+    match core.known_public_id(&name) {
+        Some(public_id) => return Some(public_id.signing_public_key()),
+        None => {},
+    };
+    let carried_public_id = match signed_message.get_routing_message().content {
+        Content::InternalRequest(InternalRequest::Connect(ref connect_request)) =>
+            Some(&connect_request.requester_fob),
+        Content::InternalResponse(InternalResponse::Connect(ref connect_response, _)) =>
+            Some(&connect_response.receiver_fob),
+        _ => None,
+    };
+    match carried_public_id {
+        Some(public_id) if public_id.name() == name &&
+                           core.verify_relocation(public_id) == NameProof::Proven =>
+            Some(public_id.signing_public_key()),
+        _ => None,
+    }
+}
+
+// The request to send when retrying, which keeps its MessageId.  A Get counts its attempts, so
+// that the retry is not held back by the filters of the nodes that saw the previous attempt.
+fn retried_request(request: ExternalRequest) -> ExternalRequest {
+    match request {
+        ExternalRequest::Get(data_request, counter) =>
+            ExternalRequest::Get(data_request, counter.wrapping_add(1)),
+        request => request,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use action::Action;
@@ -1250,18 +2124,167 @@ mod test {
     use data::{Data, DataRequest};
     use event::Event;
     use immutable_data::{ImmutableData, ImmutableDataType};
-    use messages::{ExternalRequest, ExternalResponse, SignedToken, RoutingMessage, Content};
+    use messages::{ExternalRequest, ExternalResponse, InternalRequest, SignedToken, RoutingMessage,
+                   Content, MessageId};
     use rand::{thread_rng, Rng};
     use std::sync::mpsc;
     use super::RoutingNode;
+    use test_utils::mock_network::{MockTransport, Network};
     use NameType;
     use authority::Authority;
-    use types::CacheOptions;
+    use types::{CacheOptions, RequestPolicy, RoutingConfig};
+
+    #[test]
+    fn request_timeout_after_retries() {
+        let (action_sender, action_receiver) = mpsc::channel::<Action>();
+        let (event_sender, event_receiver) = mpsc::channel::<Event>();
+        let network = Network::new();
+        let (transport, transport_receiver) = network.new_transport();
+        let mut node =
+            RoutingNode::with_transport(action_sender.clone(), action_receiver, event_sender,
+                                        true, None, RoutingConfig::default_config(), vec![],
+                                        vec![], transport, transport_receiver);
+        node.set_request_policy(RequestPolicy::new(::time::Duration::milliseconds(10), 0u32));
+        ::clock::start_virtual();
+
+        let data_request = DataRequest::PlainData(NameType::new([2u8; 64]));
+        let request = ExternalRequest::Get(data_request.clone(), 0u8);
+        let location = Authority::NaeManager(data_request.name());
+        let message_id = MessageId::new();
+        let _ = node.pending_requests.insert(message_id, super::PendingRequest {
+            location: location.clone(),
+            request: request.clone(),
+            attempt: 0u32,
+        });
 
-    fn create_routing_node() -> RoutingNode {
+        node.handle_request_timeouts();
+        assert!(event_receiver.try_recv().is_err());
+
+        ::clock::set_virtual_elapsed(20u64);
+        node.handle_request_timeouts();
+        ::clock::stop_virtual();
+        assert_eq!(event_receiver.try_recv(), Ok(Event::RequestTimeout {
+            request: request,
+            location: location,
+            message_id: message_id,
+        }));
+        assert!(node.pending_requests.remove(&message_id).is_none());
+    }
+
+    #[test]
+    fn unreachable_bootstrap_endpoints_fall_back() {
         let (action_sender, action_receiver) = mpsc::channel::<Action>();
+        let (event_sender, event_receiver) = mpsc::channel::<Event>();
+        let network = Network::new();
+        // a transport that never accepts connections
+        let (unreachable, _unreachable_receiver) = network.new_transport();
+        let (transport, transport_receiver) = network.new_transport();
+        let mut node =
+            RoutingNode::with_transport(action_sender.clone(), action_receiver, event_sender,
+                                        false, None, RoutingConfig::default_config(),
+                                        vec![unreachable.endpoint()], vec![], transport,
+                                        transport_receiver);
+        ::clock::start_virtual();
+        node.bootstrap();
+
+        let mut elapsed = 0u64;
+        let mut failed = false;
+        while !failed && elapsed < 60000u64 {
+            elapsed += 100u64;
+            ::clock::set_virtual_elapsed(elapsed);
+            let _ = node.poll();
+            while let Ok(event) = event_receiver.try_recv() {
+                failed = failed || event == Event::BootstrapFailed;
+            }
+        }
+        ::clock::stop_virtual();
+        assert!(failed);
+        // every attempt waited twice as long as the one before
+        let backoff = (0..super::MAX_BOOTSTRAP_ATTEMPTS)
+            .fold(0i64, |total, attempt| total + (super::BOOTSTRAP_RETRY_MILLISECONDS << attempt));
+        assert!(elapsed >= backoff as u64);
+    }
+
+    #[test]
+    fn cached_endpoints_are_tried_before_the_transport() {
+        let (action_sender, action_receiver) = mpsc::channel::<Action>();
+        let (event_sender, _event_receiver) = mpsc::channel::<Event>();
+        let network = Network::new();
+        let (unreachable, _unreachable_receiver) = network.new_transport();
+        let path = ::std::env::temp_dir().join(format!("routing_bootstrap_cache_{}",
+            ::rand::random::<u64>()));
+        let mut bootstrap_cache = ::bootstrap_cache::BootstrapCache::new(path.clone());
+        assert!(bootstrap_cache.update(vec![unreachable.endpoint()]).is_ok());
+        let (transport, transport_receiver) = network.new_transport();
+        let endpoint = transport.endpoint();
+        let mut node =
+            RoutingNode::with_transport(action_sender.clone(), action_receiver, event_sender,
+                                        false, None, RoutingConfig::default_config(), vec![],
+                                        vec![], transport, transport_receiver);
+        node.set_bootstrap_cache_file(path.clone());
+        let _ = ::std::fs::remove_file(&path);
+        network.hold_events();
+        ::clock::start_virtual();
+
+        // the transport is left alone while the cached endpoint gets its chance
+        node.bootstrap();
+        let _ = node.poll();
+        assert!(network.take_held_events().is_empty());
+
+        ::clock::set_virtual_elapsed(super::BOOTSTRAP_RETRY_MILLISECONDS as u64);
+        let _ = node.poll();
+        ::clock::stop_virtual();
+        let held = network.take_held_events();
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].0, endpoint);
+        match held[0].1 {
+            ::crust::Event::BootstrapFinished => {},
+            ref event => panic!("Unexpected event {:?}", event),
+        };
+    }
+
+    #[test]
+    fn retried_request_passes_filter() {
+        let (request, _) = generate_routing_messages();
+        let retry = match request.content.clone() {
+            Content::ExternalRequest(external_request) => RoutingMessage {
+                content: Content::ExternalRequest(super::retried_request(external_request)),
+                ..request.clone()
+            },
+            content => panic!("Unexpected content {:?}", content),
+        };
+        assert_eq!(retry.message_id, request.message_id);
+
+        let keys = crypto::sign::gen_keypair();
+        let claimant = ::types::Address::Node(NameType::new([3u8; 64]));
+        let mut filter = ::filter::Filter::with_expiry_duration(::time::Duration::minutes(1));
+        filter.block(&request);
+        let signed_request =
+            ::messages::SignedMessage::new(claimant.clone(), request, &keys.1).unwrap();
+        assert!(filter.check(&signed_request).is_err());
+        let signed_retry = ::messages::SignedMessage::new(claimant, retry, &keys.1).unwrap();
+        assert!(filter.check(&signed_retry).is_ok());
+    }
+
+    fn create_routing_node() -> RoutingNode<MockTransport> {
+        let (action_sender, action_receiver) = mpsc::channel::<Action>();
+        let (event_sender, _) = mpsc::channel::<Event>();
+        let (transport, transport_receiver) = Network::new().new_transport();
+        RoutingNode::with_transport(action_sender.clone(), action_receiver, event_sender, false,
+                                    None, RoutingConfig::default_config(), vec![], vec![],
+                                    transport, transport_receiver)
+    }
+
+    // A node whose own actions are queued on the returned receiver instead of being handled.
+    fn create_queued_routing_node() -> (RoutingNode<MockTransport>, mpsc::Receiver<Action>) {
+        let (queue_sender, queue_receiver) = mpsc::channel::<Action>();
+        let (_, action_receiver) = mpsc::channel::<Action>();
         let (event_sender, _) = mpsc::channel::<Event>();
-        RoutingNode::new(action_sender.clone(), action_receiver, event_sender, false, None)
+        let (transport, transport_receiver) = Network::new().new_transport();
+        (RoutingNode::with_transport(queue_sender, action_receiver, event_sender, false, None,
+                                     RoutingConfig::default_config(), vec![], vec![], transport,
+                                     transport_receiver),
+         queue_receiver)
     }
 
     // RoutingMessage's for ImmutableData Get request/response.
@@ -1282,18 +2305,21 @@ mod test {
         let data_request = DataRequest::ImmutableData(immutable.name().clone(),
                                                       immutable.get_type_tag().clone());
         let request = ExternalRequest::Get(data_request.clone(), 0u8);
+        let message_id = MessageId::new();
         let response = ExternalResponse::Get(immutable_data, data_request, Some(sign_token));
 
         let routing_message_request = RoutingMessage {
             from_authority: Authority::ClientManager(NameType::new([1u8; 64])),
             to_authority: Authority::NaeManager(NameType::new(data)),
-            content: Content::ExternalRequest(request)
+            content: Content::ExternalRequest(request),
+            message_id: message_id,
         };
 
         let routing_message_response = RoutingMessage {
             from_authority: Authority::NaeManager(NameType::new(data)),
             to_authority: Authority::ClientManager(NameType::new([1u8; 64])),
-            content: Content::ExternalResponse(response)
+            content: Content::ExternalResponse(response),
+            message_id: message_id,
         };
 
         (routing_message_request, routing_message_response)
@@ -1337,4 +2363,331 @@ mod test {
         node.handle_cache_put(&message_response);
         assert!(node.handle_cache_get(&message_request).is_none());
     }
+
+    #[test]
+    fn accumulate_verifies_client_signature() {
+        let mut node = create_routing_node();
+        let (message_request, _) = generate_routing_messages();
+        let client_keys = crypto::sign::gen_keypair();
+        let routing_message = RoutingMessage {
+            from_authority: Authority::Client(NameType::new([1u8; 64]), client_keys.0),
+            ..message_request
+        };
+
+        let signed_message = ::messages::SignedMessage::new(
+            ::types::Address::Client(client_keys.0), routing_message.clone(),
+            &client_keys.1).unwrap();
+        match node.accumulate(&signed_message) {
+            Ok(Some((message, Some(_)))) => assert_eq!(message, routing_message),
+            _ => panic!("Correctly signed message should pass with a token."),
+        };
+
+        // claiming the client key, but signed with another key
+        let other_keys = crypto::sign::gen_keypair();
+        let forged_message = ::messages::SignedMessage::new(
+            ::types::Address::Client(client_keys.0), routing_message, &other_keys.1).unwrap();
+        match node.accumulate(&forged_message) {
+            Err(::error::RoutingError::FailedSignature) => {},
+            _ => panic!("Forged message should fail its signature."),
+        };
+    }
+
+    #[test]
+    fn forged_message_does_not_move_replay_window() {
+        let mut node = create_routing_node();
+        let (message_request, _) = generate_routing_messages();
+        let client_keys = crypto::sign::gen_keypair();
+        let claimant = ::types::Address::Client(client_keys.0);
+        let routing_message = RoutingMessage {
+            from_authority: Authority::Client(NameType::new([1u8; 64]), client_keys.0),
+            ..message_request
+        };
+        let signed = |timestamp: i64, secret_key: &crypto::sign::SecretKey| {
+            let encoded_body = ::utils::encode(&(&::types::PROTOCOL_VERSION, &routing_message,
+                                                 &claimant, &0u8, &timestamp)).unwrap();
+            let signature = crypto::sign::sign_detached(&encoded_body, secret_key);
+            ::messages::SignedMessage::with_signature(claimant.clone(), routing_message.clone(),
+                0u8, timestamp, signature).unwrap()
+        };
+        let now = ::messages::current_timestamp();
+
+        // a forged message from the edge of the window would push the genuine ones out of it
+        let other_keys = crypto::sign::gen_keypair();
+        let forged_message = signed(now + 290000, &other_keys.1);
+        assert!(node.filter.check(&forged_message).is_ok());
+        match node.accumulate(&forged_message) {
+            Err(::error::RoutingError::FailedSignature) => {},
+            _ => panic!("Forged message should fail its signature."),
+        };
+        let genuine_message = signed(now - 20000, &client_keys.1);
+        assert!(node.filter.check(&genuine_message).is_ok());
+        assert!(node.accumulate(&genuine_message).is_ok());
+    }
+
+    #[test]
+    fn accumulate_drops_unverifiable_node_claimant() {
+        let mut node = create_routing_node();
+        let (message_request, _) = generate_routing_messages();
+        let id = ::id::Id::new();
+        let claimant = ::types::Address::Node(id.name());
+        let signed_message = ::messages::SignedMessage::new(claimant.clone(),
+            message_request.clone(), id.signing_private_key()).unwrap();
+        match node.accumulate(&signed_message) {
+            Err(::error::RoutingError::FailedSignature) => {},
+            _ => panic!("Message of an unknown node should be dropped."),
+        };
+
+        // a connect request carrying an uncertified public id does not vouch for itself
+        let mut public_id = ::public_id::PublicId::new(&id);
+        public_id.assign_relocated_name(::rand::random());
+        let connect_request = ::messages::ConnectRequest {
+            local_endpoints: vec![],
+            external_endpoints: vec![],
+            requester_fob: public_id.clone(),
+        };
+        let routing_message = RoutingMessage {
+            from_authority: Authority::ManagedNode(public_id.name()),
+            to_authority: Authority::ManagedNode(::rand::random()),
+            content: Content::InternalRequest(
+                ::messages::InternalRequest::Connect(connect_request)),
+            message_id: MessageId::new(),
+        };
+        let signed_message = ::messages::SignedMessage::new(
+            ::types::Address::Node(public_id.name()), routing_message,
+            id.signing_private_key()).unwrap();
+        match node.accumulate(&signed_message) {
+            Err(::error::RoutingError::FailedSignature) => {},
+            _ => panic!("Self-certified connect request should be dropped."),
+        };
+    }
+
+    #[test]
+    fn accumulate_counts_only_known_claimants() {
+        let mut node = create_routing_node();
+        let (message_request, _) = generate_routing_messages();
+        let threshold = node.group_threshold();
+        assert!(threshold > 1);
+        let ids = (0..threshold).map(|_| ::id::Id::new()).collect::<Vec<_>>();
+        let sign = |id: &::id::Id| ::messages::SignedMessage::new(
+            ::types::Address::Node(id.name()), message_request.clone(),
+            id.signing_private_key()).unwrap();
+
+        // invented names never count towards the quorum
+        for id in ids.iter() {
+            match node.accumulate(&sign(id)) {
+                Err(::error::RoutingError::FailedSignature) => {},
+                _ => panic!("Claimant with an unknown public id should not be counted."),
+            };
+        }
+
+        for id in ids.iter().take(threshold - 1) {
+            let public_id = ::public_id::PublicId::new(id);
+            node.core.cache_public_id(public_id);
+            match node.accumulate(&sign(id)) {
+                Ok(None) => {},
+                _ => panic!("Quorum should not be reached yet."),
+            };
+        }
+        // a claimant forgotten since it was accumulated no longer counts either
+        let forgotten = ::public_id::PublicId::new(&ids[0]);
+        node.core.forget_public_id(&forgotten.name());
+        let last = ::public_id::PublicId::new(&ids[threshold - 1]);
+        node.core.cache_public_id(last);
+        match node.accumulate(&sign(&ids[threshold - 1])) {
+            Ok(None) => {},
+            _ => panic!("Forgotten claimant should not be counted."),
+        };
+
+        node.core.cache_public_id(forgotten);
+        match node.accumulate(&sign(&ids[0])) {
+            Ok(Some((message, None))) => assert_eq!(message, message_request),
+            _ => panic!("Known claimants should reach the quorum."),
+        };
+    }
+
+    #[test]
+    fn content_is_sealed_end_to_end() {
+        // keep the queue of the node to catch the message it sends to itself
+        let (mut node, queue_receiver) = create_queued_routing_node();
+        node.set_encrypt_content(true);
+        let our_name: NameType = ::rand::random();
+        assert!(node.core.assign_network_name(&our_name));
+        let our_public_id = ::public_id::PublicId::new(node.core.id());
+        node.core.cache_public_id(our_public_id);
+
+        // a first node as our only contact, so we are connected
+        let mut peer_id = ::id::Id::new();
+        let first_node_name = ::utils::first_node_name(
+            &::public_id::PublicId::new(&peer_id).client_name());
+        assert!(peer_id.assign_relocated_name(first_node_name.clone()));
+        assert!(node.core.add_peer(::routing_core::ConnectionName::Routing(first_node_name),
+            ::test_utils::test::random_connection(), ::public_id::PublicId::new(&peer_id)));
+
+        let (_, routing_message) = generate_routing_messages();
+        let our_authority = Authority::ManagedNode(our_name.clone());
+        assert!(node.send_content(our_authority.clone(), our_authority,
+                                  routing_message.content.clone(),
+                                  routing_message.message_id).is_ok());
+        let mut sent_message = None;
+        while let Ok(action) = queue_receiver.try_recv() {
+            if let Action::SendMessage(signed_message) = action {
+                sent_message = Some(signed_message);
+            };
+        }
+        let signed_message = sent_message.expect("Message to ourselves should be queued.");
+
+        // a relay can not read the content
+        let sealed_content = signed_message.get_routing_message().content.clone();
+        match sealed_content {
+            Content::Encrypted(_) => {},
+            ref content => panic!("Content should be sealed, got {:?}", content),
+        };
+        let relay_keys = crypto::box_::gen_keypair();
+        match sealed_content.decrypt(&relay_keys.1) {
+            Err(::error::RoutingError::FailedDecryption) => {},
+            _ => panic!("Relay should not be able to open the content."),
+        };
+
+        match node.accumulate(&signed_message) {
+            Ok(Some((message, _))) => assert_eq!(message.content, routing_message.content),
+            _ => panic!("Recipient should open the content."),
+        };
+    }
+
+    #[test]
+    fn unsealed_bytes_drop_the_connection() {
+        let (mut node, queue_receiver) = create_queued_routing_node();
+        let (routing_message, _) = generate_routing_messages();
+        let keys = crypto::sign::gen_keypair();
+        let signed_message = ::messages::SignedMessage::new(
+            ::types::Address::Node(::rand::random()), routing_message, &keys.1).unwrap();
+        let connection = ::test_utils::test::random_connection();
+        node.bytes_received(connection.clone(),
+                            ::utils::encode_envelope(&signed_message).unwrap());
+        match queue_receiver.try_recv() {
+            Ok(Action::DropConnections(connections)) => assert_eq!(connections, vec![connection]),
+            _ => panic!("Connection sending unsealed bytes should be dropped."),
+        };
+    }
+
+    #[test]
+    fn only_originators_are_charged_for_their_messages() {
+        use routing_core::ConnectionName;
+        let name: NameType = ::rand::random();
+        let other_name: NameType = ::rand::random();
+        let client_key = crypto::sign::gen_keypair().0;
+        let node = ::types::Address::Node(name.clone());
+        let client = ::types::Address::Client(client_key);
+        assert!(super::is_originator(&ConnectionName::Routing(name.clone()), &node));
+        assert!(super::is_originator(&ConnectionName::Bootstrap(name.clone()), &node));
+        assert!(super::is_originator(&ConnectionName::Relay(client.clone()), &client));
+
+        // a node passing on the messages of others is not charged for them
+        assert!(!super::is_originator(&ConnectionName::Routing(other_name.clone()), &node));
+        assert!(!super::is_originator(&ConnectionName::Bootstrap(other_name), &node));
+        assert!(!super::is_originator(&ConnectionName::Routing(name), &client));
+        assert!(!super::is_originator(&ConnectionName::Relay(node),
+                                      &::types::Address::Client(crypto::sign::gen_keypair().0)));
+    }
+
+    #[test]
+    fn only_verified_claimants_are_rate_limited() {
+        let (action_sender, action_receiver) = mpsc::channel::<Action>();
+        let (event_sender, _event_receiver) = mpsc::channel::<Event>();
+        let (transport, transport_receiver) = Network::new().new_transport();
+        let rate_limits = ::types::RateLimits::new((10, 10), (1, 1), 10).unwrap();
+        let routing_config = RoutingConfig::default_config().with_rate_limits(rate_limits);
+        let mut node = RoutingNode::with_transport(action_sender, action_receiver, event_sender,
+            false, None, routing_config, vec![], vec![], transport, transport_receiver);
+        let victim = ::id::Id::new();
+        let victim_public_id = ::public_id::PublicId::new(&victim);
+        node.core.cache_public_id(victim_public_id);
+        let claimant = ::types::Address::Node(victim.name());
+
+        // messages spoofing the victim do not use up its rate
+        let spoofer = crypto::sign::gen_keypair();
+        for _ in 0..3 {
+            let (routing_message, _) = generate_routing_messages();
+            let forged_message = ::messages::SignedMessage::new(claimant.clone(), routing_message,
+                                                                &spoofer.1).unwrap();
+            match node.accumulate(&forged_message) {
+                Err(::error::RoutingError::FailedSignature) => {},
+                _ => panic!("Spoofed message should fail its signature."),
+            };
+        }
+        let (routing_message, _) = generate_routing_messages();
+        let signed_message = ::messages::SignedMessage::new(claimant.clone(), routing_message,
+                                                            victim.signing_private_key()).unwrap();
+        assert!(node.accumulate(&signed_message).is_ok());
+        match node.accumulate(&signed_message) {
+            Err(::error::RoutingError::RateExceeded) => {},
+            _ => panic!("Second message should exceed the rate of the claimant."),
+        };
+    }
+
+    #[test]
+    fn relay_refuses_a_network_name_without_admission_work() {
+        let difficulty = 8u8;
+        let (action_sender, action_receiver) = mpsc::channel::<Action>();
+        let (event_sender, _event_receiver) = mpsc::channel::<Event>();
+        let (transport, transport_receiver) = Network::new().new_transport();
+        let routing_config = RoutingConfig::default_config()
+                                 .with_admission_difficulty(difficulty).unwrap();
+        let mut relay = RoutingNode::with_transport(action_sender, action_receiver, event_sender,
+            false, None, routing_config, vec![], vec![], transport, transport_receiver);
+        let relay_name: NameType = ::rand::random();
+        assert!(relay.core.assign_network_name(&relay_name));
+
+        // the joining node runs without a difficulty and is bootstrapped off the relay
+        let (mut joiner, joiner_queue) = create_queued_routing_node();
+        assert!(joiner.core.add_peer(::routing_core::ConnectionName::Bootstrap(relay_name.clone()),
+            ::test_utils::test::random_connection(), ::public_id::PublicId::new(relay.core.id())));
+        let joiner_public_id = ::public_id::PublicId::new(joiner.core.id());
+        let joiner_key = joiner.core.id().signing_public_key();
+        let joiner_secret_key = joiner.core.id().signing_private_key().clone();
+        let request_network_name = |nonce: u64| {
+            let routing_message = RoutingMessage {
+                from_authority: Authority::Client(relay_name.clone(), joiner_key.clone()),
+                to_authority: Authority::NaeManager(joiner_public_id.name()),
+                content: Content::InternalRequest(InternalRequest::RequestNetworkName(
+                    joiner_public_id.clone(), nonce)),
+                message_id: MessageId::new(),
+            };
+            ::messages::SignedMessage::new(::types::Address::Client(joiner_key.clone()),
+                routing_message, &joiner_secret_key).unwrap()
+        };
+        let lazy_nonce = (0u64..).find(|nonce|
+            !::proof_of_work::verify(&joiner_public_id, difficulty, *nonce)).unwrap();
+        let request = request_network_name(lazy_nonce);
+
+        match relay.message_received(request.clone()) {
+            Err(::error::RoutingError::InsufficientProofOfWork) => {},
+            _ => panic!("Relay should not pass on a request without admission work."),
+        };
+        let refusal = relay.admission_refusal(&request).unwrap()
+                           .expect("Relay should refuse the request.");
+
+        // the joiner learns the difficulty from the refusal and solves it
+        let (message, _) = joiner.accumulate(&refusal).unwrap().unwrap();
+        match message.content {
+            Content::InternalResponse(ref response) =>
+                assert!(joiner.handle_insufficient_proof_of_work(response.clone(),
+                    message.from_authority.clone(), message.to_authority.clone()).is_ok()),
+            ref content => panic!("Expected a refusal, got {:?}", content),
+        };
+        let nonce = match joiner_queue.recv() {
+            Ok(Action::SendContent(_, _, Content::InternalRequest(
+                    InternalRequest::RequestNetworkName(ref public_id, nonce)), _)) => {
+                assert_eq!(*public_id, joiner_public_id);
+                nonce
+            },
+            _ => panic!("Joiner should request a network name again."),
+        };
+        assert!(relay.admission_refusal(&request_network_name(nonce)).unwrap().is_none());
+
+        // the same refusal again, or one asking for too much work, is not solved again
+        assert!(!joiner.core.retry_network_name(&relay_name, difficulty));
+        assert!(!joiner.core.retry_network_name(&relay_name,
+                                                ::types::MAX_ADMISSION_DIFFICULTY + 1));
+    }
 }
@@ -16,19 +16,26 @@
 // relating to use of the SAFE Network Software.
 
 use std::cmp;
-use std::usize;
 use std::collections::HashMap;
 
 use crust::{Endpoint, Connection};
+use time::Duration;
 
 use common_bits::*;
 use public_id::PublicId;
-use name_type::{closer_to_target, closer_to_target_or_equal, NameType};
-use types;
+use snapshot::ContactSnapshot;
+use name_type::{closer_to_target, closer_to_target_or_equal, NameType, NAME_TYPE_LEN};
 
-static BUCKET_SIZE: usize = 1;
 pub static PARALLELISM: usize = 4;
-static OPTIMAL_SIZE: usize = 64;
+// one bucket for each number of leading bits a name can share with ours
+const BUCKET_COUNT: usize = 8 * NAME_TYPE_LEN;
+// the reliability a contact needs to count as reliable
+const MIN_RELIABILITY: f64 = 0.9;
+// the reliability of a contact not probed yet, below that of a contact answering its probes
+const UNPROBED_RELIABILITY: f64 = 0.5;
+// the weight of the latest liveness probe in the reliability of a contact, so that older probes
+// count less and less
+const PROBE_WEIGHT: f64 = 0.25;
 
 #[derive(Clone, Debug)]
 #[allow(unused)]
@@ -37,6 +44,10 @@ pub struct NodeInfo {
     pub endpoints: Vec<Endpoint>,
     //pub connected_endpoint: Option<Endpoint>,
     pub connection: Option<Connection>,
+    /// The smoothed round trip time of the answered liveness probes.
+    pub round_trip_time: Option<Duration>,
+    /// The smoothed share of liveness probes answered, from 0 to 1.
+    pub reliability: f64,
     #[cfg(test)]
     pub id: NameType,
 }
@@ -52,6 +63,8 @@ impl NodeInfo {
             public_id: public_id,
             endpoints: endpoints,
             connection: connection,
+            round_trip_time: None,
+            reliability: UNPROBED_RELIABILITY,
         }
     }
     #[cfg(not(test))]
@@ -69,6 +82,8 @@ impl NodeInfo {
             public_id: public_id,
             endpoints: endpoints,
             connection: connection,
+            round_trip_time: None,
+            reliability: UNPROBED_RELIABILITY,
             id: id,
         }
     }
@@ -76,40 +91,108 @@ impl NodeInfo {
     pub fn id(&self) -> NameType {
         self.id.clone()
     }
+
+    /// Record an answered liveness probe and its round trip time.  Both the round trip time and
+    /// the reliability are smoothed, so that recent probes count most.
+    pub fn record_answered_probe(&mut self, round_trip_time: Duration) {
+        self.round_trip_time = Some(match self.round_trip_time {
+            Some(smoothed) => (smoothed * 7 + round_trip_time) / 8,
+            None => round_trip_time,
+        });
+        self.reliability += (1f64 - self.reliability) * PROBE_WEIGHT;
+    }
+
+    /// Record a liveness probe left unanswered.
+    pub fn record_missed_probe(&mut self) {
+        self.reliability -= self.reliability * PROBE_WEIGHT;
+    }
+
+    /// Returns true if the contact has answered its recent liveness probes and hardly missed
+    /// any.  A contact not probed yet is not reliable.
+    pub fn is_reliable(&self) -> bool {
+        self.reliability >= MIN_RELIABILITY
+    }
+
+    /// Order contacts by preference as a next hop: a reliable one first.  Among reliable
+    /// contacts the one with the shorter round trip time is preferred, otherwise the more
+    /// reliable one; a measured round trip time goes before an unknown one.
+    pub fn compare_quality(&self, other: &NodeInfo) -> cmp::Ordering {
+        match other.is_reliable().cmp(&self.is_reliable()) {
+            cmp::Ordering::Equal => {},
+            ordering => return ordering,
+        };
+        let by_reliability = other.reliability.partial_cmp(&self.reliability)
+                                  .unwrap_or(cmp::Ordering::Equal);
+        let by_round_trip_time = match (self.round_trip_time, other.round_trip_time) {
+            (Some(ours), Some(theirs)) => ours.cmp(&theirs),
+            (Some(_), None) => cmp::Ordering::Less,
+            (None, Some(_)) => cmp::Ordering::Greater,
+            (None, None) => cmp::Ordering::Equal,
+        };
+        match (self.is_reliable(), by_reliability, by_round_trip_time) {
+            (true, by_reliability, cmp::Ordering::Equal) => by_reliability,
+            (true, _, by_round_trip_time) => by_round_trip_time,
+            (false, cmp::Ordering::Equal, by_round_trip_time) => by_round_trip_time,
+            (false, by_reliability, _) => by_reliability,
+        }
+    }
+}
+
+/// A bucket holds the contacts that share the same number of leading bits with our name, closest
+/// to us first, and a replacement cache of names that found the bucket full, most recently seen
+/// last.
+struct Bucket {
+    contacts: Vec<NodeInfo>,
+    replacements: Vec<NameType>,
+}
+
+impl Bucket {
+    fn new() -> Bucket {
+        Bucket { contacts: Vec::new(), replacements: Vec::new() }
+    }
 }
 
 /// The RoutingTable class is used to maintain a list of contacts to which the node is connected.
+/// Contacts are kept in buckets by the number of leading bits they share with our name.  A bucket
+/// holds up to bucket_size contacts, except for members of our close group, which are always kept.
 #[allow(unused)]
 pub struct RoutingTable {
-    routing_table: Vec<NodeInfo>,
+    buckets: Vec<Bucket>,
+    size: usize,
     lookup_map: HashMap<Endpoint, NameType>,
     our_id: NameType,
+    group_size: usize,
+    bucket_size: usize,
+    // counts the changes to the contacts, so that a snapshot can tell whether it is outdated
+    version: u64,
 }
 
 #[allow(unused)]
 impl RoutingTable {
-    pub fn new(our_id: &NameType) -> RoutingTable {
+    // The group size and bucket size come from a RoutingConfig, which guarantees both are at
+    // least one.
+    pub fn new(our_id: &NameType, group_size: usize, bucket_size: usize) -> RoutingTable {
         RoutingTable {
-            routing_table: Vec::<NodeInfo>::new(),
+            buckets: (0..BUCKET_COUNT).map(|_| Bucket::new()).collect(),
+            size: 0usize,
             lookup_map: HashMap::new(),
             our_id: our_id.clone(),
+            group_size: group_size,
+            bucket_size: bucket_size,
+            version: 0,
         }
     }
 
-    pub fn get_bucket_size() -> usize {
-        BUCKET_SIZE
-    }
-
     pub fn get_parallelism() -> usize {
         PARALLELISM
     }
 
-    pub fn get_optimal_size() -> usize {
-        OPTIMAL_SIZE
+    pub fn get_group_size(&self) -> usize {
+        self.group_size
     }
 
-    pub fn get_group_size() -> usize {
-        types::GROUP_SIZE
+    pub fn get_bucket_size(&self) -> usize {
+        self.bucket_size
     }
 
     /// Adds a contact to the routing table.  If the contact is added, the first return arg is true,
@@ -117,52 +200,59 @@ impl RoutingTable {
     /// one is returned in the second field, otherwise the optional field is empty.  The following
     /// steps are used to determine whether to add the new contact or not:
     ///
-    /// 1 - if the contact is ourself, or doesn't have a valid public key, or is already in the
-    ///     table, it will not be added
-    /// 2 - if the routing table is not full (size < OptimalSize()), the contact will be added
-    /// 3 - if the contact is within our close group, it will be added
-    /// 4 - if we can find a candidate for removal (a contact in a bucket with more than BUCKET_SIZE
-    ///     contacts, which is also not within our close group), and if the new contact will fit in
-    ///     a bucket closer to our own bucket, then we add the new contact.
+    /// 1 - if the contact is ourself, or is already in the table, it will not be added
+    /// 2 - if the bucket of the contact holds less than bucket_size contacts, it will be added
+    /// 3 - if the contact is within our close group, it will be added; if its bucket then holds
+    ///     too many contacts, the furthest one of that bucket outside of our close group is
+    ///     dropped and becomes a replacement candidate
+    /// 4 - otherwise the contact becomes a replacement candidate of its bucket
     pub fn add_node(&mut self, their_info: NodeInfo) -> (bool, Option<NodeInfo>) {
-        if self.our_id == their_info.id() {
+        let their_id = their_info.id();
+        if self.our_id == their_id || self.has_node(&their_id) {
             return (false, None);
         }
 
-        if self.has_node(&their_info.id()) {
+        let index = self.bucket_index(&their_id);
+        if self.buckets[index].contacts.len() >= self.bucket_size &&
+           !self.is_closer_than_close_group(&their_id) {
+            let _ = self.add_candidate(&their_id);
             return (false, None);
         }
 
-        if self.routing_table.len() < RoutingTable::get_optimal_size() {
-            self.push_back_then_sort(their_info);
+        self.insert(index, their_info);
+        if self.buckets[index].contacts.len() <= self.bucket_size {
             return (true, None);
         }
-
-        if closer_to_target(&their_info.id(),
-                            &self.routing_table[RoutingTable::get_group_size()].id(),
-                            &self.our_id) {
-            self.push_back_then_sort(their_info);
-            let removal_node_index = self.find_candidate_for_removal();
-            if removal_node_index == usize::MAX {
-                return (true, None);
-            } else {
-                let removal_node = self.routing_table[removal_node_index].clone();
-                self.remove_dangling_endpoints(&removal_node.id());
-                let _ = self.routing_table.remove(removal_node_index);
-                return (true, Some(removal_node));
-            }
+        let furthest_close_node = match self.nth_closest(self.group_size - 1) {
+            Some(node_info) => node_info.id(),
+            None => return (true, None),
+        };
+        let last = self.buckets[index].contacts.len() - 1;
+        if !closer_to_target(&furthest_close_node, &self.buckets[index].contacts[last].id(),
+                             &self.our_id) {
+            // the whole bucket is within our close group
+            return (true, None);
         }
+        let removal_node = self.remove(index, last);
+        let bucket_size = self.bucket_size;
+        push_candidate(&mut self.buckets[index].replacements, removal_node.id(), bucket_size);
+        (true, Some(removal_node))
+    }
 
-        let removal_node_index = self.find_candidate_for_removal();
-        if removal_node_index != usize::MAX &&
-           self.new_node_is_better_than_existing(&their_info.id(), removal_node_index) {
-            let removal_node = self.routing_table[removal_node_index].clone();
-            self.remove_dangling_endpoints(&removal_node.id());
-            let _ = self.routing_table.remove(removal_node_index);
-            self.push_back_then_sort(their_info);
-            return (true, Some(removal_node));
+    /// Keep the name as a replacement candidate of its bucket, if that bucket is full and the name
+    /// isn't a contact already.  A bucket remembers up to bucket_size of the most recently seen
+    /// candidates.  Returns true if the name is held as a candidate.
+    pub fn add_candidate(&mut self, name: &NameType) -> bool {
+        if self.our_id == *name || self.has_node(name) {
+            return false;
         }
-        (false, None)
+        let index = self.bucket_index(name);
+        if self.buckets[index].contacts.len() < self.bucket_size {
+            return false;
+        }
+        let bucket_size = self.bucket_size;
+        push_candidate(&mut self.buckets[index].replacements, name.clone(), bucket_size);
+        true
     }
 
     /// This changes the connected status of the peer from false to true.  Only one connection is
@@ -171,136 +261,176 @@ impl RoutingTable {
     #[allow(dead_code)]
     pub fn mark_as_connected(&mut self, connection: &Connection) -> Option<NameType> {
         let endpoint = connection.peer_endpoint();
-        let has_endpoint = |ref node_info: &NodeInfo| {
-                               for ref candidate_endpoint in &node_info.endpoints {
-                                   if **candidate_endpoint == endpoint {
-                                       return true;
-                                   }
-                               }
-                               false
-                           };
-        match self.routing_table.iter().position(has_endpoint) {
-            None => None,
-            Some(index) => {
-                self.routing_table[index].connection = Some(connection.clone());
-                // always force update lookup_map
-                let _ = self.lookup_map.remove(&endpoint);
-                let _ = self.lookup_map.entry(endpoint.clone())
-                                       .or_insert(self.routing_table[index].id());
-                Some(self.routing_table[index].id())
+        let mut connected = None;
+        for bucket in self.buckets.iter_mut().rev() {
+            match bucket.contacts.iter_mut()
+                        .find(|node_info| node_info.endpoints.contains(&endpoint)) {
+                Some(node_info) => {
+                    node_info.connection = Some(connection.clone());
+                    connected = Some(node_info.id());
+                    break;
+                },
+                None => {},
             }
         }
+        match connected {
+            Some(name) => {
+                self.version += 1;
+                // always force update lookup_map
+                let _ = self.lookup_map.insert(endpoint, name.clone());
+                Some(name)
+            },
+            None => None,
+        }
+    }
+
+    /// This records an answered liveness probe and the round trip time measured on the connection
+    /// of a contact.  The round trip time is smoothed over the answered probes.  It returns the
+    /// contact's ID, or None if the connection doesn't belong to any contact in the table.
+    pub fn record_round_trip_time(&mut self, connection: &Connection, round_trip_time: Duration)
+            -> Option<NameType> {
+        let name = match self.find_by_connection(connection) {
+            Some(node_info) => {
+                node_info.record_answered_probe(round_trip_time);
+                Some(node_info.id())
+            },
+            None => None,
+        };
+        if name.is_some() {
+            self.version += 1;
+        }
+        name
+    }
+
+    /// This records a liveness probe left unanswered on the connection of a contact.  It returns
+    /// the contact's ID, or None if the connection doesn't belong to any contact in the table.
+    pub fn record_missed_probe(&mut self, connection: &Connection) -> Option<NameType> {
+        let name = match self.find_by_connection(connection) {
+            Some(node_info) => {
+                node_info.record_missed_probe();
+                Some(node_info.id())
+            },
+            None => None,
+        };
+        if name.is_some() {
+            self.version += 1;
+        }
+        name
     }
 
     /// This is used to check whether it is worth while retrieving a contact's public key from the
     /// PKI with a view to adding the contact to our routing table.  The checking procedure is the
     /// same as for 'AddNode' above, except for the lack of a public key to check in step 1.
     pub fn check_node(&self, their_id: &NameType) -> bool {
-        if self.our_id == *their_id {
+        if self.our_id == *their_id || self.has_node(their_id) {
             return false;
         }
-        if self.has_node(their_id) {
-            return false;
-        }
-        if self.routing_table.len() < RoutingTable::get_optimal_size() {
-            return true;
-        }
-        let group_size = RoutingTable::get_group_size() - 1;
-        if closer_to_target(&their_id, &self.routing_table[group_size].id(), &self.our_id) {
-            return true;
-        }
-        self.new_node_is_better_than_existing(&their_id, self.find_candidate_for_removal())
+        self.buckets[self.bucket_index(their_id)].contacts.len() < self.bucket_size ||
+            self.is_closer_than_close_group(their_id)
     }
 
-    /// This unconditionally removes the contact from the table.
-    pub fn drop_node(&mut self, node_to_drop: &NameType) {
-        let mut index_of_removal = usize::MAX;
-
-        for i in 0..self.routing_table.len() {
-            if self.routing_table[i].id() == *node_to_drop {
-                index_of_removal = i;
-                break;
-            }
+    /// This unconditionally removes the contact from the table.  If that leaves room in its
+    /// bucket, the most recently seen replacement candidate of the bucket is returned, so that it
+    /// can be connected to in place of the dropped contact.  The candidate stays in the cache
+    /// until remove_candidate is called for it.
+    pub fn drop_node(&mut self, node_to_drop: &NameType) -> Option<NameType> {
+        if self.our_id == *node_to_drop {
+            return None;
+        }
+        let index = self.bucket_index(node_to_drop);
+        let position = match self.buckets[index].contacts.iter()
+                                  .position(|node_info| node_info.id() == *node_to_drop) {
+            Some(position) => position,
+            None => return None,
+        };
+        let _ = self.remove(index, position);
+        if self.buckets[index].contacts.len() >= self.bucket_size {
+            return None;
         }
+        self.buckets[index].replacements.last().cloned()
+    }
 
-        if index_of_removal < self.routing_table.len() {
-            let removal_name : NameType = self.routing_table[index_of_removal].id();
-            self.remove_dangling_endpoints(&removal_name);
-            let _ = self.routing_table.remove(index_of_removal);
+    /// Take the name out of the replacement cache of its bucket.  Returns true if it was held.
+    pub fn remove_candidate(&mut self, name: &NameType) -> bool {
+        if self.our_id == *name {
+            return false;
         }
+        let index = self.bucket_index(name);
+        let replacements = &mut self.buckets[index].replacements;
+        let held = replacements.len();
+        replacements.retain(|candidate| candidate != name);
+        replacements.len() != held
     }
 
     /// This returns a collection of contacts to which a message should be sent onwards.  It will
     /// return all of our close group (comprising 'GroupSize' contacts) if the closest one to the
-    /// target is within our close group.  If not, it will return the 'Parallelism()' closest
-    /// contacts to the target.
+    /// target is within our close group.  If not, it will return 'Parallelism()' contacts closest
+    /// to the target.  Contacts sharing the same number of leading bits with the target are
+    /// equally useful, among those the more reliable and faster ones are preferred.
     pub fn target_nodes(&self, target: &NameType) -> Vec<NodeInfo> {
-        let mut our_close_group = Vec::new();
-        let mut closest_to_target = Vec::new();
-        let mut result: Vec<NodeInfo> = Vec::new();
-        let mut iterations = 0usize;
-
-        let parallelism = if RoutingTable::get_parallelism() < self.routing_table.len() {
-            RoutingTable::get_parallelism()
-        } else {
-            self.routing_table.len()
-        };
-
-        for iter in self.routing_table.iter() {
-            if iterations < RoutingTable::get_group_size() {
-                our_close_group.push(iter.clone());
-            }
-            closest_to_target.push(iter.clone());
-            iterations += 1;
-        }
-
-        if closest_to_target.is_empty() {
-            return result;
+        let mut closest_to_target: Option<&NodeInfo> = None;
+        for node_info in self.closest_contacts() {
+            closest_to_target = match closest_to_target {
+                Some(closest) if !closer_to_target(&node_info.id(), &closest.id(), target) =>
+                    Some(closest),
+                _ => Some(node_info),
+            };
         }
-
-        closest_to_target.sort_by(
-            |a, b| if closer_to_target(&a.id(), &b.id(), &target) {
-                cmp::Ordering::Less
-            } else {
-                cmp::Ordering::Greater
-            });
-
-        if RoutingTable::is_any_of(&our_close_group, &closest_to_target) {
-            for iter in our_close_group.iter() {
-                result.push(iter.clone());
-            }
-        } else {
-            for iter in closest_to_target.iter().take(parallelism) {
-                result.push(iter.clone());
+        let closest_to_target = match closest_to_target {
+            Some(node_info) => node_info.id(),
+            None => return Vec::new(),
+        };
+        if self.closest_contacts().take(self.group_size)
+               .any(|node_info| node_info.id() == closest_to_target) {
+            return self.our_close_group();
+        }
+        // only the best few contacts are kept in order, rather than sorting the whole table
+        let mut targets = Vec::<&NodeInfo>::with_capacity(PARALLELISM + 1);
+        for node_info in self.closest_contacts() {
+            let position = targets.iter()
+                .position(|target_node| compare_targets(node_info, target_node, target) ==
+                                        cmp::Ordering::Less)
+                .unwrap_or(targets.len());
+            if position < PARALLELISM {
+                targets.insert(position, node_info);
+                targets.truncate(PARALLELISM);
             }
         }
-        result
+        targets.into_iter().cloned().collect()
     }
 
     /// This returns our close group, i.e. the 'GroupSize' contacts closest to our ID (or the entire
     /// table if we hold less than 'GroupSize' contacts in total).
     pub fn our_close_group(&self) -> Vec<NodeInfo> {
-        let group_size = RoutingTable::get_group_size();
-        let size = cmp::min(group_size, self.routing_table.len());
-        let mut result = Vec::new();
-        for i in 0..size {
-            // is cloning advisable?
-            result.push(self.routing_table[i].clone());
-        }
-        result
+        self.closest_contacts().into_iter().take(self.group_size).cloned().collect()
     }
 
-    // /// This returns the public key for the given node if the node is in our table.
-    // pub fn public_id(&self, their_id: &NameType)->Option<PublicId> {
-    //     debug_assert!(self.is_nodes_sorted(), "RT::public_id: Nodes are not sorted");
-    //     match self.routing_table.iter().find(|&node_info| node_info.id() == *their_id) {
-    //         Some(node) => Some(node.public_id.clone()),
-    //         None => None,
-    //     }
-    // }
+    /// This returns a copy of all contacts, closest to us first.
+    pub fn snapshot(&self) -> Vec<ContactSnapshot> {
+        self.closest_contacts().into_iter().enumerate()
+            .map(|(position, node_info)| ContactSnapshot {
+                name: node_info.id(),
+                endpoints: node_info.endpoints.clone(),
+                connection: node_info.connection.clone(),
+                bucket_index: self.bucket_index(&node_info.id()),
+                in_close_group: position < self.group_size,
+                round_trip_time: node_info.round_trip_time,
+                reliable: node_info.is_reliable(),
+            })
+            .collect()
+    }
+
+    /// This returns a number that changes whenever the contacts of the table change.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// This returns the public id for the given node if the node is in our table.
+    pub fn public_id(&self, their_id: &NameType) -> Option<PublicId> {
+        self.find(their_id).map(|node_info| node_info.public_id.clone())
+    }
 
     pub fn lookup_endpoint(&self, their_endpoint: &Endpoint) -> Option<NameType> {
-        debug_assert!(self.is_nodes_sorted(), "RT::Lookup: Nodes are not sorted");
         match self.lookup_map.get(their_endpoint) {
             Some(name) => Some(name.clone()),
             None => None,
@@ -309,146 +439,132 @@ impl RoutingTable {
 
     /// This returns the length of the routing table.
     pub fn size(&self) -> usize {
-        //std::lock_guard<std::mutex> lock(mutex_);
-        self.routing_table.len()
+        self.size
     }
 
     pub fn our_name(&self) -> NameType {
         self.our_id.clone()
     }
 
+    /// Returns the endpoints of all nodes listed in the routing table, closest nodes first
+    pub fn all_endpoints(&self) -> Vec<Endpoint> {
+        self.closest_contacts().into_iter().flat_map(|n| n.endpoints.iter().cloned()).collect()
+    }
+
+    /// Returns the endpoints of the connected contacts that are reliable, closest contacts first.
+    pub fn reliable_endpoints(&self) -> Vec<Endpoint> {
+        self.closest_contacts().into_iter()
+            .filter(|n| n.connection.is_some() && n.is_reliable())
+            .flat_map(|n| n.endpoints.iter().cloned())
+            .collect()
+    }
+
     /// Returns all connections listed in the routing table
     pub fn all_connections(&self) -> Vec<::crust::Connection> {
-        self.routing_table.iter().filter_map(|n| n.connection.clone()).collect::<Vec<::crust::Connection>>()
+        self.closest_contacts().into_iter().filter_map(|n| n.connection.clone()).collect()
+    }
+
+    /// This returns true if, as far as our routing table knows, the given node is one of the
+    /// GroupSize closest nodes to the target.  Nodes unknown to us can not be accounted for, so
+    /// only a node which is provably out of range is rejected; callers counting the name towards
+    /// a quorum have to check it is a known node themselves.
+    pub fn is_in_group_range(&self, target: &NameType, name: &NameType) -> bool {
+        let mut closer_nodes = self.closest_contacts().into_iter()
+            .filter(|node_info| node_info.id() != *name &&
+                                closer_to_target(&node_info.id(), name, target))
+            .count();
+        if self.our_id != *name && closer_to_target(&self.our_id, name, target) {
+            closer_nodes += 1;
+        }
+        closer_nodes < self.get_group_size()
     }
 
     /// This returns true if the provided id is closer than or equal to the furthest node in our
     /// close group. If the routing table contains less than GroupSize nodes, then every address is
     /// considered to be in our close group range.
     pub fn address_in_our_close_group_range(&self, id: &NameType) -> bool {
-        if self.routing_table.len() < types::GROUP_SIZE {
-            return true;
+        match self.nth_closest(self.get_group_size() - 1) {
+            Some(furthest_close_node) =>
+                closer_to_target_or_equal(&id, &furthest_close_node.id(), &self.our_id),
+            None => true,
         }
-        let furthest_close_node = self.routing_table[types::GROUP_SIZE - 1].clone();
-        closer_to_target_or_equal(&id, &furthest_close_node.id(), &self.our_id)
     }
 
-    fn find_candidate_for_removal(&self) -> usize {
-        assert!(self.routing_table.len() >= RoutingTable::get_optimal_size());
-
-        let mut number_in_bucket = 0usize;
-        let mut current_bucket = 0usize;
-
-        // Start iterating from the end, i.e. the furthest from our ID.
-        let mut counter = self.routing_table.len() - 1;
-        let mut furthest_in_this_bucket = counter;
-
-        // Stop iterating at our furthest close group member since we won't remove any peer in our
-        // close group
-        let finish = RoutingTable::get_group_size();
-
-        while counter >= finish {
-            let bucket_index = self.bucket_index(&self.routing_table[counter].id());
-
-            // If we're entering a new bucket, reset details.
-            if bucket_index != current_bucket {
-                current_bucket = bucket_index;
-                number_in_bucket = 0;
-                furthest_in_this_bucket = counter;
-            }
-
-            // Check for an excess of contacts in this bucket.
-            number_in_bucket += 1;
-            if number_in_bucket > RoutingTable::get_bucket_size() {
-                break;
-            }
-
-            counter -= 1;
-        }
+    pub fn has_node(&self, node_id: &NameType) -> bool {
+        self.find(node_id).is_some()
+    }
 
-        if counter < finish {
-            usize::MAX
-        } else {
-            furthest_in_this_bucket
+    fn find(&self, name: &NameType) -> Option<&NodeInfo> {
+        if self.our_id == *name {
+            return None;
         }
+        self.buckets[self.bucket_index(name)].contacts.iter()
+            .find(|node_info| node_info.id() == *name)
     }
 
-    fn bucket_index(&self, id: &NameType) -> usize {
-        let mut index_of_mismatch = 0usize;
+    // All contacts, closest to us first: a contact in a higher bucket shares more leading bits
+    // with our name than any contact in a lower bucket, so it is closer to us.  The contacts are
+    // visited in place, so callers only pay for the contacts they look at.
+    fn closest_contacts<'a>(&'a self) -> Box<Iterator<Item = &'a NodeInfo> + 'a> {
+        Box::new(self.buckets.iter().rev().flat_map(|bucket| bucket.contacts.iter()))
+    }
 
-        while index_of_mismatch < self.our_id.0.len() {
-            if id.0[index_of_mismatch] != self.our_id.0[index_of_mismatch] {
-                break;
-            }
-            index_of_mismatch += 1;
-        }
+    fn nth_closest(&self, n: usize) -> Option<&NodeInfo> {
+        self.closest_contacts().nth(n)
+    }
 
-        if index_of_mismatch == self.our_id.0.len() {
-            return 8 * self.our_id.0.len();
+    // Whether the name would take the place of a member of our close group, or our close group is
+    // not complete yet.
+    fn is_closer_than_close_group(&self, name: &NameType) -> bool {
+        match self.nth_closest(self.group_size - 1) {
+            Some(furthest_close_node) =>
+                closer_to_target(name, &furthest_close_node.id(), &self.our_id),
+            None => true,
         }
+    }
 
-        let common_bits = K_COMMON_BITS[self.our_id.0[index_of_mismatch] as usize]
-                                       [id.0[index_of_mismatch] as usize];
-        8 * index_of_mismatch + common_bits as usize
+    fn bucket_index(&self, id: &NameType) -> usize {
+        common_leading_bits(&self.our_id, id)
     }
 
-    pub fn has_node(&self, node_id: &NameType) -> bool {
-        for node_info in &self.routing_table {
-            if node_info.id() == *node_id {
-                return true;
-            }
-        }
-        false
+    fn find_by_connection(&mut self, connection: &Connection) -> Option<&mut NodeInfo> {
+        let name = match self.lookup_map.get(&connection.peer_endpoint()) {
+            Some(name) => name.clone(),
+            None => return None,
+        };
+        let index = self.bucket_index(&name);
+        self.buckets[index].contacts.iter_mut()
+            .find(|node_info| node_info.id() == name &&
+                              node_info.connection == Some(connection.clone()))
     }
 
-    fn push_back_then_sort(&mut self, node_info: NodeInfo) {
+    fn insert(&mut self, index: usize, node_info: NodeInfo) {
         match node_info.connection.clone().map(|c|c.peer_endpoint()) {
             Some(endpoint) => {
-                let _ = self.lookup_map.remove(&endpoint);
-                let _ = self.lookup_map.entry(endpoint.clone())
-                                       .or_insert(node_info.id());
+                let _ = self.lookup_map.insert(endpoint, node_info.id());
             }
             None => (),
         };
-        self.routing_table.push(node_info);
-        let our_id = &self.our_id;
-        self.routing_table.sort_by(
-            |a, b| if closer_to_target(&a.id(), &b.id(), our_id) {
-                cmp::Ordering::Less
-            } else {
-                cmp::Ordering::Greater
-            });
-    }
-
-    fn is_nodes_sorted(&self) -> bool {
-        for i in 1..self.routing_table.len() {
-            if closer_to_target(&self.routing_table[i].id(),
-                                &self.routing_table[i - 1].id(),
-                                &self.our_id) {
-                return false;
-            }
-        }
-        true
-    }
-
-    fn new_node_is_better_than_existing(&self,
-                                        new_node: &NameType,
-                                        removal_node_index: usize)
-                                        -> bool {
-        if removal_node_index >= self.routing_table.len() {
-            return false;
-        }
-        let removal_node = &self.routing_table[removal_node_index];
-        self.bucket_index(new_node) > self.bucket_index(&removal_node.id())
+        {
+            let our_id = &self.our_id;
+            let bucket = &mut self.buckets[index];
+            let name = node_info.id();
+            bucket.replacements.retain(|candidate| *candidate != name);
+            let position = bucket.contacts.iter()
+                .position(|contact| closer_to_target(&name, &contact.id(), our_id))
+                .unwrap_or(bucket.contacts.len());
+            bucket.contacts.insert(position, node_info);
+        }
+        self.size += 1;
+        self.version += 1;
     }
 
-    fn is_any_of(vec_close_group: &Vec<NodeInfo>, vec_closest_to_target: &Vec<NodeInfo>) -> bool {
-        for iter in vec_close_group.iter() {
-            if iter.id() == vec_closest_to_target[0].id() {
-                return true;
-            }
-        }
-        false
+    fn remove(&mut self, index: usize, position: usize) -> NodeInfo {
+        let node_info = self.buckets[index].contacts.remove(position);
+        self.remove_dangling_endpoints(&node_info.id());
+        self.size -= 1;
+        self.version += 1;
+        node_info
     }
 
     fn remove_dangling_endpoints(&mut self, name_removed: &NameType) {
@@ -463,6 +579,52 @@ impl RoutingTable {
     }
 }
 
+// Orders contacts by how well they route a message on to the target: the contact sharing more
+// leading bits with the target first, among those the more reliable and faster one, and then the
+// one closer to the target.
+fn compare_targets(lhs: &NodeInfo, rhs: &NodeInfo, target: &NameType) -> cmp::Ordering {
+    match common_leading_bits(&rhs.id(), target).cmp(&common_leading_bits(&lhs.id(), target)) {
+        cmp::Ordering::Equal => match lhs.compare_quality(rhs) {
+            cmp::Ordering::Equal => if closer_to_target(&lhs.id(), &rhs.id(), target) {
+                cmp::Ordering::Less
+            } else {
+                cmp::Ordering::Greater
+            },
+            ordering => ordering,
+        },
+        ordering => ordering,
+    }
+}
+
+// The number of leading bits both names have in common.
+fn common_leading_bits(lhs: &NameType, rhs: &NameType) -> usize {
+    let mut index_of_mismatch = 0usize;
+
+    while index_of_mismatch < lhs.0.len() {
+        if rhs.0[index_of_mismatch] != lhs.0[index_of_mismatch] {
+            break;
+        }
+        index_of_mismatch += 1;
+    }
+
+    if index_of_mismatch == lhs.0.len() {
+        return 8 * lhs.0.len();
+    }
+
+    let common_bits = K_COMMON_BITS[lhs.0[index_of_mismatch] as usize]
+                                   [rhs.0[index_of_mismatch] as usize];
+    8 * index_of_mismatch + common_bits as usize
+}
+
+// Remember the name as the most recently seen candidate, forgetting the oldest beyond capacity.
+fn push_candidate(replacements: &mut Vec<NameType>, name: NameType, capacity: usize) {
+    replacements.retain(|candidate| *candidate != name);
+    replacements.push(name);
+    while replacements.len() > capacity {
+        let _ = replacements.remove(0);
+    }
+}
+
 
 
 #[cfg(test)]
@@ -470,6 +632,9 @@ mod test {
     extern crate bit_vec;
     use rand;
 
+    // contacts of as many distinct buckets fit in a table of any bucket size
+    const TABLE_SIZE: usize = 64;
+
     enum ContactType {
         Far,
         Mid,
@@ -560,15 +725,12 @@ mod test {
             let node_info = create_random_node_info();
             let table = RoutingTableUnitTest {
                 our_id: node_info.id().clone(),
-                table: super::RoutingTable {
-                    routing_table: Vec::new(),
-                    lookup_map: ::std::collections::HashMap::new(),
-                    our_id: node_info.id().clone(),
-                },
+                table: super::RoutingTable::new(&node_info.id(), ::types::GROUP_SIZE,
+                                                ::types::BUCKET_SIZE),
                 buckets: initialise_buckets(&node_info.id()),
                 node_info: node_info,
                 initial_count:
-                    (::rand::random::<usize>() % (super::RoutingTable::get_group_size() - 1)) + 1,
+                    (::rand::random::<usize>() % (::types::GROUP_SIZE - 1)) + 1,
                 added_ids: Vec::new(),
             };
 
@@ -600,21 +762,17 @@ mod test {
         }
 
         fn complete_filling_table(&mut self) {
-            for i in self.initial_count..super::RoutingTable::get_optimal_size() {
+            for i in self.initial_count..TABLE_SIZE {
                 self.node_info.id = self.buckets[i].mid_contact.clone();
                 self.added_ids.push(self.node_info.id().clone());
                 assert!(self.table.add_node(self.node_info.clone()).0);
             }
 
-            assert_eq!(super::RoutingTable::get_optimal_size(), self.table.size());
+            assert_eq!(TABLE_SIZE, self.table.size());
         }
 
         fn public_id(&self, their_id: &::NameType) -> Option<::public_id::PublicId> {
-            debug_assert!(self.table.is_nodes_sorted(), "RT::public_id: Nodes are not sorted");
-            match self.table.routing_table.iter().find(|&node_info| node_info.id() == *their_id) {
-                Some(node) => Some(node.public_id.clone()),
-                None => None,
-            }
+            self.table.public_id(their_id)
         }
 
     }
@@ -643,17 +801,16 @@ mod test {
             public_id: public_id,
             endpoints: random_endpoints(),
             connection: None,
+            round_trip_time: None,
+            reliability: UNPROBED_RELIABILITY,
         }
     }
 
     fn create_random_routing_tables(num_of_tables: usize) -> Vec<super::RoutingTable> {
         let mut vector: Vec<super::RoutingTable> = Vec::with_capacity(num_of_tables);
         for _ in 0..num_of_tables {
-            vector.push(super::RoutingTable {
-                routing_table: Vec::new(),
-                lookup_map: ::std::collections::HashMap::new(),
-                our_id: rand::random()
-            });
+            vector.push(super::RoutingTable::new(&rand::random(), ::types::GROUP_SIZE,
+                                                 ::types::BUCKET_SIZE));
         }
         vector
     }
@@ -679,25 +836,22 @@ mod test {
     #[test]
     fn routing_table_test() {
 
-        let mut table = super::RoutingTable {
-            routing_table: Vec::new(),
-            lookup_map: ::std::collections::HashMap::new(),
-            our_id: rand::random(),
-        };
+        let mut table = super::RoutingTable::new(&rand::random(), ::types::GROUP_SIZE,
+                                                 ::types::BUCKET_SIZE);
 
-        for _ in 0..super::RoutingTable::get_group_size() {
+        for _ in 0..::types::GROUP_SIZE {
             let id = rand::random();
             assert!(table.check_node(&id));
         }
 
         assert_eq!(table.size(), 0);
 
-        for _ in 0..super::RoutingTable::get_group_size() {
+        for _ in 0..::types::GROUP_SIZE {
             let node_info = create_random_node_info();
             assert!(table.add_node(node_info).0);
         }
 
-        assert_eq!(table.size(), super::RoutingTable::get_group_size());
+        assert_eq!(table.size(), ::types::GROUP_SIZE);
     }
 
     #[test]
@@ -723,7 +877,7 @@ mod test {
                     ::std::cmp::Ordering::Greater
                 });
             let mut groups = it.our_close_group();
-            assert_eq!(groups.len(), super::RoutingTable::get_group_size());
+            assert_eq!(groups.len(), ::types::GROUP_SIZE);
 
             // TODO(Spandan) vec.dedup does not compile - manually doing it
             if groups.len() > 1 {
@@ -739,9 +893,9 @@ mod test {
                 assert_eq!(new_end, groups.len());
             }
 
-            assert_eq!(groups.len(), super::RoutingTable::get_group_size());
+            assert_eq!(groups.len(), ::types::GROUP_SIZE);
 
-            for i in 0..super::RoutingTable::get_group_size() {
+            for i in 0..::types::GROUP_SIZE {
                 assert!(groups[i].id() == addresses[i + 1]);
             }
         }
@@ -750,200 +904,247 @@ mod test {
     #[test]
     fn add_node_test() {
         let mut test = RoutingTableUnitTest::new();
-
+        test.table = super::RoutingTable::new(&test.our_id, ::types::GROUP_SIZE, 2usize);
         assert_eq!(test.table.size(), 0);
 
         // try with our id - should fail
         test.node_info.id = test.table.our_id.clone();
         let mut result_of_add = test.table.add_node(test.node_info.clone());
         assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
+        assert!(result_of_add.1.is_none());
         assert_eq!(test.table.size(), 0);
 
-        // add first contact
+        // fill our close group from the buckets closest to us, each contact only once
+        for i in 0..::types::GROUP_SIZE {
+            test.node_info.id = test.buckets[99 - i].mid_contact.clone();
+            result_of_add = test.table.add_node(test.node_info.clone());
+            assert!(result_of_add.0);
+            assert!(result_of_add.1.is_none());
+            result_of_add = test.table.add_node(test.node_info.clone());
+            assert!(!result_of_add.0);
+            assert!(result_of_add.1.is_none());
+            assert_eq!(i + 1, test.table.size());
+        }
+
+        // bucket 0 (furthest) takes contacts up to the bucket size
         test.node_info.id = test.buckets[0].far_contact.clone();
         result_of_add = test.table.add_node(test.node_info.clone());
         assert!(result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(test.table.size(), 1);
-
-        // try with the same contact - should fail
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(test.table.size(), 1);
-
-        // Add further 'OptimalSize()' - 1 contacts (should all succeed with no removals).  Set this
-        // up so that bucket 0 (furthest) and bucket 1 have 3 contacts each and all others have 0 or
-        // 1 contacts.
-
-        // Bucket 0
+        assert!(result_of_add.1.is_none());
         test.node_info.id = test.buckets[0].mid_contact.clone();
         result_of_add = test.table.add_node(test.node_info.clone());
         assert!(result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(2, test.table.size());
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(2, test.table.size());
+        assert!(result_of_add.1.is_none());
+        assert_eq!(::types::GROUP_SIZE + 2, test.table.size());
 
+        // a further contact outside our close group is refused and kept as a candidate
         test.node_info.id = test.buckets[0].close_contact.clone();
         result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(3, test.table.size());
-        result_of_add = test.table.add_node(test.node_info.clone());
         assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(3, test.table.size());
-
-        // Bucket 1
-        test.node_info.id = test.buckets[1].far_contact.clone();
+        assert!(result_of_add.1.is_none());
+        assert_eq!(::types::GROUP_SIZE + 2, test.table.size());
+        assert_eq!(test.table.buckets[0].replacements, vec![test.buckets[0].close_contact]);
+
+        // a contact closer than our close group is added to a full bucket; as the bucket is
+        // within our close group, no contact is dropped
+        test.node_info.id = test.buckets[99].far_contact.clone();
+        assert!(test.table.add_node(test.node_info.clone()).0);
+        test.node_info.id = test.buckets[99].close_contact.clone();
         result_of_add = test.table.add_node(test.node_info.clone());
         assert!(result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(4, test.table.size());
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(4, test.table.size());
+        assert!(result_of_add.1.is_none());
+        assert_eq!(::types::GROUP_SIZE + 4, test.table.size());
 
-        test.node_info.id = test.buckets[1].mid_contact.clone();
+        // dropping a contact of bucket 0 promotes the candidate, which can then be added
+        assert_eq!(test.table.drop_node(&test.buckets[0].far_contact),
+                   Some(test.buckets[0].close_contact));
+        assert_eq!(test.table.buckets[0].replacements, vec![test.buckets[0].close_contact]);
+        test.node_info.id = test.buckets[0].close_contact.clone();
         result_of_add = test.table.add_node(test.node_info.clone());
         assert!(result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(5, test.table.size());
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(5, test.table.size());
+        assert!(result_of_add.1.is_none());
+        assert_eq!(::types::GROUP_SIZE + 4, test.table.size());
+    }
 
-        test.node_info.id = test.buckets[1].close_contact.clone();
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(6, test.table.size());
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(6, test.table.size());
+    #[test]
+    fn bucket_overflow_drops_contact_outside_close_group() {
+        let our_id: ::NameType = rand::random();
+        let buckets = initialise_buckets(&our_id);
+        let mut table = super::RoutingTable::new(&our_id, 1usize, 1usize);
+        let mut node_info = create_random_node_info();
+
+        node_info.id = buckets[5].far_contact.clone();
+        assert!(table.add_node(node_info.clone()).0);
+        // a closer contact of the same bucket takes the place of the far contact in our close
+        // group of one, so the far contact is dropped and kept as a candidate
+        node_info.id = buckets[5].mid_contact.clone();
+        let (added, dropped) = table.add_node(node_info.clone());
+        assert!(added);
+        assert_eq!(dropped.map(|node_info| node_info.id()), Some(buckets[5].far_contact));
+        assert_eq!(table.size(), 1);
+        assert!(!table.check_node(&buckets[5].far_contact));
+
+        // the candidate is returned once its bucket has room again
+        assert_eq!(table.drop_node(&buckets[5].mid_contact), Some(buckets[5].far_contact));
+        assert_eq!(table.size(), 0);
+        assert!(table.check_node(&buckets[5].far_contact));
+    }
 
-        // Add remaining contacts
-        for i in 2..(super::RoutingTable::get_optimal_size() - 4) {
-            test.node_info.id = test.buckets[i].mid_contact.clone();
-            result_of_add = test.table.add_node(test.node_info.clone());
-            assert!(result_of_add.0);
-            match result_of_add.1 {
-                Some(_) => panic!("Unexpected"),
-                None => {}
-            };
-            assert_eq!(i + 5, test.table.size());
-            result_of_add = test.table.add_node(test.node_info.clone());
-            assert!(!result_of_add.0);
-            match result_of_add.1 {
-                Some(_) => panic!("Unexpected"),
-                None => {}
-            };
-            assert_eq!(i + 5, test.table.size());
-        }
+    #[test]
+    fn replacement_cache_keeps_most_recent_candidates() {
+        let our_id: ::NameType = rand::random();
+        let buckets = initialise_buckets(&our_id);
+        let mut table = super::RoutingTable::new(&our_id, 1usize, 1usize);
+        let mut node_info = create_random_node_info();
+        node_info.id = buckets[99].mid_contact.clone();
+        assert!(table.add_node(node_info.clone()).0);
+        node_info.id = buckets[0].far_contact.clone();
+        assert!(table.add_node(node_info.clone()).0);
+
+        // only a name for a full bucket which isn't a contact is a candidate
+        assert!(table.add_candidate(&buckets[0].mid_contact));
+        assert!(table.add_candidate(&buckets[0].close_contact));
+        assert!(!table.add_candidate(&buckets[0].far_contact));
+        assert!(!table.add_candidate(&buckets[1].far_contact));
+        assert!(!table.add_candidate(&our_id));
+
+        // the cache holds bucket size candidates, the most recently seen is promoted
+        assert_eq!(table.buckets[0].replacements, vec![buckets[0].close_contact]);
+        assert_eq!(table.drop_node(&buckets[0].far_contact), Some(buckets[0].close_contact));
+        assert_eq!(table.drop_node(&buckets[0].far_contact), None);
+
+        // a candidate stays cached until we try to connect to it
+        assert_eq!(table.buckets[0].replacements, vec![buckets[0].close_contact]);
+        assert!(table.remove_candidate(&buckets[0].close_contact));
+        assert!(!table.remove_candidate(&buckets[0].close_contact));
+        assert!(!table.remove_candidate(&our_id));
+        assert!(table.buckets[0].replacements.is_empty());
+    }
 
-        // Check next 4 closer additions return 'buckets_[0].far_contact', 'buckets_[0].mid_contact',
-        // 'buckets_[1].far_contact', and 'buckets_[1].mid_contact' as dropped (in that order)
-        let mut dropped: Vec<::NameType> = Vec::new();
-        let optimal_size = super::RoutingTable::get_optimal_size();
-        for i in (optimal_size - 4)..optimal_size {
-            test.node_info.id = test.buckets[i].mid_contact.clone();
-            result_of_add = test.table.add_node(test.node_info.clone());
-            assert!(result_of_add.0);
-            match result_of_add.1 {
-                Some(dropped_info) => {
-                    dropped.push(dropped_info.id())
-                }
-                None => panic!("Unexpected"),
-            };
-            assert_eq!(super::RoutingTable::get_optimal_size(), test.table.size());
-            result_of_add = test.table.add_node(test.node_info.clone());
-            assert!(!result_of_add.0);
-            match result_of_add.1 {
-                Some(_) => panic!("Unexpected"),
-                None => {}
-            };
-            assert_eq!(super::RoutingTable::get_optimal_size(), test.table.size());
-        }
-        assert!(test.buckets[0].far_contact == dropped[0]);
-        assert!(test.buckets[0].mid_contact == dropped[1]);
-        assert!(test.buckets[1].far_contact == dropped[2]);
-        assert!(test.buckets[1].mid_contact == dropped[3]);
+    #[test]
+    fn record_round_trip_time_test() {
+        let mut table = super::RoutingTable::new(&rand::random(), ::types::GROUP_SIZE,
+                                                 TABLE_SIZE);
+        let connection = ::test_utils::messages_util::test::random_connection();
+        let round_trip_time = ::time::Duration::milliseconds(42);
+        let mut node_info = create_random_node_info();
+        node_info.endpoints = vec![connection.peer_endpoint()];
+        node_info.connection = Some(connection.clone());
+        let name = node_info.id();
+
+        assert!(table.record_round_trip_time(&connection, round_trip_time).is_none());
+        assert!(table.add_node(node_info).0);
+        assert_eq!(table.our_close_group()[0].round_trip_time, None);
+        assert_eq!(table.record_round_trip_time(&connection, round_trip_time), Some(name));
+        assert_eq!(table.our_close_group()[0].round_trip_time, Some(round_trip_time));
+        assert!(table.record_round_trip_time(&connection, round_trip_time * 9).is_some());
+        assert_eq!(table.our_close_group()[0].round_trip_time, Some(round_trip_time * 2));
+        let version = table.version();
+        assert_eq!(table.record_missed_probe(&connection), Some(name));
+        assert!(table.version() > version);
+        assert!(!table.snapshot()[0].reliable);
+        // two answered probes and a missed one, from the reliability of an unprobed contact
+        assert_eq!(table.our_close_group()[0].reliability, 0.5390625f64);
+        let other_connection = ::test_utils::messages_util::test::random_connection();
+        assert!(table.record_round_trip_time(&other_connection, round_trip_time).is_none());
+    }
 
-        // Try to add far contacts again (should fail)
-        for far_contact in dropped {
-            test.node_info.id = far_contact.clone();
-            result_of_add = test.table.add_node(test.node_info.clone());
-            assert!(!result_of_add.0);
-            match result_of_add.1 {
-                Some(_) => panic!("Unexpected"),
-                None => {}
-            };
-            assert_eq!(super::RoutingTable::get_optimal_size(), test.table.size());
+    #[test]
+    fn reliable_endpoints_test() {
+        let mut table = super::RoutingTable::new(&rand::random(), ::types::GROUP_SIZE,
+                                                 TABLE_SIZE);
+        let connection = ::test_utils::messages_util::test::random_connection();
+        let mut node_info = create_random_node_info();
+        node_info.endpoints = vec![connection.peer_endpoint()];
+        node_info.connection = Some(connection.clone());
+        assert!(table.add_node(node_info).0);
+
+        // a contact has to answer a few probes before it counts as reliable
+        let round_trip_time = ::time::Duration::milliseconds(42);
+        for _ in 0..5 {
+            assert!(table.reliable_endpoints().is_empty());
+            let _ = table.record_round_trip_time(&connection, round_trip_time);
+        }
+        let _ = table.record_round_trip_time(&connection, round_trip_time);
+        assert_eq!(table.reliable_endpoints(), vec![connection.peer_endpoint()]);
+
+        // a missed probe makes it unreliable until it answers again for a while
+        let _ = table.record_missed_probe(&connection);
+        assert!(table.reliable_endpoints().is_empty());
+        for _ in 0..5 {
+            let _ = table.record_round_trip_time(&connection, round_trip_time);
+        }
+        assert_eq!(table.reliable_endpoints(), vec![connection.peer_endpoint()]);
+    }
+
+    #[test]
+    fn target_nodes_prefer_reliable_and_fast_contacts() {
+        let our_id: ::NameType = rand::random();
+        let mut table = super::RoutingTable::new(&our_id, 1usize, TABLE_SIZE);
+        let mut target = our_id.0;
+        target[0] ^= 0x80;
+        let target = ::NameType::new(target);
+        // contacts sharing exactly eight leading bits with the target, closest to it first
+        let contacts = (0..5u8).map(|i| {
+            let mut name = target.0;
+            name[1] ^= 0x80 | i;
+            ::NameType::new(name)
+        }).collect::<Vec<::NameType>>();
+
+        let mut close_contact = our_id.0;
+        close_contact[63] ^= 1;
+        let mut node_info = create_random_node_info();
+        node_info.id = ::NameType::new(close_contact);
+        assert!(table.add_node(node_info).0);
+        for (i, name) in contacts.iter().enumerate() {
+            let mut node_info = create_random_node_info();
+            node_info.id = name.clone();
+            if i == 0 {
+                node_info.record_missed_probe();
+            }
+            if i == 3 {
+                node_info.record_answered_probe(::time::Duration::milliseconds(10));
+            }
+            assert!(table.add_node(node_info).0);
         }
 
-        // Add final close contact to push size of table_ above OptimalSize()
-        test.node_info.id =
-            test.buckets[super::RoutingTable::get_optimal_size()].mid_contact.clone();
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(super::RoutingTable::get_optimal_size() + 1, test.table.size());
-        result_of_add = test.table.add_node(test.node_info.clone());
-        assert!(!result_of_add.0);
-        match result_of_add.1 {
-            Some(_) => panic!("Unexpected"),
-            None => {}
-        };
-        assert_eq!(super::RoutingTable::get_optimal_size() + 1, test.table.size());
+        let target_nodes = table.target_nodes(&target).iter()
+            .map(|node_info| node_info.id())
+            .collect::<Vec<::NameType>>();
+        assert_eq!(target_nodes, vec![contacts[3].clone(), contacts[1].clone(),
+                                      contacts[2].clone(), contacts[4].clone()]);
+    }
+
+    #[test]
+    fn unprobed_contacts_rank_below_reliable_ones() {
+        let round_trip_time = ::time::Duration::milliseconds(100);
+        let unprobed = create_random_node_info();
+        let mut well_measured = create_random_node_info();
+        for _ in 0..999 {
+            well_measured.record_answered_probe(round_trip_time);
+        }
+        well_measured.record_missed_probe();
+        for _ in 0..10 {
+            well_measured.record_answered_probe(round_trip_time);
+        }
+        assert!(well_measured.is_reliable());
+        assert!(!unprobed.is_reliable());
+        assert_eq!(well_measured.compare_quality(&unprobed), ::std::cmp::Ordering::Less);
+
+        // among reliable contacts the faster one goes first
+        let mut fast = create_random_node_info();
+        for _ in 0..20 {
+            fast.record_answered_probe(::time::Duration::milliseconds(10));
+        }
+        assert_eq!(fast.compare_quality(&well_measured), ::std::cmp::Ordering::Less);
+
+        // old probes are forgotten: a contact that stops answering loses its standing
+        for _ in 0..3 {
+            well_measured.record_missed_probe();
+        }
+        assert!(!well_measured.is_reliable());
+        assert!(well_measured.reliability < unprobed.reliability);
+        assert_eq!(unprobed.compare_quality(&well_measured), ::std::cmp::Ordering::Less);
     }
 
     #[test]
@@ -958,123 +1159,66 @@ mod test {
         test.complete_filling_table();
 
         // Try with invalid Address
-        test.table.drop_node(&::NameType::new([0u8;64]));
-        assert_eq!(super::RoutingTable::get_optimal_size(), test.table.size());
+        assert!(test.table.drop_node(&::NameType::new([0u8;64])).is_none());
+        assert_eq!(TABLE_SIZE, test.table.size());
 
         // Try with our ID
         let drop_id = test.table.our_id.clone();
-        test.table.drop_node(&drop_id);
-        assert_eq!(super::RoutingTable::get_optimal_size(), test.table.size());
+        assert!(test.table.drop_node(&drop_id).is_none());
+        assert_eq!(TABLE_SIZE, test.table.size());
 
         // Try with Address of node not in table
-        test.table.drop_node(&test.buckets[0].far_contact);
-        assert_eq!(super::RoutingTable::get_optimal_size(), test.table.size());
+        assert!(test.table.drop_node(&test.buckets[0].far_contact).is_none());
+        assert_eq!(TABLE_SIZE, test.table.size());
 
         // Remove all nodes one at a time
         // TODO(Spandan) Shuffle not implemented
         let mut size = test.table.size();
         for id in test.added_ids {
             size -= 1;
-            test.table.drop_node(&id);
+            let _ = test.table.drop_node(&id);
             assert_eq!(size, test.table.size());
         }
     }
 
     #[test]
     fn check_node_test() {
-        let mut routing_table_utest = RoutingTableUnitTest::new();
+        let mut test = RoutingTableUnitTest::new();
+        test.table = super::RoutingTable::new(&test.our_id, ::types::GROUP_SIZE, 2usize);
 
         // Try with our ID
-        assert_eq!(routing_table_utest.table.check_node(&routing_table_utest.table.our_id), false);
+        assert_eq!(test.table.check_node(&test.our_id), false);
 
         // Should return true for empty routing table
-        assert!(routing_table_utest.table.check_node(&routing_table_utest.buckets[0].far_contact));
-
-        // Add the first contact, and check it doesn't allow duplicates
-        let mut new_node_0 = create_random_node_info();
-        new_node_0.id = routing_table_utest.buckets[0].far_contact.clone();
-        assert!(routing_table_utest.table.add_node(new_node_0).0);
-        assert_eq!(
-          routing_table_utest.table.check_node(&routing_table_utest.buckets[0].far_contact.clone()),
-          false);
-
-        // Add further 'OptimalSize()' - 1 contact (should all succeed with no removals).  Set this
-        // up so that bucket 0 (furthest) and bucket 1 have 3 contacts each and all others have 0 or
-        // 1 contacts.
-
-        let mut new_node_1 = create_random_node_info();
-        new_node_1.id =  routing_table_utest.buckets[0].mid_contact.clone();
-        assert!(routing_table_utest.table.check_node(&new_node_1.id()));
-        assert!(routing_table_utest.table.add_node(new_node_1).0);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[0].mid_contact.clone()), false);
-
-        let mut new_node_2 = create_random_node_info();
-        new_node_2.id =  routing_table_utest.buckets[0].close_contact.clone();
-        assert!(routing_table_utest.table.check_node(&new_node_2.id()));
-        assert!(routing_table_utest.table.add_node(new_node_2).0);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[0].close_contact.clone()), false);
-
-        let mut new_node_3 = create_random_node_info();
-        new_node_3.id = routing_table_utest.buckets[1].far_contact.clone();
-        assert!(routing_table_utest.table.check_node(&new_node_3.id()));
-        assert!(routing_table_utest.table.add_node(new_node_3).0);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[1].far_contact.clone()), false);
-
-        let mut new_node_4 = create_random_node_info();
-        new_node_4.id =  routing_table_utest.buckets[1].mid_contact.clone();
-        assert!(routing_table_utest.table.check_node(&new_node_4.id()));
-        assert!(routing_table_utest.table.add_node(new_node_4).0);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[1].mid_contact.clone()), false);
-
-        let mut new_node_5 = create_random_node_info();
-        new_node_5.id =  routing_table_utest.buckets[1].close_contact.clone();
-        assert!(routing_table_utest.table.check_node(&new_node_5.id()));
-        assert!(routing_table_utest.table.add_node(new_node_5).0);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[1].close_contact.clone()), false);
-
-        for i in 2..(super::RoutingTable::get_optimal_size() - 4) {
-            let mut new_node = create_random_node_info();
-            new_node.id =  routing_table_utest.buckets[i].mid_contact.clone();
-            assert!(routing_table_utest.table.check_node(&new_node.id()));
-            assert!(routing_table_utest.table.add_node(new_node).0);
-            assert_eq!(routing_table_utest.table.check_node(
-              &routing_table_utest.buckets[i].mid_contact.clone()), false);
-        }
-
-        assert_eq!(super::RoutingTable::get_optimal_size(),
-                   routing_table_utest.table.routing_table.len());
-
-        let optimal_size = super::RoutingTable::get_optimal_size();
-        for i in (optimal_size - 4)..optimal_size {
-            let mut new_node = create_random_node_info();
-            new_node.id =  routing_table_utest.buckets[i].mid_contact.clone();
-            assert!(routing_table_utest.table.check_node(&new_node.id()));
-            assert!(routing_table_utest.table.add_node(new_node).0);
-            assert_eq!(routing_table_utest.table.check_node(
-              &routing_table_utest.buckets[i].mid_contact.clone()), false);
-            assert_eq!(super::RoutingTable::get_optimal_size(),
-              routing_table_utest.table.routing_table.len());
-        }
-
-      // Check for contacts again which are now not in the table
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[0].far_contact.clone()), false);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[0].mid_contact.clone()), false);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[1].far_contact.clone()), false);
-        assert_eq!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[1].mid_contact.clone()), false);
-
-      // Check final close contact which would push size of table_ above OptimalSize()
-        assert!(routing_table_utest.table.check_node(
-          &routing_table_utest.buckets[super::RoutingTable::get_optimal_size()]
-                              .mid_contact.clone()));
+        assert!(test.table.check_node(&test.buckets[0].far_contact));
+
+        // fill our close group from the closest buckets, checking each contact before adding it
+        // and that it isn't accepted twice
+        for i in 0..::types::GROUP_SIZE {
+            test.node_info.id = test.buckets[99 - i].mid_contact.clone();
+            assert!(test.table.check_node(&test.node_info.id()));
+            assert!(test.table.add_node(test.node_info.clone()).0);
+            assert_eq!(test.table.check_node(&test.node_info.id()), false);
+        }
+
+        // bucket 0 (furthest) is checked up to the bucket size
+        for contact in vec![test.buckets[0].far_contact, test.buckets[0].mid_contact] {
+            test.node_info.id = contact;
+            assert!(test.table.check_node(&contact));
+            assert!(test.table.add_node(test.node_info.clone()).0);
+            assert_eq!(test.table.check_node(&contact), false);
+        }
+        assert_eq!(test.table.check_node(&test.buckets[0].close_contact), false);
+
+        // a full bucket still accepts a contact closer than our close group
+        test.node_info.id = test.buckets[99].far_contact.clone();
+        assert!(test.table.check_node(&test.node_info.id()));
+        assert!(test.table.add_node(test.node_info.clone()).0);
+        assert!(test.table.check_node(&test.buckets[99].close_contact));
+
+        // dropping a contact of bucket 0 makes room in that bucket again
+        let _ = test.table.drop_node(&test.buckets[0].far_contact);
+        assert!(test.table.check_node(&test.buckets[0].close_contact));
     }
 
     #[test]
@@ -1104,15 +1248,15 @@ mod test {
 
         for i in 0..tables.len() {
             for j in 0..drop_vec.len() {
-                tables[i].drop_node(&drop_vec[j]);
+                let _ = tables[i].drop_node(&drop_vec[j]);
             }
         }
         // remove IDs too
         addresses.truncate(nodes_to_remove);
 
         for i in 0..tables.len() {
-            let size = if super::RoutingTable::get_group_size() < tables[i].size() {
-                super::RoutingTable::get_group_size()
+            let size = if ::types::GROUP_SIZE < tables[i].size() {
+                ::types::GROUP_SIZE
             } else {
                 tables[i].size()
             };
@@ -1152,9 +1296,9 @@ mod test {
                     ::std::cmp::Ordering::Greater
                 });
             // if target is in close group return the whole close group excluding target
-            for j in 1..(super::RoutingTable::get_group_size() - ::types::QUORUM_SIZE) {
+            for j in 1..(::types::GROUP_SIZE - ::types::QUORUM_SIZE) {
                 let target_close_group = tables[i].target_nodes(&addresses[j]);
-                assert_eq!(super::RoutingTable::get_group_size(), target_close_group.len());
+                assert_eq!(::types::GROUP_SIZE, target_close_group.len());
                 // should contain our close group
                 for k in 0..target_close_group.len() {
                     assert!(target_close_group[k].id() == addresses[k + 1]);
@@ -1168,29 +1312,23 @@ mod test {
         // independent double verification of our_close_group()
         // this test verifies that the close group is returned sorted
         let our_id_name = ::id::Id::new().name();
-        let mut routing_table = super::RoutingTable::new(&our_id_name);
+        let mut routing_table = super::RoutingTable::new(&our_id_name, ::types::GROUP_SIZE,
+                                                         ::types::BUCKET_SIZE);
 
-        let mut count: usize = 0;
-        loop {
+        for _ in 0..2 * TABLE_SIZE {
             let _ = routing_table.add_node(super::NodeInfo::new(
                 ::public_id::PublicId::new(&::id::Id::new()), random_endpoints(), None));
-            count += 1;
-            if routing_table.size() >= super::RoutingTable::get_optimal_size() {
-                break;
-            }
-            if count >= 2 * super::RoutingTable::get_optimal_size() {
-                panic!("Routing table does not fill up.");
-            }
         }
+        assert!(routing_table.size() > ::types::GROUP_SIZE);
         let our_close_group: Vec<super::NodeInfo> = routing_table.our_close_group();
-        assert_eq!(our_close_group.len(), super::RoutingTable::get_group_size() );
+        assert_eq!(our_close_group.len(), ::types::GROUP_SIZE );
         let mut closer_name: ::NameType = our_id_name.clone();
         for close_node in &our_close_group {
             assert!(::name_type::closer_to_target(&closer_name, &close_node.id(), &our_id_name));
             assert!(routing_table.address_in_our_close_group_range(&close_node.id()));
             closer_name = close_node.id().clone();
         }
-        for node in &routing_table.routing_table {
+        for node in routing_table.closest_contacts() {
             if our_close_group.iter().filter(|close_node| close_node.id() == node.id())
                               .count() > 0 {
                 assert!(routing_table.address_in_our_close_group_range(&node.id()));
@@ -1200,6 +1338,58 @@ mod test {
         }
     }
 
+    #[test]
+    fn configured_group_size() {
+        let group_size = 3usize;
+        let our_id_name = ::id::Id::new().name();
+        let mut routing_table = super::RoutingTable::new(&our_id_name, group_size,
+                                                         ::types::BUCKET_SIZE);
+        assert_eq!(routing_table.get_group_size(), group_size);
+        assert_eq!(routing_table.get_bucket_size(), ::types::BUCKET_SIZE);
+
+        for _ in 0..10 {
+            let _ = routing_table.add_node(super::NodeInfo::new(
+                ::public_id::PublicId::new(&::id::Id::new()), random_endpoints(), None));
+        }
+        let our_close_group = routing_table.our_close_group();
+        assert_eq!(our_close_group.len(), group_size);
+        assert!(routing_table.address_in_our_close_group_range(
+            &our_close_group[group_size - 1].id()));
+        assert!(!routing_table.address_in_our_close_group_range(
+            &routing_table.closest_contacts().nth(group_size).unwrap().id()));
+        assert_eq!(routing_table.target_nodes(&our_close_group[0].id()).len(), group_size);
+    }
+
+    #[test]
+    fn group_range_of_claimants() {
+        let group_size = 3usize;
+        let our_id_name = ::id::Id::new().name();
+        let mut routing_table = super::RoutingTable::new(&our_id_name, group_size,
+                                                         ::types::BUCKET_SIZE);
+        for _ in 0..10 {
+            let _ = routing_table.add_node(super::NodeInfo::new(
+                ::public_id::PublicId::new(&::id::Id::new()), random_endpoints(), None));
+        }
+        let target: ::NameType = rand::random();
+        let mut known_names = routing_table.closest_contacts().into_iter()
+            .map(|node_info| node_info.id()).collect::<Vec<::NameType>>();
+        known_names.push(our_id_name);
+        known_names.sort_by(|a, b| if ::name_type::closer_to_target(a, b, &target) {
+                ::std::cmp::Ordering::Less
+            } else {
+                ::std::cmp::Ordering::Greater
+            });
+
+        for name in known_names.iter().take(group_size) {
+            assert!(routing_table.is_in_group_range(&target, name));
+        }
+        for name in known_names.iter().skip(group_size) {
+            assert!(!routing_table.is_in_group_range(&target, name));
+        }
+        // the target itself is closest, even if we do not know it
+        assert!(routing_table.is_in_group_range(&target, &target));
+    }
+
     #[test]
     fn our_close_group_test() {
         let mut table_unit_test = RoutingTableUnitTest::new();
@@ -1214,7 +1404,7 @@ mod test {
         }
 
         table_unit_test.complete_filling_table();
-        assert_eq!(super::RoutingTable::get_group_size(),
+        assert_eq!(::types::GROUP_SIZE,
                    table_unit_test.table.our_close_group().len());
 
         for close_node in table_unit_test.table.our_close_group().iter() {
@@ -1251,17 +1441,17 @@ mod test {
             assert!(assert_checker == 1);
         }
 
-        // Complete filling the table up to RoutingTable::get_optimal_size() contacts
+        // Complete filling the table up to TABLE_SIZE contacts
         routing_table_utest.complete_filling_table();
 
         // Try with our ID (should return closest to us, i.e. buckets 63 to 32)
         target_nodes_ =
             routing_table_utest.table.target_nodes(&routing_table_utest.table.our_id);
-        assert_eq!(super::RoutingTable::get_group_size(), target_nodes_.len());
+        assert_eq!(::types::GROUP_SIZE, target_nodes_.len());
 
-        for i in ((super::RoutingTable::get_optimal_size() -
-                   super::RoutingTable::get_group_size())..
-                   super::RoutingTable::get_optimal_size() - 1).rev() {
+        for i in ((TABLE_SIZE -
+                   ::types::GROUP_SIZE)..
+                   TABLE_SIZE - 1).rev() {
             let mut assert_checker = 0;
             for j in 0..target_nodes_.len() {
                 if target_nodes_[j].id() == routing_table_utest.buckets[i].mid_contact {
@@ -1276,8 +1466,8 @@ mod test {
         // return 'RoutingTable::Parallelism()' contacts closest to target)
         let mut target: ::NameType;
         for count in 0..2 {
-            for i in 0..(super::RoutingTable::get_optimal_size() -
-                         super::RoutingTable::get_group_size()) {
+            for i in 0..(TABLE_SIZE -
+                         ::types::GROUP_SIZE) {
                 target = if count == 0 {
                     routing_table_utest.buckets[i].far_contact.clone()
                 } else {
@@ -1309,16 +1499,16 @@ mod test {
         // Try with nodes close to us, first time *not* in table and second time *in* table (should
         // return GroupSize closest to target)
         for count in 0..2 {
-            for i in (super::RoutingTable::get_optimal_size() -
-                      super::RoutingTable::get_group_size())..
-                      super::RoutingTable::get_optimal_size() {
+            for i in (TABLE_SIZE -
+                      ::types::GROUP_SIZE)..
+                      TABLE_SIZE {
                 target = if count == 0 {
                     routing_table_utest.buckets[i].far_contact.clone()
                 } else {
                     routing_table_utest.buckets[i].mid_contact.clone()
                 };
                 target_nodes_ = routing_table_utest.table.target_nodes(&target);
-                assert_eq!(super::RoutingTable::get_group_size(), target_nodes_.len());
+                assert_eq!(::types::GROUP_SIZE, target_nodes_.len());
                 routing_table_utest.table.our_close_group().sort_by(
                     |a, b| if ::name_type::closer_to_target(
                             &a.id(), &b.id(), &routing_table_utest.our_id) {
@@ -1349,7 +1539,7 @@ mod test {
             None => {}
         }
         assert!(table_unit_test.our_id == table_unit_test.table.our_id);
-        assert_eq!(0, table_unit_test.table.routing_table.len());
+        assert_eq!(0, table_unit_test.table.size());
 
         // Check on partially filled the table
         table_unit_test.partially_fill_table();
@@ -1369,12 +1559,12 @@ mod test {
             None => {}
         }
         assert!(table_unit_test.our_id == table_unit_test.table.our_id);
-        assert_eq!(table_unit_test.initial_count + 1, table_unit_test.table.routing_table.len());
+        assert_eq!(table_unit_test.initial_count + 1, table_unit_test.table.size());
 
         // Check on fully filled the table
-        table_unit_test.table.drop_node(&test_node.id().clone());
+        let _ = table_unit_test.table.drop_node(&test_node.id().clone());
         table_unit_test.complete_filling_table();
-        table_unit_test.table.drop_node(&table_unit_test.buckets[0].mid_contact.clone());
+        let _ = table_unit_test.table.drop_node(&table_unit_test.buckets[0].mid_contact.clone());
         table_unit_test.node_info = test_node.clone();
         assert!(table_unit_test.table.add_node(table_unit_test.node_info.clone()).0);
 
@@ -1390,7 +1580,7 @@ mod test {
         // EXPECT_TRUE(asymm::MatchingKeys(info_.dht_public_id.public_key(),
         //                                 *table_.GetPublicKey(info_.id())));
         assert!(table_unit_test.our_id == table_unit_test.table.our_id);
-        assert_eq!(super::RoutingTable::get_optimal_size(),
-                   table_unit_test.table.routing_table.len());
+        assert_eq!(TABLE_SIZE,
+                   table_unit_test.table.size());
     }
 }
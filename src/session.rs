@@ -0,0 +1,327 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Sessions encrypt and authenticate all bytes on a connection once the Hello handshake has
+//! exchanged ephemeral keys.  Each side puts a fresh box_ public key in its signed Hello; once we
+//! have sent ours and received the peer's, both sides derive the same precomputed key.  Every
+//! further frame carries a counter, which is used for its nonce and must strictly increase, so
+//! frames can neither be read nor replayed by an on-path observer.  Only the Hello itself is sent
+//! unsealed; anything sent before the session is established is queued until it is.
+
+use std::collections::HashMap;
+
+use crust::Connection;
+use error::RoutingError;
+use sodiumoxide::crypto::box_;
+
+/// The most frames kept for a connection while its handshake is in progress.
+pub const MAX_QUEUED_FRAMES: usize = 256;
+
+/// The frame in which the bytes of an established session are sent.
+#[derive(PartialEq, Eq, Clone, Debug, RustcEncodable, RustcDecodable)]
+struct SealedFrame {
+    counter: u64,
+    cipher_text: Vec<u8>,
+}
+
+struct Session {
+    our_keys: Option<(box_::PublicKey, box_::SecretKey)>,
+    their_key: Option<box_::PublicKey>,
+    shared_key: Option<box_::PrecomputedKey>,
+    sent: u64,
+    received: u64,
+    queued: Vec<Vec<u8>>,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session {
+            our_keys: None,
+            their_key: None,
+            shared_key: None,
+            sent: 0,
+            received: 0,
+            queued: Vec::new(),
+        }
+    }
+
+    fn establish(&mut self) {
+        if let (&Some((_, ref our_secret_key)), &Some(ref their_key)) =
+                (&self.our_keys, &self.their_key) {
+            self.shared_key = Some(box_::precompute(their_key, our_secret_key));
+        }
+    }
+
+    /// Both directions share the precomputed key, so the side with the lower ephemeral key
+    /// marks the nonces it sends with 0 and the other side with 1.
+    fn nonce(&self, counter: u64, sending: bool) -> Option<box_::Nonce> {
+        let lower = match (&self.our_keys, &self.their_key) {
+            (&Some((ref our_key, _)), &Some(ref their_key)) => our_key < their_key,
+            _ => return None,
+        };
+        let mut nonce = [0u8; box_::NONCEBYTES];
+        nonce[0] = if lower == sending { 0 } else { 1 };
+        for i in 0..8 {
+            nonce[box_::NONCEBYTES - 1 - i] = (counter >> (8 * i)) as u8;
+        }
+        Some(box_::Nonce(nonce))
+    }
+
+    fn seal(&mut self, bytes: &[u8]) -> Result<Vec<u8>, RoutingError> {
+        let counter = self.sent + 1;
+        let cipher_text = match (&self.shared_key, self.nonce(counter, true)) {
+            (&Some(ref shared_key), Some(nonce)) =>
+                box_::seal_precomputed(bytes, &nonce, shared_key),
+            _ => return Err(RoutingError::NoSession),
+        };
+        self.sent = counter;
+        Ok(try!(::utils::encode(&SealedFrame { counter: counter, cipher_text: cipher_text })))
+    }
+}
+
+/// Sessions keeps the session state of every connection.
+pub struct Sessions {
+    sessions: HashMap<Connection, Session>,
+}
+
+impl Sessions {
+    /// Construct without any sessions.
+    pub fn new() -> Sessions {
+        Sessions { sessions: HashMap::new() }
+    }
+
+    /// Return our ephemeral key for the connection, generating it if needed.  The returned flag
+    /// is true if the key is new, in which case the Hello announcing it has to be sent as is,
+    /// since the peer can't have derived the session key yet.
+    pub fn ephemeral_key(&mut self, connection: &Connection) -> (box_::PublicKey, bool) {
+        let session = self.sessions.entry(connection.clone()).or_insert_with(Session::new);
+        if let Some((ref public_key, _)) = session.our_keys {
+            return (public_key.clone(), false);
+        }
+        let (public_key, secret_key) = box_::gen_keypair();
+        session.our_keys = Some((public_key.clone(), secret_key));
+        session.establish();
+        (public_key, true)
+    }
+
+    /// Record the ephemeral key the peer sent in its Hello.  Fails if the peer already announced
+    /// a different key on this connection.
+    pub fn set_peer_key(&mut self, connection: &Connection, key: box_::PublicKey)
+            -> Result<(), RoutingError> {
+        let session = self.sessions.entry(connection.clone()).or_insert_with(Session::new);
+        match session.their_key {
+            Some(ref their_key) if *their_key == key => return Ok(()),
+            Some(_) => return Err(RoutingError::SessionKeyChanged),
+            None => {},
+        };
+        session.their_key = Some(key);
+        session.establish();
+        Ok(())
+    }
+
+    /// Returns true once both sides of the connection exchanged their ephemeral keys.
+    pub fn is_established(&self, connection: &Connection) -> bool {
+        self.sessions.get(connection).map(|session| session.shared_key.is_some()).unwrap_or(false)
+    }
+
+    /// Encrypt the bytes for the connection.  Before the session is established the bytes are
+    /// queued and None is returned; `take_queued` hands them out once the handshake completes.
+    pub fn seal(&mut self, connection: &Connection, bytes: Vec<u8>)
+            -> Result<Option<Vec<u8>>, RoutingError> {
+        let session = self.sessions.entry(connection.clone()).or_insert_with(Session::new);
+        if session.shared_key.is_none() {
+            if session.queued.len() >= MAX_QUEUED_FRAMES {
+                return Err(RoutingError::NoSession);
+            };
+            session.queued.push(bytes);
+            return Ok(None);
+        };
+        session.seal(&bytes).map(Some)
+    }
+
+    /// Seal the bytes queued for the connection while its handshake was in progress.  Nothing is
+    /// returned until the session is established.
+    pub fn take_queued(&mut self, connection: &Connection) -> Result<Vec<Vec<u8>>, RoutingError> {
+        let session = match self.sessions.get_mut(connection) {
+            Some(session) => session,
+            None => return Ok(vec![]),
+        };
+        if session.shared_key.is_none() {
+            return Ok(vec![]);
+        };
+        let queued = ::std::mem::replace(&mut session.queued, Vec::new());
+        let mut frames = Vec::with_capacity(queued.len());
+        for bytes in queued {
+            frames.push(try!(session.seal(&bytes)));
+        }
+        Ok(frames)
+    }
+
+    /// Decrypt the bytes received on the connection.  Fails with NoSession before the session is
+    /// established, with ReplayedFrame for a frame seen before and with FailedDecryption for
+    /// anything else that isn't an authentic frame.
+    pub fn open(&mut self, connection: &Connection, bytes: &Vec<u8>)
+            -> Result<Vec<u8>, RoutingError> {
+        let session = match self.sessions.get_mut(connection) {
+            Some(session) => session,
+            None => return Err(RoutingError::NoSession),
+        };
+        if session.shared_key.is_none() {
+            return Err(RoutingError::NoSession);
+        };
+        let frame = try!(::utils::decode::<SealedFrame>(bytes));
+        if frame.counter <= session.received {
+            return Err(RoutingError::ReplayedFrame);
+        };
+        let opened = match (&session.shared_key, session.nonce(frame.counter, false)) {
+            (&Some(ref shared_key), Some(nonce)) =>
+                box_::open_precomputed(&frame.cipher_text, &nonce, shared_key),
+            _ => return Err(RoutingError::NoSession),
+        };
+        let plain_text = match opened {
+            Ok(plain_text) => plain_text,
+            Err(()) => return Err(RoutingError::FailedDecryption),
+        };
+        session.received = frame.counter;
+        Ok(plain_text)
+    }
+
+    /// Drop the session of a connection.
+    pub fn remove(&mut self, connection: &Connection) {
+        let _ = self.sessions.remove(connection);
+    }
+
+    /// Drop all sessions.
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_utils::test::random_connection;
+
+    // Run the handshake between two sides of the same connection, both sending a Hello.
+    fn handshake() -> (super::Sessions, super::Sessions, ::crust::Connection) {
+        let connection = random_connection();
+        let mut a = super::Sessions::new();
+        let mut b = super::Sessions::new();
+        let (a_key, a_new) = a.ephemeral_key(&connection);
+        assert!(a_new);
+        assert!(!a.is_established(&connection));
+        assert!(b.set_peer_key(&connection, a_key).is_ok());
+        let (b_key, b_new) = b.ephemeral_key(&connection);
+        assert!(b_new);
+        assert!(b.is_established(&connection));
+        assert!(a.set_peer_key(&connection, b_key).is_ok());
+        assert!(a.is_established(&connection));
+        (a, b, connection)
+    }
+
+    #[test]
+    fn nothing_passes_before_established() {
+        let connection = random_connection();
+        let mut sessions = super::Sessions::new();
+        let _ = sessions.ephemeral_key(&connection);
+        let bytes = vec![1u8, 2, 3];
+        assert_eq!(sessions.seal(&connection, bytes.clone()).unwrap(), None);
+        assert!(sessions.take_queued(&connection).unwrap().is_empty());
+        match sessions.open(&connection, &bytes) {
+            Err(::error::RoutingError::NoSession) => {},
+            _ => panic!("Unsealed bytes should be refused."),
+        };
+        match sessions.open(&random_connection(), &bytes) {
+            Err(::error::RoutingError::NoSession) => {},
+            _ => panic!("Bytes on a connection without session should be refused."),
+        };
+    }
+
+    #[test]
+    fn queued_until_established() {
+        let connection = random_connection();
+        let mut a = super::Sessions::new();
+        let mut b = super::Sessions::new();
+        assert_eq!(a.seal(&connection, vec![1u8]).unwrap(), None);
+        assert_eq!(a.seal(&connection, vec![2u8]).unwrap(), None);
+        let (a_key, _) = a.ephemeral_key(&connection);
+        assert!(b.set_peer_key(&connection, a_key).is_ok());
+        let (b_key, _) = b.ephemeral_key(&connection);
+        assert!(a.take_queued(&connection).unwrap().is_empty());
+        assert!(a.set_peer_key(&connection, b_key).is_ok());
+
+        let frames = a.take_queued(&connection).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(b.open(&connection, &frames[0]).unwrap(), vec![1u8]);
+        assert_eq!(b.open(&connection, &frames[1]).unwrap(), vec![2u8]);
+        assert!(a.take_queued(&connection).unwrap().is_empty());
+
+        // the queue is bounded
+        let connection = random_connection();
+        for _ in 0..super::MAX_QUEUED_FRAMES {
+            assert_eq!(a.seal(&connection, vec![0u8]).unwrap(), None);
+        }
+        assert!(a.seal(&connection, vec![0u8]).is_err());
+    }
+
+    #[test]
+    fn seal_and_open_both_ways() {
+        let (mut a, mut b, connection) = handshake();
+        let bytes = vec![1u8, 2, 3];
+        let sealed = a.seal(&connection, bytes.clone()).unwrap().unwrap();
+        assert!(sealed != bytes);
+        assert_eq!(b.open(&connection, &sealed).unwrap(), bytes);
+        let sealed = b.seal(&connection, bytes.clone()).unwrap().unwrap();
+        assert_eq!(a.open(&connection, &sealed).unwrap(), bytes);
+        // plain bytes are refused once established
+        assert!(a.open(&connection, &bytes).is_err());
+    }
+
+    #[test]
+    fn replay_and_tampering_fail() {
+        let (mut a, mut b, connection) = handshake();
+        let first = a.seal(&connection, vec![1u8]).unwrap().unwrap();
+        let second = a.seal(&connection, vec![2u8]).unwrap().unwrap();
+        assert!(b.open(&connection, &second).is_ok());
+        match b.open(&connection, &first) {
+            Err(::error::RoutingError::ReplayedFrame) => {},
+            _ => panic!("Older frame should be refused as replayed."),
+        };
+        match b.open(&connection, &second) {
+            Err(::error::RoutingError::ReplayedFrame) => {},
+            _ => panic!("Repeated frame should be refused as replayed."),
+        };
+
+        let mut tampered = a.seal(&connection, vec![3u8]).unwrap().unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        match b.open(&connection, &tampered) {
+            Err(::error::RoutingError::FailedDecryption) => {},
+            _ => panic!("Tampered frame should fail to open."),
+        };
+    }
+
+    #[test]
+    fn peer_key_is_fixed() {
+        let (mut a, _, connection) = handshake();
+        let (other_key, _) = ::sodiumoxide::crypto::box_::gen_keypair();
+        match a.set_peer_key(&connection, other_key) {
+            Err(::error::RoutingError::SessionKeyChanged) => {},
+            _ => panic!("Changing the session key should fail."),
+        };
+    }
+}
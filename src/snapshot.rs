@@ -0,0 +1,65 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use crust::{Connection, Endpoint};
+
+use NameType;
+use routing_core::State;
+
+/// A contact of the routing table as it was when the snapshot was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContactSnapshot {
+    /// The network name of the contact.
+    pub name: NameType,
+    /// The endpoints the contact can be reached on.
+    pub endpoints: Vec<Endpoint>,
+    /// The connection to the contact, None while it is not connected yet.
+    pub connection: Option<Connection>,
+    /// The bucket of the contact, i.e. the number of leading bits its name shares with ours.
+    pub bucket_index: usize,
+    /// Whether the contact is a member of our close group.
+    pub in_close_group: bool,
+    /// The smoothed round trip time of the liveness probes the contact answered.
+    pub round_trip_time: Option<::time::Duration>,
+    /// Whether the contact has reliably answered its recent liveness probes.
+    pub reliable: bool,
+}
+
+impl ContactSnapshot {
+    /// Returns true if we hold a connection to the contact.
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+}
+
+/// A read-only copy of the routing table of a node, with the name and state of the node itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoutingTableSnapshot {
+    /// Our own name.
+    pub our_name: NameType,
+    /// The state of our node.
+    pub state: State,
+    /// The contacts of the routing table, closest to our name first.
+    pub contacts: Vec<ContactSnapshot>,
+}
+
+impl RoutingTableSnapshot {
+    /// The contacts in our close group, closest to our name first.
+    pub fn close_group(&self) -> Vec<&ContactSnapshot> {
+        self.contacts.iter().filter(|contact| contact.in_close_group).collect()
+    }
+}
@@ -20,8 +20,7 @@ extern crate time;
 
 /// Network Client.
 pub struct Client {
-    routing_client: ::routing_client::RoutingClient,
-    receiver: ::std::sync::mpsc::Receiver<::event::Event>,
+    blocking_client: ::blocking_client::BlockingClient,
     id: ::id::Id,
 }
 
@@ -34,59 +33,66 @@ impl Client {
         let encrypt_keys = ::sodiumoxide::crypto::box_::gen_keypair();
         let id = ::id::Id::with_keys(sign_keys.clone(), encrypt_keys.clone());
         let public_id = ::public_id::PublicId::new(&id);
-        let routing_client = ::routing_client::RoutingClient::new(sender, Some(id));
+        let routing_client = ::routing_client::RoutingClient::new(sender, Some(id),
+            ::types::RoutingConfig::default_config());
 
         debug!("Client name {:?}", public_id.clone());
 
-        Client {
-            routing_client: routing_client,
-            receiver: receiver,
-            id: ::id::Id::with_keys(sign_keys, encrypt_keys),
-        }
+        Client::with_routing_client(routing_client, receiver,
+                                    ::id::Id::with_keys(sign_keys, encrypt_keys))
+    }
+
+    /// Construct new Client on an in-process mock network.
+    pub fn with_network(network: &::test_utils::mock_network::Network) -> Client {
+        let (sender, receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let sign_keys = ::sodiumoxide::crypto::sign::gen_keypair();
+        let encrypt_keys = ::sodiumoxide::crypto::box_::gen_keypair();
+        let id = ::id::Id::with_keys(sign_keys.clone(), encrypt_keys.clone());
+        let (transport, transport_receiver) = network.new_client_transport();
+        let routing_client = ::routing_client::RoutingClient::with_transport(sender, Some(id),
+            ::types::RoutingConfig::default_config(), transport, transport_receiver);
+
+        Client::with_routing_client(routing_client, receiver,
+                                    ::id::Id::with_keys(sign_keys, encrypt_keys))
+    }
+
+    fn with_routing_client(routing_client: ::routing_client::RoutingClient,
+                           receiver: ::std::sync::mpsc::Receiver<::event::Event>,
+                           id: ::id::Id) -> Client {
+        let mut blocking_client = ::blocking_client::BlockingClient::new(routing_client,
+                                                                         receiver);
+        blocking_client.set_timeout(::time::Duration::milliseconds(10000));
+        Client { blocking_client: blocking_client, id: id }
+    }
+
+    /// Block until the client has bootstrapped, returns false if it timed out.
+    pub fn wait_for_bootstrap(&mut self) -> bool {
+        self.blocking_client.wait_for_bootstrap()
     }
 
     /// Get data from the network.
     pub fn get(&mut self, request: ::data::DataRequest) -> Option<::data::Data> {
         debug!("Get request from Client for {:?}", request);
-        self.routing_client.get_request(
-            ::authority::Authority::NaeManager(request.name()), request.clone());
-
-        // Block until the data arrives.
-        let timeout = ::time::Duration::milliseconds(10000);
         let time = ::time::SteadyTime::now();
-        loop {
-            while let Ok(event) = self.receiver.try_recv() {
-                debug!("Client received routing event: {:?}", event);
-                match event {
-                    ::event::Event::Response{ response, our_authority: _, from_authority : _} => {
-                        match response {
-                            ::messages::ExternalResponse::Get(data, _, _) => {
-                                debug!("Client received data {:?} for get request.", data);
-                                debug!("Get took {:?} to arrive.",
-                                        ::time::SteadyTime::now() - time);
-                                return Some(data);
-                            },
-                            _ => debug!("Received unexpected external response {:?},", response),
-                        };
-                    },
-                    _ => {},
-                };
-
-                break;
-            }
-
-            if time + timeout < ::time::SteadyTime::now() {
-                debug!("Timed out waiting for data");
-                return None;
-            }
-            ::std::thread::sleep_ms(10);
+        match self.blocking_client.get(
+                ::authority::Authority::NaeManager(request.name()), request.clone()) {
+            Ok(data) => {
+                debug!("Client received data {:?} for get request.", data);
+                debug!("Get took {:?} to arrive.", ::time::SteadyTime::now() - time);
+                Some(data)
+            },
+            Err(error) => {
+                debug!("Get request failed: {:?}", error);
+                None
+            },
         }
     }
 
     /// Put data onto the network.
-    pub fn put(&self, data: ::data::Data) {
+    pub fn put(&mut self, data: ::data::Data) {
         debug!("Put request from Client for {:?}", data);
-        self.routing_client.put_request(::authority::Authority::ClientManager(self.name()), data);
+        let location = ::authority::Authority::ClientManager(self.name());
+        let _ = self.blocking_client.send_put(location, data);
     }
 
     // /// Post data onto the network.
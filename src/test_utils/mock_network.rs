@@ -0,0 +1,349 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! An in-process network for tests.
+//!
+//! Every `MockTransport` handed out by a `Network` gets a distinct loopback endpoint and forwards
+//! its traffic through channels to the other transports of the same network, so any number of
+//! `Routing` and `RoutingClient` instances can run inside a single test process.  Nodes bootstrap
+//! onto the oldest node that is still listening, which lets the first node of a network start the
+//! network without racing the nodes that join after it.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use transport::Transport;
+
+// One side of an open connection.
+struct Link {
+    owner: ::crust::Endpoint,
+    peer: ::crust::Endpoint,
+    peer_connection: ::crust::Connection,
+}
+
+struct NetworkImpl {
+    next_port: u16,
+    // listening endpoints in the order the transports started accepting
+    listeners: Vec<::crust::Endpoint>,
+    senders: HashMap<::crust::Endpoint, Sender<::crust::Event>>,
+    links: HashMap<::crust::Connection, Link>,
+    // while held, events are queued here instead of being delivered
+    held: Option<Vec<(::crust::Endpoint, ::crust::Event)>>,
+}
+
+impl NetworkImpl {
+    fn next_address(&mut self) -> SocketAddr {
+        let port = self.next_port;
+        self.next_port = self.next_port.wrapping_add(1);
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), port))
+    }
+
+    fn send_event(&mut self, endpoint: &::crust::Endpoint, event: ::crust::Event) {
+        match self.held {
+            Some(ref mut held) => return held.push((endpoint.clone(), event)),
+            None => {},
+        };
+        self.deliver(endpoint, event);
+    }
+
+    fn deliver(&self, endpoint: &::crust::Endpoint, event: ::crust::Event) {
+        match self.senders.get(endpoint) {
+            Some(sender) => { let _ = sender.send(event); },
+            None => debug!("Mock network: no transport on {:?} for {:?}", endpoint, event),
+        }
+    }
+
+    // Opens a connection from `from` to the listener `to`, reporting OnAccept to the listener and
+    // OnConnect with the given token to the connecting side.
+    fn connect(&mut self, from: &::crust::Endpoint, to: &::crust::Endpoint, token: u32) -> bool {
+        if from == to || !self.listeners.contains(to) { return false; };
+        let to_address = match *to {
+            ::crust::Endpoint::Tcp(address) => address,
+            _ => return false,
+        };
+        let from_address = self.next_address();
+        let outgoing = ::crust::Connection::new(::crust::Protocol::Tcp, from_address, to_address);
+        let incoming = ::crust::Connection::new(::crust::Protocol::Tcp, to_address, from_address);
+        let _ = self.links.insert(outgoing.clone(),
+            Link { owner: from.clone(), peer: to.clone(), peer_connection: incoming.clone() });
+        let _ = self.links.insert(incoming.clone(),
+            Link { owner: to.clone(), peer: from.clone(), peer_connection: outgoing.clone() });
+        self.send_event(to, ::crust::Event::OnAccept(incoming));
+        self.send_event(from, ::crust::Event::OnConnect(outgoing, token));
+        true
+    }
+
+    // Closes the connection from both sides; only the peer is notified, as with crust.
+    fn disconnect(&mut self, connection: &::crust::Connection) {
+        let link = match self.links.remove(connection) {
+            Some(link) => link,
+            None => return,
+        };
+        let _ = self.links.remove(&link.peer_connection);
+        self.send_event(&link.peer, ::crust::Event::LostConnection(link.peer_connection));
+    }
+}
+
+/// A network of in-process transports.  Cloning a Network yields a handle to the same network.
+#[derive(Clone)]
+pub struct Network {
+    network: Arc<Mutex<NetworkImpl>>,
+}
+
+impl Network {
+    /// Create an empty network.
+    pub fn new() -> Network {
+        Network {
+            network: Arc::new(Mutex::new(NetworkImpl {
+                next_port: 1u16,
+                listeners: Vec::new(),
+                senders: HashMap::new(),
+                links: HashMap::new(),
+                held: None,
+            })),
+        }
+    }
+
+    /// Create a transport for a node on this network, together with the receiver on which the
+    /// transport reports its events.
+    pub fn new_transport(&self) -> (MockTransport, Receiver<::crust::Event>) {
+        self.create_transport(true)
+    }
+
+    /// Create a transport for a client on this network.  A client transport does not accept
+    /// connections, so no node will ever bootstrap onto it.
+    pub fn new_client_transport(&self) -> (MockTransport, Receiver<::crust::Event>) {
+        self.create_transport(false)
+    }
+
+    /// Number of transports currently accepting connections.
+    pub fn listener_count(&self) -> usize {
+        self.network.lock().unwrap().listeners.len()
+    }
+
+    /// Stop delivering events; from now on they are queued until collected with
+    /// `take_held_events` and handed over with `deliver`.
+    pub fn hold_events(&self) {
+        let mut network = self.network.lock().unwrap();
+        if network.held.is_none() {
+            network.held = Some(Vec::new());
+        };
+    }
+
+    /// Collect the events queued since the last call, in the order they were generated.
+    pub fn take_held_events(&self) -> Vec<(::crust::Endpoint, ::crust::Event)> {
+        match self.network.lock().unwrap().held {
+            Some(ref mut held) => ::std::mem::replace(held, Vec::new()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Deliver an event to the transport on the given endpoint, if it is still running.
+    pub fn deliver(&self, endpoint: &::crust::Endpoint, event: ::crust::Event) {
+        self.network.lock().unwrap().deliver(endpoint, event);
+    }
+
+    fn create_transport(&self, accepting: bool) -> (MockTransport, Receiver<::crust::Event>) {
+        let (sender, receiver) = channel::<::crust::Event>();
+        let mut network = self.network.lock().unwrap();
+        let endpoint = ::crust::Endpoint::Tcp(network.next_address());
+        let _ = network.senders.insert(endpoint.clone(), sender);
+        (MockTransport {
+            network: self.network.clone(),
+            endpoint: endpoint,
+            accepting: accepting,
+        }, receiver)
+    }
+}
+
+/// Transport connecting to the other transports of a `Network`.
+pub struct MockTransport {
+    network: Arc<Mutex<NetworkImpl>>,
+    endpoint: ::crust::Endpoint,
+    accepting: bool,
+}
+
+impl MockTransport {
+    /// The endpoint this transport is known by on the network.
+    pub fn endpoint(&self) -> ::crust::Endpoint {
+        self.endpoint.clone()
+    }
+}
+
+impl Transport for MockTransport {
+    fn start_acceptors(&mut self) -> Vec<::crust::Endpoint> {
+        if !self.accepting { return vec![]; };
+        let mut network = self.network.lock().unwrap();
+        if !network.listeners.contains(&self.endpoint) {
+            network.listeners.push(self.endpoint.clone());
+        };
+        vec![self.endpoint.clone()]
+    }
+
+    // every mock transport is reachable on a single endpoint, whichever port is asked for
+    fn start_accepting(&mut self, _port: ::crust::Port) -> Vec<::crust::Endpoint> {
+        self.start_acceptors()
+    }
+
+    fn get_external_endpoints(&self) {}
+
+    fn bootstrap(&mut self, token: u32) {
+        let mut network = self.network.lock().unwrap();
+        // only nodes which started listening before us are bootstrap contacts
+        let contact = network.listeners.iter()
+            .take_while(|listener| **listener != self.endpoint)
+            .cloned()
+            .next();
+        match contact {
+            Some(contact) => { let _ = network.connect(&self.endpoint, &contact, token); },
+            None => {},
+        };
+        network.send_event(&self.endpoint, ::crust::Event::BootstrapFinished);
+    }
+
+    fn connect(&self, token: u32, endpoints: Vec<::crust::Endpoint>) {
+        let mut network = self.network.lock().unwrap();
+        for endpoint in endpoints {
+            if network.connect(&self.endpoint, &endpoint, token) { return; };
+        }
+    }
+
+    fn send(&self, connection: ::crust::Connection, bytes: Vec<u8>) {
+        let mut network = self.network.lock().unwrap();
+        let (peer, peer_connection) = match network.links.get(&connection) {
+            Some(link) => (link.peer.clone(), link.peer_connection.clone()),
+            None => {
+                debug!("Mock network: send on closed connection {:?}", connection);
+                return;
+            },
+        };
+        network.send_event(&peer, ::crust::Event::NewMessage(peer_connection, bytes));
+    }
+
+    fn drop_node(&self, connection: ::crust::Connection) {
+        self.network.lock().unwrap().disconnect(&connection);
+    }
+
+    fn stop(&mut self) {
+        let mut network = self.network.lock().unwrap();
+        let endpoint = self.endpoint.clone();
+        network.listeners.retain(|listener| *listener != endpoint);
+        let _ = network.senders.remove(&endpoint);
+        let connections = network.links.iter()
+            .filter(|&(_, link)| link.owner == endpoint)
+            .map(|(connection, _)| connection.clone())
+            .collect::<Vec<::crust::Connection>>();
+        for connection in connections {
+            network.disconnect(&connection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use transport::Transport;
+
+    #[test]
+    fn bootstrap_onto_earlier_node() {
+        let network = super::Network::new();
+        let (mut first, first_receiver) = network.new_transport();
+        let (mut second, second_receiver) = network.new_transport();
+        let _ = first.start_acceptors();
+        let _ = second.start_acceptors();
+
+        // the first node has nobody to bootstrap onto
+        first.bootstrap(0u32);
+        match first_receiver.try_recv() {
+            Ok(::crust::Event::BootstrapFinished) => {},
+            event => panic!("Unexpected event {:?}", event),
+        };
+
+        second.bootstrap(0u32);
+        let accepted = match first_receiver.try_recv() {
+            Ok(::crust::Event::OnAccept(connection)) => connection,
+            event => panic!("Unexpected event {:?}", event),
+        };
+        let connected = match second_receiver.try_recv() {
+            Ok(::crust::Event::OnConnect(connection, 0u32)) => connection,
+            event => panic!("Unexpected event {:?}", event),
+        };
+        match second_receiver.try_recv() {
+            Ok(::crust::Event::BootstrapFinished) => {},
+            event => panic!("Unexpected event {:?}", event),
+        };
+
+        second.send(connected.clone(), vec![1u8, 2u8, 3u8]);
+        match first_receiver.try_recv() {
+            Ok(::crust::Event::NewMessage(connection, bytes)) => {
+                assert_eq!(connection, accepted);
+                assert_eq!(bytes, vec![1u8, 2u8, 3u8]);
+            },
+            event => panic!("Unexpected event {:?}", event),
+        };
+
+        second.stop();
+        match first_receiver.try_recv() {
+            Ok(::crust::Event::LostConnection(connection)) => assert_eq!(connection, accepted),
+            event => panic!("Unexpected event {:?}", event),
+        };
+        assert_eq!(network.listener_count(), 1usize);
+    }
+
+    #[test]
+    fn held_events_are_queued() {
+        let network = super::Network::new();
+        let (mut first, first_receiver) = network.new_transport();
+        let (mut second, _second_receiver) = network.new_transport();
+        let _ = first.start_acceptors();
+        let _ = second.start_acceptors();
+
+        network.hold_events();
+        second.bootstrap(0u32);
+        assert!(first_receiver.try_recv().is_err());
+
+        let held = network.take_held_events();
+        assert_eq!(held.len(), 3usize);
+        assert_eq!(held[0].0, first.endpoint());
+        assert!(network.take_held_events().is_empty());
+
+        for (endpoint, event) in held {
+            network.deliver(&endpoint, event);
+        }
+        match first_receiver.try_recv() {
+            Ok(::crust::Event::OnAccept(_)) => {},
+            event => panic!("Unexpected event {:?}", event),
+        };
+    }
+
+    #[test]
+    fn client_is_never_a_bootstrap_contact() {
+        let network = super::Network::new();
+        let (mut client, _client_receiver) = network.new_client_transport();
+        let (mut node, node_receiver) = network.new_transport();
+        assert!(client.start_acceptors().is_empty());
+        let _ = node.start_acceptors();
+
+        node.bootstrap(0u32);
+        match node_receiver.try_recv() {
+            Ok(::crust::Event::BootstrapFinished) => {},
+            event => panic!("Unexpected event {:?}", event),
+        };
+    }
+}
@@ -21,6 +21,10 @@ pub mod node;
 pub mod client;
 /// Various random values for types available to tests.
 pub mod messages_util;
+/// In-process network to run many nodes in a single test.
+pub mod mock_network;
+/// Seeded discrete-event simulation of many nodes.
+pub mod simulator;
 
 pub use self::node::*;
 pub use self::client::*;
@@ -24,6 +24,7 @@ pub struct Node {
     db: ::std::collections::BTreeMap<::NameType, ::data::Data>,
     client_accounts: ::std::collections::BTreeMap<::NameType, u64>,
     connected: bool,
+    group_size: usize,
 }
 
 impl Node {
@@ -31,7 +32,8 @@ impl Node {
     /// Construct a new node.
     pub fn new() -> Node {
         let (sender, receiver) = ::std::sync::mpsc::channel::<::event::Event>();
-        let routing = ::routing::Routing::new(sender.clone());
+        let routing = ::routing::Routing::new(sender.clone(),
+                                              ::types::RoutingConfig::default_config());
 
         Node {
             routing: routing,
@@ -40,6 +42,49 @@ impl Node {
             db: ::std::collections::BTreeMap::new(),
             client_accounts: ::std::collections::BTreeMap::new(),
             connected: false,
+            group_size: ::types::GROUP_SIZE,
+        }
+    }
+
+    /// Construct a new node with the settings of a configuration file.
+    pub fn with_config_file(config_file: &::config_file::ConfigFile)
+            -> Result<Node, ::error::RoutingError> {
+        let (sender, receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let routing = try!(config_file.builder()).build(sender.clone());
+
+        Ok(Node {
+            routing: routing,
+            receiver: receiver,
+            sender: sender,
+            db: ::std::collections::BTreeMap::new(),
+            client_accounts: ::std::collections::BTreeMap::new(),
+            connected: false,
+            group_size: ::types::GROUP_SIZE,
+        })
+    }
+
+    /// Construct a new node on an in-process mock network.
+    pub fn with_network(network: &::test_utils::mock_network::Network) -> Node {
+        Node::with_network_config(network, ::types::RoutingConfig::default_config())
+    }
+
+    /// Construct a new node on an in-process mock network with the given group parameters.
+    pub fn with_network_config(network: &::test_utils::mock_network::Network,
+                               routing_config: ::types::RoutingConfig) -> Node {
+        let (sender, receiver) = ::std::sync::mpsc::channel::<::event::Event>();
+        let (transport, transport_receiver) = network.new_transport();
+        let group_size = routing_config.group_size();
+        let routing = ::routing::Routing::with_transport(sender.clone(), routing_config,
+                                                         transport, transport_receiver);
+
+        Node {
+            routing: routing,
+            receiver: receiver,
+            sender: sender,
+            db: ::std::collections::BTreeMap::new(),
+            client_accounts: ::std::collections::BTreeMap::new(),
+            connected: false,
+            group_size: group_size,
         }
     }
 
@@ -48,9 +93,11 @@ impl Node {
         while let Ok(event) = self.receiver.recv() {
             debug!("Node: Received event {:?}", event);
             match event {
-                ::event::Event::Request{ request, our_authority, from_authority, response_token } =>
-                    self.handle_request(request, our_authority, from_authority, response_token),
-                ::event::Event::Response{ response, our_authority, from_authority } => {
+                ::event::Event::Request{ request, our_authority, from_authority, response_token,
+                                         message_id } =>
+                    self.handle_request(request, our_authority, from_authority, response_token,
+                                        message_id),
+                ::event::Event::Response{ response, our_authority, from_authority, .. } => {
                     debug!("Received response event");
                     self.handle_response(response, our_authority, from_authority)
                 },
@@ -71,19 +118,29 @@ impl Node {
                     self.handle_churn(close_group, cause)
                 },
                 ::event::Event::Bootstrapped => debug!("Received bootstraped event"),
+                ::event::Event::BootstrapFailed => debug!("Received bootstrap failed event"),
                 ::event::Event::Connected => {
                     debug!("Received connected event");
                     self.connected = true;
                 },
                 ::event::Event::Disconnected => debug!("Received disconnected event"),
-                ::event::Event::FailedRequest{ request, our_authority, location, interface_error } => {
+                ::event::Event::IncompatiblePeer(address, version) => {
+                    debug!("Received incompatible peer event for {:?} at version {:?}",
+                        address, version);
+                },
+                ::event::Event::FailedRequest{ request, our_authority, location, interface_error,
+                                               .. } => {
                     debug!("Received failed request event");
                     self.handle_failed_request(request, our_authority, location, interface_error)
                 },
-                ::event::Event::FailedResponse{ response, our_authority, location, interface_error } => {
+                ::event::Event::FailedResponse{ response, our_authority, location, interface_error,
+                                                .. } => {
                     debug!("Received failed response event");
                     self.handle_failed_response(response, our_authority, location, interface_error)
                 },
+                ::event::Event::RequestTimeout{ request, .. } => {
+                    debug!("Received request timeout event for {:?}", request);
+                },
                 ::event::Event::Terminated => {
                     debug!("Received terminate event");
                     self.stop();
@@ -107,13 +164,16 @@ impl Node {
     fn handle_request(&mut self, request: ::ExternalRequest,
                                  our_authority: ::authority::Authority,
                                  from_authority: ::authority::Authority,
-                                 response_token: Option<::SignedToken>) {
+                                 response_token: Option<::SignedToken>,
+                                 message_id: ::MessageId) {
         match request {
             ::ExternalRequest::Get(data_request, _) => {
-                self.handle_get_request(data_request, our_authority, from_authority, response_token);
+                self.handle_get_request(data_request, our_authority, from_authority, response_token,
+                                        message_id);
             },
             ::ExternalRequest::Put(data) => {
-                self.handle_put_request(data, our_authority, from_authority, response_token);
+                self.handle_put_request(data, our_authority, from_authority, response_token,
+                                        message_id);
             },
             ::ExternalRequest::Post(_) => {
                 debug!("Node: Post unimplemented.");
@@ -127,7 +187,8 @@ impl Node {
     fn handle_get_request(&mut self, data_request: ::data::DataRequest,
                                      our_authority: ::authority::Authority,
                                      from_authority: ::authority::Authority,
-                                     response_token: Option<::SignedToken>) {
+                                     response_token: Option<::SignedToken>,
+                                     message_id: ::MessageId) {
         let data = match self.db.get(&data_request.name()) {
             Some(data) => data.clone(),
             None => {
@@ -136,13 +197,15 @@ impl Node {
             }
         };
 
-        self.routing.get_response(our_authority, from_authority, data, data_request, response_token);
+        self.routing.get_response(our_authority, from_authority, data, data_request, response_token,
+                                  message_id);
     }
 
     fn handle_put_request(&mut self, data: ::data::Data,
                                      our_authority: ::authority::Authority,
                                      _from_authority: ::authority::Authority,
-                                     _response_token: Option<::SignedToken>) {
+                                     _response_token: Option<::SignedToken>,
+                                     message_id: ::MessageId) {
         match our_authority {
             ::authority::Authority::NaeManager(_) => {
                 debug!("Storing: key {:?}, value {:?}", data.name(), data);
@@ -150,8 +213,9 @@ impl Node {
             },
             ::authority::Authority::ClientManager(_) => {
                 debug!("Sending: key {:?}, value {:?}", data.name(), data);
-                self.routing.put_request(
-                    our_authority, ::authority::Authority::NaeManager(data.name()), data); 
+                let _ = self.routing.group_request(our_authority,
+                    ::authority::Authority::NaeManager(data.name()), ::ExternalRequest::Put(data),
+                    message_id);
             },
             _ => {
                 debug!("Node: Unexpected our_authority ({:?})", our_authority);
@@ -162,10 +226,10 @@ impl Node {
 
     fn handle_churn(&mut self, our_close_group: Vec<::NameType>, cause: ::NameType) {
         let mut exit = false;
-        if our_close_group.len() < ::types::GROUP_SIZE {
+        if our_close_group.len() < self.group_size {
             if self.connected {
                 debug!("Close group ({:?}) has fallen below group size {:?}, terminating node",
-                    our_close_group.len(), ::types::GROUP_SIZE);
+                    our_close_group.len(), self.group_size);
                 exit = true;
             } else {
                 debug!("Ignoring churn as we are not yet connected.");
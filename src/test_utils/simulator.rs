@@ -0,0 +1,424 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Discrete-event simulation of a network of routing nodes.
+//!
+//! The simulator holds all traffic of a mock `Network` and delivers it according to a virtual
+//! clock.  Joins, leaves, message loss, latency and the keys of the nodes are drawn from RNGs
+//! seeded with a single `u32`, so a failing scenario can be replayed by rerunning it with the
+//! reported seed.  All nodes are polled in turn on the thread of the simulator, which drives the
+//! clock of routing's own timers and timestamps; only the caches of external crates still expire
+//! on the system clock.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use action::Action;
+use event::Event;
+use id::Id;
+use routing_node::{Poll, RoutingNode};
+use test_utils::mock_network::{MockTransport, Network};
+use types::RoutingConfig;
+
+/// Parameters of a simulation, all durations are in virtual milliseconds.
+#[derive(Clone, Debug)]
+pub struct SimulatorConfig {
+    /// Number of nodes started at the beginning of the simulation.
+    pub initial_nodes: usize,
+    /// The number of nodes churn will never go below.
+    pub min_nodes: usize,
+    /// The number of nodes churn will never go above.
+    pub max_nodes: usize,
+    /// Interval between two churn decisions.
+    pub churn_interval: u64,
+    /// Probability that a churn decision makes a node join, otherwise a node leaves.
+    pub join_probability: f64,
+    /// Probability that a message is lost.
+    pub loss_probability: f64,
+    /// Minimal latency of a delivery.
+    pub min_latency: u64,
+    /// Maximal latency of a delivery.
+    pub max_latency: u64,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> SimulatorConfig {
+        SimulatorConfig {
+            initial_nodes: 4usize,
+            min_nodes: 2usize,
+            max_nodes: 12usize,
+            churn_interval: 1000u64,
+            join_probability: 0.5f64,
+            loss_probability: 0.0f64,
+            min_latency: 1u64,
+            max_latency: 50u64,
+        }
+    }
+}
+
+/// Churn the simulator applied at a point in virtual time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Churn {
+    /// A new node with the given index joined.
+    Join(usize),
+    /// The node with the given index left.
+    Leave(usize),
+}
+
+enum Scheduled {
+    Churn,
+    Deliver(::crust::Endpoint, ::crust::Event),
+}
+
+struct SimulatedNode {
+    routing_node: RoutingNode<MockTransport>,
+    action_sender: Sender<Action>,
+    event_receiver: Receiver<Event>,
+}
+
+/// Drives a network of nodes on a virtual clock.
+pub struct Simulator {
+    seed: u32,
+    config: SimulatorConfig,
+    network: Network,
+    churn_rng: XorShiftRng,
+    network_rng: XorShiftRng,
+    id_rng: XorShiftRng,
+    clock: u64,
+    churning: bool,
+    // scheduled events, ordered by virtual time and then by order of scheduling
+    queue: BTreeMap<(u64, u64), Scheduled>,
+    sequence: u64,
+    // latest delivery time per endpoint, deliveries to one endpoint keep their order
+    last_delivery: BTreeMap<String, u64>,
+    nodes: BTreeMap<usize, SimulatedNode>,
+    next_index: usize,
+    trace: Vec<(u64, Churn)>,
+    events: Vec<(u64, usize, Event)>,
+    lost: usize,
+}
+
+impl Simulator {
+    /// Create a simulator for the given seed, the initial nodes join at virtual time zero.  The
+    /// clock of routing runs virtually on the calling thread until the simulator is dropped.
+    pub fn new(seed: u32, config: SimulatorConfig) -> Simulator {
+        debug!("Simulator seed {:?} with {:?}", seed, config);
+        ::clock::start_virtual();
+        let network = Network::new();
+        network.hold_events();
+        let mut simulator = Simulator {
+            seed: seed,
+            config: config,
+            network: network,
+            churn_rng: XorShiftRng::from_seed([seed, 0x193a_6754, 0xa8a7_d469, 0x9783_0e05]),
+            network_rng: XorShiftRng::from_seed([seed, 0x113b_a7bb, 0x0c29_6a58, 0x2be3_0c05]),
+            id_rng: XorShiftRng::from_seed([seed, 0x5f35_6495, 0x7a2c_1b8e, 0x3ba9_4d21]),
+            clock: 0u64,
+            churning: true,
+            queue: BTreeMap::new(),
+            sequence: 0u64,
+            last_delivery: BTreeMap::new(),
+            nodes: BTreeMap::new(),
+            next_index: 0usize,
+            trace: Vec::new(),
+            events: Vec::new(),
+            lost: 0usize,
+        };
+        for _ in 0..simulator.config.initial_nodes {
+            simulator.join();
+        }
+        let churn_interval = simulator.config.churn_interval;
+        simulator.schedule(churn_interval, Scheduled::Churn);
+        simulator
+    }
+
+    /// The seed given in the environment variable ROUTING_SIMULATION_SEED to replay a simulation,
+    /// or a random seed if it is not set.
+    pub fn seed_from_env() -> u32 {
+        match ::std::env::var("ROUTING_SIMULATION_SEED").ok().and_then(|seed| seed.parse().ok()) {
+            Some(seed) => seed,
+            None => ::rand::random::<u32>(),
+        }
+    }
+
+    /// The seed this simulation was created with.
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Current virtual time.
+    pub fn clock(&self) -> u64 {
+        self.clock
+    }
+
+    /// The network the nodes run on.
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// Number of nodes currently running.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of running nodes which have at least one contact in their routing table.
+    pub fn connected_node_count(&self) -> usize {
+        self.nodes.values()
+            .filter(|node| match node.routing_node.snapshot().lock() {
+                Ok(snapshot) => !snapshot.contacts.is_empty(),
+                Err(_) => false,
+            })
+            .count()
+    }
+
+    /// Number of messages dropped so far.
+    pub fn lost_messages(&self) -> usize {
+        self.lost
+    }
+
+    /// Churn applied so far together with the virtual time it happened at.
+    pub fn trace(&self) -> &Vec<(u64, Churn)> {
+        &self.trace
+    }
+
+    /// The churn and connection events the nodes reported to their users so far, with the
+    /// virtual time and the index of the node.
+    pub fn events(&self) -> &Vec<(u64, usize, Event)> {
+        &self.events
+    }
+
+    /// Stop joining and removing nodes, e.g. to let the network settle before checking it.
+    pub fn stop_churn(&mut self) {
+        self.churning = false;
+    }
+
+    /// Run the simulation until the virtual clock has advanced by duration.
+    pub fn run_for(&mut self, duration: u64) {
+        let end = self.clock + duration;
+        loop {
+            self.settle();
+            let key = match self.queue.keys().next() {
+                Some(key) if key.0 <= end => key.clone(),
+                _ => break,
+            };
+            let scheduled = self.queue.remove(&key).unwrap();
+            self.set_clock(key.0);
+            match scheduled {
+                Scheduled::Churn => {
+                    if self.churning {
+                        self.churn();
+                        let churn_interval = self.config.churn_interval;
+                        self.schedule(churn_interval, Scheduled::Churn);
+                    };
+                },
+                Scheduled::Deliver(endpoint, event) => self.network.deliver(&endpoint, event),
+            };
+        }
+        self.set_clock(end);
+        self.settle();
+    }
+
+    /// Stop all nodes.
+    pub fn stop(&mut self) {
+        let indices = self.nodes.keys().cloned().collect::<Vec<usize>>();
+        for index in indices {
+            let _ = self.terminate(index);
+        }
+    }
+
+    fn set_clock(&mut self, clock: u64) {
+        self.clock = clock;
+        ::clock::set_virtual_elapsed(clock);
+    }
+
+    fn schedule(&mut self, delay: u64, scheduled: Scheduled) {
+        let _ = self.queue.insert((self.clock + delay, self.sequence), scheduled);
+        self.sequence += 1;
+    }
+
+    fn churn(&mut self) {
+        let join = self.churn_rng.gen::<f64>() < self.config.join_probability;
+        if join && self.nodes.len() < self.config.max_nodes {
+            self.join();
+        } else if !join && self.nodes.len() > self.config.min_nodes {
+            let position = self.churn_rng.gen_range(0usize, self.nodes.len());
+            let index = self.nodes.keys().nth(position).cloned().unwrap();
+            self.leave(index);
+        }
+    }
+
+    fn join(&mut self) {
+        let index = self.next_index;
+        self.next_index += 1;
+        let (action_sender, action_receiver) = channel::<Action>();
+        let (event_sender, event_receiver) = channel::<Event>();
+        let (transport, transport_receiver) = self.network.new_transport();
+        let id = seeded_id(&mut self.id_rng);
+        let mut routing_node = RoutingNode::with_transport(action_sender.clone(), action_receiver,
+            event_sender, false, Some(id), RoutingConfig::default_config(), vec![], vec![],
+            transport, transport_receiver);
+        routing_node.bootstrap();
+        let _ = self.nodes.insert(index, SimulatedNode {
+            routing_node: routing_node,
+            action_sender: action_sender,
+            event_receiver: event_receiver,
+        });
+        debug!("Simulator {:?}: node {:?} joins", self.clock, index);
+        self.trace.push((self.clock, Churn::Join(index)));
+        self.settle();
+    }
+
+    fn leave(&mut self, index: usize) {
+        if !self.terminate(index) {
+            return;
+        };
+        debug!("Simulator {:?}: node {:?} leaves", self.clock, index);
+        self.trace.push((self.clock, Churn::Leave(index)));
+        self.settle();
+    }
+
+    // Terminates the node, returns false if there is no node with that index.
+    fn terminate(&mut self, index: usize) -> bool {
+        let mut node = match self.nodes.remove(&index) {
+            Some(node) => node,
+            None => return false,
+        };
+        let _ = node.action_sender.send(Action::Terminate);
+        while node.routing_node.poll() != Poll::Terminated {}
+        true
+    }
+
+    // Polls the nodes in turn until none of them has anything left to do at the current time,
+    // and schedules the deliveries of everything they sent.
+    fn settle(&mut self) {
+        loop {
+            let mut busy = false;
+            for (index, node) in self.nodes.iter_mut() {
+                while node.routing_node.poll() == Poll::Busy {
+                    busy = true;
+                }
+                while let Ok(event) = node.event_receiver.try_recv() {
+                    match event {
+                        Event::Churn(_, _) | Event::Connected | Event::Disconnected =>
+                            self.events.push((self.clock, *index, event)),
+                        _ => {},
+                    };
+                }
+            }
+            let mut held = self.network.take_held_events();
+            if held.is_empty() && !busy {
+                break;
+            };
+            // the order in which a node sends to different peers is not significant, the order
+            // of the traffic towards each peer is kept
+            held.sort_by(|lhs, rhs| format!("{:?}", lhs.0).cmp(&format!("{:?}", rhs.0)));
+            for (endpoint, event) in held {
+                self.schedule_delivery(endpoint, event);
+            }
+        }
+    }
+
+    fn schedule_delivery(&mut self, endpoint: ::crust::Endpoint, event: ::crust::Event) {
+        // only messages get lost, the connection events of the transport are reliable
+        let lost = match event {
+            ::crust::Event::NewMessage(_, _) =>
+                self.network_rng.gen::<f64>() < self.config.loss_probability,
+            _ => false,
+        };
+        if lost {
+            self.lost += 1;
+            debug!("Simulator {:?}: dropped message for {:?}", self.clock, endpoint);
+            return;
+        }
+        let latency = self.network_rng.gen_range(self.config.min_latency,
+                                                 self.config.max_latency + 1);
+        let key = format!("{:?}", endpoint);
+        let time = match self.last_delivery.get(&key) {
+            Some(&last) if last > self.clock + latency => last,
+            _ => self.clock + latency,
+        };
+        let _ = self.last_delivery.insert(key, time);
+        let _ = self.queue.insert((time, self.sequence), Scheduled::Deliver(endpoint, event));
+        self.sequence += 1;
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.stop();
+        ::clock::stop_virtual();
+    }
+}
+
+// The keys of a node drawn from the RNG, so that the names of the nodes follow from the seed.
+fn seeded_id(rng: &mut XorShiftRng) -> Id {
+    use sodiumoxide::crypto::{box_, sign};
+    use sodiumoxide::crypto::scalarmult::curve25519;
+
+    let sign_keys = sign::keypair_from_seed(&sign::Seed(rng.gen::<[u8; 32]>()));
+    let encrypt_secret = rng.gen::<[u8; 32]>();
+    let encrypt_public = curve25519::scalarmult_base(&curve25519::Scalar(encrypt_secret));
+    Id::with_keys(sign_keys, (box_::PublicKey(encrypt_public.0), box_::SecretKey(encrypt_secret)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Churn, Simulator, SimulatorConfig};
+
+    #[test]
+    fn same_seed_replays_run() {
+        let config = SimulatorConfig { initial_nodes: 3usize, ..SimulatorConfig::default() };
+        let mut first = Simulator::new(42u32, config.clone());
+        first.run_for(5000u64);
+        first.stop();
+        let first_events = first.events().clone();
+        let first_trace = first.trace().clone();
+        drop(first);
+        let mut second = Simulator::new(42u32, config);
+        second.run_for(5000u64);
+        second.stop();
+
+        assert_eq!(first_trace, *second.trace());
+        assert_eq!(first_trace[0], (0u64, Churn::Join(0usize)));
+        assert_eq!(second.clock(), 5000u64);
+        // the nodes have the same names and see the same churn at the same virtual times
+        assert!(first_events.iter().any(|&(_, _, ref event)| match *event {
+            ::event::Event::Churn(_, _) => true,
+            _ => false,
+        }));
+        assert_eq!(first_events, *second.events());
+    }
+
+    #[test]
+    fn churn_stays_within_bounds() {
+        let config = SimulatorConfig {
+            initial_nodes: 3usize,
+            min_nodes: 2usize,
+            max_nodes: 4usize,
+            churn_interval: 100u64,
+            ..SimulatorConfig::default()
+        };
+        let mut simulator = Simulator::new(7u32, config);
+        for _ in 0..20 {
+            simulator.run_for(100u64);
+            assert!(simulator.node_count() >= 2usize);
+            assert!(simulator.node_count() <= 4usize);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! The connection layer routing is built on.
+//!
+//! RoutingNode never talks to the network directly; it issues commands through the `Transport`
+//! trait and consumes the resulting `::crust::Event`s from a channel handed over together with
+//! the transport.  `::crust::Service` is the default implementation; alternative implementations
+//! (e.g. an in-process network for tests) only need to honour the same event contract.
+
+/// Operations routing requires from its connection layer.
+pub trait Transport {
+    /// Start listening for incoming connections and return the endpoints we accept on.
+    fn start_acceptors(&mut self) -> Vec<::crust::Endpoint>;
+
+    /// Start listening for incoming connections on the given port and return the endpoints we
+    /// accept on.
+    fn start_accepting(&mut self, port: ::crust::Port) -> Vec<::crust::Endpoint>;
+
+    /// Request our external endpoints; they are reported through
+    /// `::crust::Event::ExternalEndpoints`.
+    fn get_external_endpoints(&self);
+
+    /// Bootstrap onto the network; every bootstrap connection is reported through
+    /// `::crust::Event::OnConnect` carrying the given token, followed by
+    /// `::crust::Event::BootstrapFinished`.
+    fn bootstrap(&mut self, token: u32);
+
+    /// Try to connect to any of the given endpoints; a successful connection is reported through
+    /// `::crust::Event::OnConnect` carrying the given token.
+    fn connect(&self, token: u32, endpoints: Vec<::crust::Endpoint>);
+
+    /// Send the bytes over the given connection.
+    fn send(&self, connection: ::crust::Connection, bytes: Vec<u8>);
+
+    /// Close the given connection.
+    fn drop_node(&self, connection: ::crust::Connection);
+
+    /// Stop the transport and close all connections.
+    fn stop(&mut self);
+}
+
+impl Transport for ::crust::Service {
+    fn start_acceptors(&mut self) -> Vec<::crust::Endpoint> {
+        self.start_default_acceptors().into_iter()
+            .filter_map(|ep|ep.ok())
+            .flat_map(::crust::ifaddrs_if_unspecified)
+            .collect()
+    }
+
+    fn start_accepting(&mut self, port: ::crust::Port) -> Vec<::crust::Endpoint> {
+        ::crust::Service::start_accepting(self, port).into_iter()
+            .flat_map(::crust::ifaddrs_if_unspecified)
+            .collect()
+    }
+
+    fn get_external_endpoints(&self) {
+        ::crust::Service::get_external_endpoints(self);
+    }
+
+    fn bootstrap(&mut self, token: u32) {
+        ::crust::Service::bootstrap(self, token);
+    }
+
+    fn connect(&self, token: u32, endpoints: Vec<::crust::Endpoint>) {
+        ::crust::Service::connect(self, token, endpoints);
+    }
+
+    fn send(&self, connection: ::crust::Connection, bytes: Vec<u8>) {
+        ::crust::Service::send(self, connection, bytes);
+    }
+
+    fn drop_node(&self, connection: ::crust::Connection) {
+        ::crust::Service::drop_node(self, connection);
+    }
+
+    fn stop(&mut self) {
+        ::crust::Service::stop(self);
+    }
+}
+
+/// Start a crust::Service, returning it together with the receiver for its events.
+pub fn start_crust_service() -> (::crust::Service,
+                                 ::std::sync::mpsc::Receiver<::crust::Event>) {
+    let (crust_sender, crust_receiver) = ::std::sync::mpsc::channel::<::crust::Event>();
+    let crust_service = match ::crust::Service::new(crust_sender) {
+        Ok(service) => service,
+        Err(what) => panic!(format!("Unable to start crust::Service {}", what)),
+    };
+    (crust_service, crust_receiver)
+}
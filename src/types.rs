@@ -54,10 +54,43 @@ pub fn generate_random_vec_u8(size: usize) -> Vec<u8> {
     vec
 }
 
-/// Group size.
+/// Default group size.
 pub const GROUP_SIZE: usize = 8;
-/// Quorum size.
+/// Default quorum size.
 pub const QUORUM_SIZE: usize = 5;
+/// Default number of contacts in a bucket of the routing table, outside of our close group.
+pub const BUCKET_SIZE: usize = 8;
+/// Highest admission difficulty, in leading zero bits, a network can be configured with and a
+/// joining node will solve for.
+pub const MAX_ADMISSION_DIFFICULTY: u8 = 32;
+/// Default messages per second and burst size allowed on a single connection.
+pub const CONNECTION_RATE: (u32, u32) = (200, 400);
+/// Default messages per second and burst size allowed for a single verified claimant.
+pub const CLAIMANT_RATE: (u32, u32) = (50, 100);
+/// Default number of outstanding excess messages after which a connection is dropped.
+pub const MAX_EXCESS_MESSAGES: u32 = 100;
+/// Version of the wire protocol we speak, sent in every Hello and SignedMessage and ahead of
+/// every message on the wire.
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Oldest protocol version we still speak.
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
+
+/// Return whether we can read a message of the given protocol version.
+pub fn is_compatible_version(version: u8) -> bool {
+    version >= MIN_PROTOCOL_VERSION && version <= PROTOCOL_VERSION
+}
+
+/// Negotiate the version to speak with a peer, given the newest and oldest version it announced
+/// in its Hello: the newest version both sides speak.  A newer peer has to downgrade to it.
+/// Returns None if the peer and we have no version in common.
+pub fn negotiate_version(their_version: u8, their_min_version: u8) -> Option<u8> {
+    let version = ::std::cmp::min(PROTOCOL_VERSION, their_version);
+    if version >= ::std::cmp::max(MIN_PROTOCOL_VERSION, their_min_version) {
+        Some(version)
+    } else {
+        None
+    }
+}
 /// Type definition.
 pub type Bytes = Vec<u8>;
 
@@ -93,7 +126,7 @@ impl ::std::fmt::Debug for Address {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, RustcEncodable, RustcDecodable)]
 /// CacheOptions.
 pub struct CacheOptions {
     cache_plain_data: bool,
@@ -153,9 +186,310 @@ impl CacheOptions {
     }
 }
 
+#[derive(PartialEq, Eq, Clone, Debug)]
+/// RequestPolicy sets how long a client waits for the response to a Get request and how often the
+/// request is sent again before routing gives up on it with Event::RequestTimeout.  Put, Post and
+/// Delete requests are only answered on failure and are not retried.
+pub struct RequestPolicy {
+    timeout: ::time::Duration,
+    retries: u32,
+}
+
+impl RequestPolicy {
+
+    /// Construct with the given time to wait for each attempt and the number of retries.
+    pub fn new(timeout: ::time::Duration, retries: u32) -> RequestPolicy {
+        RequestPolicy {
+            timeout: timeout,
+            retries: retries,
+        }
+    }
+
+    /// Construct with a timeout of thirty seconds and two retries.
+    pub fn default_policy() -> RequestPolicy {
+        RequestPolicy::new(::time::Duration::seconds(30), 2u32)
+    }
+
+    /// Return the time to wait for a response to each attempt.
+    pub fn timeout(&self) -> ::time::Duration {
+        self.timeout
+    }
+
+    /// Return the number of times a request is sent again after the first attempt timed out.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+/// ExpiryDurations sets how long a node remembers filtered messages, partially accumulated
+/// messages and refreshes, and the public ids of its peers.
+pub struct ExpiryDurations {
+    filter: ::time::Duration,
+    message_accumulator: ::time::Duration,
+    refresh_accumulator: ::time::Duration,
+    public_id_cache: ::time::Duration,
+}
+
+impl ExpiryDurations {
+
+    /// Construct with the given durations.
+    pub fn new(filter: ::time::Duration, message_accumulator: ::time::Duration,
+            refresh_accumulator: ::time::Duration, public_id_cache: ::time::Duration)
+            -> ExpiryDurations {
+        ExpiryDurations {
+            filter: filter,
+            message_accumulator: message_accumulator,
+            refresh_accumulator: refresh_accumulator,
+            public_id_cache: public_id_cache,
+        }
+    }
+
+    /// Construct with twenty minutes for the filter, five minutes for both accumulators and ten
+    /// minutes for the public id cache.
+    pub fn default_durations() -> ExpiryDurations {
+        ExpiryDurations::new(::time::Duration::minutes(20), ::time::Duration::minutes(5),
+                             ::time::Duration::minutes(5), ::time::Duration::minutes(10))
+    }
+
+    /// Return the expiry duration of the message filter.
+    pub fn filter(&self) -> ::time::Duration {
+        self.filter
+    }
+
+    /// Return the expiry duration of the message accumulator.
+    pub fn message_accumulator(&self) -> ::time::Duration {
+        self.message_accumulator
+    }
+
+    /// Return the expiry duration of the refresh accumulator.
+    pub fn refresh_accumulator(&self) -> ::time::Duration {
+        self.refresh_accumulator
+    }
+
+    /// Return the expiry duration of the public id cache.
+    pub fn public_id_cache(&self) -> ::time::Duration {
+        self.public_id_cache
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+/// RateLimits sets how many messages per second, and in a single burst, are handled from one
+/// connection and from one verified claimant, and after how many messages in excess of its rate
+/// a connection is dropped.
+pub struct RateLimits {
+    connection_rate: (u32, u32),
+    claimant_rate: (u32, u32),
+    max_excess_messages: u32,
+}
+
+impl RateLimits {
+
+    /// Construct with the given rates and burst sizes and the number of excess messages after
+    /// which a connection is dropped.  None of them can be zero.
+    pub fn new(connection_rate: (u32, u32), claimant_rate: (u32, u32), max_excess_messages: u32)
+            -> Result<RateLimits, ::error::RoutingError> {
+        if connection_rate.0 == 0 || connection_rate.1 == 0 {
+            return Err(::error::RoutingError::InvalidConfig("connection rate can not be zero"));
+        };
+        if claimant_rate.0 == 0 || claimant_rate.1 == 0 {
+            return Err(::error::RoutingError::InvalidConfig("claimant rate can not be zero"));
+        };
+        if max_excess_messages == 0 {
+            return Err(::error::RoutingError::InvalidConfig(
+                "maximum of excess messages can not be zero"));
+        };
+        Ok(RateLimits {
+            connection_rate: connection_rate,
+            claimant_rate: claimant_rate,
+            max_excess_messages: max_excess_messages,
+        })
+    }
+
+    /// Construct with CONNECTION_RATE, CLAIMANT_RATE and MAX_EXCESS_MESSAGES.
+    pub fn default_limits() -> RateLimits {
+        RateLimits {
+            connection_rate: CONNECTION_RATE,
+            claimant_rate: CLAIMANT_RATE,
+            max_excess_messages: MAX_EXCESS_MESSAGES,
+        }
+    }
+
+    /// Return the messages per second and burst size allowed on a single connection.
+    pub fn connection_rate(&self) -> (u32, u32) {
+        self.connection_rate
+    }
+
+    /// Return the messages per second and burst size allowed for a single verified claimant.
+    pub fn claimant_rate(&self) -> (u32, u32) {
+        self.claimant_rate
+    }
+
+    /// Return the number of outstanding excess messages after which a connection is dropped.
+    pub fn max_excess_messages(&self) -> u32 {
+        self.max_excess_messages
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+/// ThresholdPolicy decides how many members of a group need to agree on a message before it is
+/// accepted from that group.
+pub enum ThresholdPolicy {
+    /// Always require the full quorum.
+    Quorum,
+    /// Require the quorum, or the given percentage of our routing table if that is smaller.
+    /// This allows a network smaller than a group to function.
+    RoutingTablePercentage(u8),
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+/// RoutingConfig sets the group size, the quorum size and the threshold policy of a network.  All
+/// nodes of a network are expected to run with the same configuration.
+pub struct RoutingConfig {
+    group_size: usize,
+    quorum_size: usize,
+    threshold_policy: ThresholdPolicy,
+    admission_difficulty: u8,
+    bucket_size: usize,
+    rate_limits: RateLimits,
+}
+
+impl RoutingConfig {
+
+    /// Construct with the given group size, quorum size and threshold policy.  Neither size can
+    /// be zero and the quorum size can not exceed the group size.
+    pub fn new(group_size: usize, quorum_size: usize, threshold_policy: ThresholdPolicy)
+            -> Result<RoutingConfig, ::error::RoutingError> {
+        if group_size == 0usize {
+            return Err(::error::RoutingError::InvalidConfig("group size can not be zero"));
+        };
+        if quorum_size == 0usize {
+            return Err(::error::RoutingError::InvalidConfig("quorum size can not be zero"));
+        };
+        if quorum_size > group_size {
+            return Err(::error::RoutingError::InvalidConfig("quorum size exceeds group size"));
+        };
+        Ok(RoutingConfig {
+            group_size: group_size,
+            quorum_size: quorum_size,
+            threshold_policy: threshold_policy,
+            admission_difficulty: 0u8,
+            bucket_size: BUCKET_SIZE,
+            rate_limits: RateLimits::default_limits(),
+        })
+    }
+
+    /// Require a joining node to solve a hashcash puzzle of the given number of leading zero bits
+    /// for its public id before the network relocates it.  Zero, the default, admits every node.
+    /// The difficulty can not exceed MAX_ADMISSION_DIFFICULTY.
+    pub fn with_admission_difficulty(mut self, admission_difficulty: u8)
+            -> Result<RoutingConfig, ::error::RoutingError> {
+        if admission_difficulty > MAX_ADMISSION_DIFFICULTY {
+            return Err(::error::RoutingError::InvalidConfig("admission difficulty too high"));
+        };
+        self.admission_difficulty = admission_difficulty;
+        Ok(self)
+    }
+
+    /// Keep up to bucket_size contacts in each bucket of the routing table; members of our close
+    /// group are kept regardless.  The bucket size can not be zero.
+    pub fn with_bucket_size(mut self, bucket_size: usize)
+            -> Result<RoutingConfig, ::error::RoutingError> {
+        if bucket_size == 0usize {
+            return Err(::error::RoutingError::InvalidConfig("bucket size can not be zero"));
+        };
+        self.bucket_size = bucket_size;
+        Ok(self)
+    }
+
+    /// Limit the messages handled from a single connection and a single claimant.
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> RoutingConfig {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Construct with GROUP_SIZE, QUORUM_SIZE and a threshold of 80% of the routing table.
+    pub fn default_config() -> RoutingConfig {
+        RoutingConfig {
+            group_size: GROUP_SIZE,
+            quorum_size: QUORUM_SIZE,
+            threshold_policy: ThresholdPolicy::RoutingTablePercentage(80u8),
+            admission_difficulty: 0u8,
+            bucket_size: BUCKET_SIZE,
+            rate_limits: RateLimits::default_limits(),
+        }
+    }
+
+    /// Return the number of nodes in a close group.
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// Return the number of group members needed for a quorum.
+    pub fn quorum_size(&self) -> usize {
+        self.quorum_size
+    }
+
+    /// Return the number of leading zero bits of work a joining node has to prove.
+    pub fn admission_difficulty(&self) -> u8 {
+        self.admission_difficulty
+    }
+
+    /// Return the number of contacts kept in a bucket of the routing table.
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// Return the limits on the messages handled from a single connection and claimant.
+    pub fn rate_limits(&self) -> &RateLimits {
+        &self.rate_limits
+    }
+
+    /// Return the threshold policy.
+    pub fn threshold_policy(&self) -> &ThresholdPolicy {
+        &self.threshold_policy
+    }
+
+    /// Return the number of group members that need to agree on a message, given the number of
+    /// nodes in our routing table.
+    pub fn group_threshold(&self, routing_table_size: usize) -> usize {
+        match self.threshold_policy {
+            ThresholdPolicy::Quorum => self.quorum_size,
+            ThresholdPolicy::RoutingTablePercentage(percentage) =>
+                ::std::cmp::min(self.quorum_size,
+                                routing_table_size * percentage as usize / 100usize),
+        }
+    }
+
+    /// Return the number of group members that have to certify a relocated name, given the
+    /// number of nodes in the network as far as we know it, ourselves included.  This is the
+    /// quorum size, unless the network is too small to hold a quorum, in which case it is all of
+    /// its nodes up to the group size.  The joining node and its peers both use it, so they agree
+    /// whenever the network holds a quorum.
+    pub fn relocation_quorum(&self, network_size: usize) -> usize {
+        ::std::cmp::min(self.quorum_size, ::std::cmp::min(self.group_size, network_size))
+    }
+}
+
 #[cfg(test)]
 mod test {
 
+    #[test]
+    fn compatible_versions() {
+        assert!(super::is_compatible_version(super::PROTOCOL_VERSION));
+        assert!(!super::is_compatible_version(super::PROTOCOL_VERSION + 1));
+        assert!(!super::is_compatible_version(super::MIN_PROTOCOL_VERSION - 1));
+
+        // a newer peer still speaking our version downgrades to it
+        assert_eq!(super::negotiate_version(super::PROTOCOL_VERSION + 1,
+                                            super::PROTOCOL_VERSION),
+                   Some(super::PROTOCOL_VERSION));
+        assert_eq!(super::negotiate_version(super::PROTOCOL_VERSION + 1,
+                                            super::PROTOCOL_VERSION + 1), None);
+        assert_eq!(super::negotiate_version(super::MIN_PROTOCOL_VERSION - 1,
+                                            super::MIN_PROTOCOL_VERSION - 1), None);
+    }
+
     #[test]
     fn check_conversions() {
         let bytes: super::Bytes = super::generate_random_vec_u8(64);
@@ -171,6 +505,56 @@ mod test {
         assert_eq!(&bytes[..], &array[..]);
     }
 
+    #[test]
+    fn request_policy() {
+        let request_policy = super::RequestPolicy::new(::time::Duration::seconds(5), 3u32);
+
+        assert_eq!(request_policy.timeout(), ::time::Duration::seconds(5));
+        assert_eq!(request_policy.retries(), 3u32);
+    }
+
+    #[test]
+    fn routing_config_group_threshold() {
+        let config = super::RoutingConfig::default_config();
+        assert_eq!(config.group_threshold(0usize), 0usize);
+        assert_eq!(config.group_threshold(5usize), 4usize);
+        assert_eq!(config.group_threshold(64usize), super::QUORUM_SIZE);
+
+        let config = super::RoutingConfig::new(3usize, 2usize, super::ThresholdPolicy::Quorum)
+                         .unwrap();
+        assert_eq!(config.group_size(), 3usize);
+        assert_eq!(config.group_threshold(0usize), 2usize);
+        assert_eq!(config.group_threshold(64usize), 2usize);
+        assert_eq!(config.relocation_quorum(1usize), 1usize);
+        assert_eq!(config.relocation_quorum(64usize), 2usize);
+        assert_eq!(config.admission_difficulty(), 0u8);
+        let config = config.with_admission_difficulty(12u8).unwrap();
+        assert_eq!(config.admission_difficulty(), 12u8);
+        assert_eq!(config.bucket_size(), super::BUCKET_SIZE);
+        let config = config.with_bucket_size(20usize).unwrap();
+        assert_eq!(config.bucket_size(), 20usize);
+    }
+
+    #[test]
+    fn routing_config_rejects_invalid_sizes() {
+        let policy = super::ThresholdPolicy::Quorum;
+        assert!(super::RoutingConfig::new(0usize, 0usize, policy.clone()).is_err());
+        assert!(super::RoutingConfig::new(3usize, 0usize, policy.clone()).is_err());
+        assert!(super::RoutingConfig::new(3usize, 4usize, policy.clone()).is_err());
+        let config = super::RoutingConfig::new(1usize, 1usize, policy).unwrap();
+        assert!(config.clone().with_bucket_size(0usize).is_err());
+        assert!(config.clone().with_bucket_size(1usize).is_ok());
+        assert!(config.clone().with_admission_difficulty(super::MAX_ADMISSION_DIFFICULTY).is_ok());
+        assert!(config.with_admission_difficulty(super::MAX_ADMISSION_DIFFICULTY + 1).is_err());
+
+        assert!(super::RateLimits::new((0, 1), (1, 1), 1).is_err());
+        assert!(super::RateLimits::new((1, 1), (1, 0), 1).is_err());
+        assert!(super::RateLimits::new((1, 1), (1, 1), 0).is_err());
+        let rate_limits = super::RateLimits::new((1, 2), (3, 4), 5).unwrap();
+        let config = super::RoutingConfig::default_config().with_rate_limits(rate_limits.clone());
+        assert_eq!(*config.rate_limits(), rate_limits);
+    }
+
     #[test]
     fn cache_options_no_caching() {
         let cache_options = super::CacheOptions::no_caching();
@@ -34,7 +34,7 @@ impl<K, V> ExpirationMap<K, V> where K: PartialOrd + Ord + Clone, V: Clone {
 
     /// Inserts a key-value pair into the map. Returns replaced value if key is already present.
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        match self.map.insert(key, (value, ::time::SteadyTime::now())) {
+        match self.map.insert(key, (value, ::clock::now())) {
             Some((value, _)) => Some(value),
             None => None,
         }
@@ -52,7 +52,7 @@ impl<K, V> ExpirationMap<K, V> where K: PartialOrd + Ord + Clone, V: Clone {
     pub fn get(&mut self, key: &K) -> Option<&V> {
         match self.map.get(key) {
             Some(&(ref value, time)) => {
-                if time + self.time_to_live < ::time::SteadyTime::now() {
+                if time + self.time_to_live < ::clock::now() {
                     None
                 } else {
                     Some(value)
@@ -66,7 +66,7 @@ impl<K, V> ExpirationMap<K, V> where K: PartialOrd + Ord + Clone, V: Clone {
     pub fn contains_key(&mut self, key: &K) -> bool {
         match self.map.get(key) {
             Some(&(_, time)) => {
-                if time + self.time_to_live < ::time::SteadyTime::now() {
+                if time + self.time_to_live < ::clock::now() {
                     false
                 } else {
                     true
@@ -90,7 +90,7 @@ impl<K, V> ExpirationMap<K, V> where K: PartialOrd + Ord + Clone, V: Clone {
     /// Recover expired key-value pairs removing any such from the map.
     pub fn remove_expired(&mut self) -> Vec<(K,V)> {
         let mut expired = Vec::new();
-        let now = ::time::SteadyTime::now();
+        let now = ::clock::now();
 
         for (key, &(ref value, time)) in self.map.iter() {
             if time + self.time_to_live < now {
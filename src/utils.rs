@@ -47,11 +47,29 @@ pub fn decode<T>(bytes: &Vec<u8>) -> Result<T, ::cbor::CborError>
     }
 }
 
+/// Encode a value for the wire, in an envelope of our protocol version and the encoded value, so
+/// that the receiver can check the version before decoding the value.
+pub fn encode_envelope<T>(value: &T) -> Result<Vec<u8>, ::cbor::CborError>
+    where T: ::rustc_serialize::Encodable
+{
+    encode(&(::types::PROTOCOL_VERSION, try!(encode(value))))
+}
+
+/// Decode an envelope into the protocol version and the still encoded value.
+pub fn decode_envelope(bytes: &Vec<u8>) -> Result<(u8, Vec<u8>), ::cbor::CborError> {
+    decode(bytes)
+}
+
 /// The name client name is the SHA512 of the public signing key
 pub fn public_key_to_client_name(key: &::sodiumoxide::crypto::sign::PublicKey) -> ::NameType {
     ::NameType(::sodiumoxide::crypto::hash::sha512::hash(&key[..]).0)
 }
 
+/// The first node of a network can't be relocated and names itself the SHA512 of its client name
+pub fn first_node_name(client_name: &::NameType) -> ::NameType {
+    ::NameType(::sodiumoxide::crypto::hash::sha512::hash(&client_name.0).0)
+}
+
 /// relocated_name = Hash(original_name + 1st closest node id + 2nd closest node id)
 /// In case of only one close node provided (in initial network setup scenario),
 /// relocated_name = Hash(original_name + 1st closest node id)
@@ -105,6 +123,15 @@ mod test {
         assert_eq!(name, ::NameType(::types::slice_as_u8_64_array(&decoded[..])));
     }
 
+    #[test]
+    fn envelope_carries_version() {
+        let name: ::NameType = rand::random();
+        let envelope = super::encode_envelope(&name).unwrap();
+        let (version, encoded) = super::decode_envelope(&envelope).unwrap();
+        assert_eq!(version, ::types::PROTOCOL_VERSION);
+        assert_eq!(super::decode::<::NameType>(&encoded).unwrap(), name);
+    }
+
     #[test]
     fn calculate_relocated_name() {
         let original_name : ::NameType = rand::random();
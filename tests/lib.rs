@@ -36,41 +36,72 @@ extern crate env_logger;
 extern crate routing;
 extern crate sodiumoxide;
 
-use std::error::Error;
+use std::sync::mpsc::{channel, Receiver, Sender};
 
-fn start_nodes(number_of_nodes: u32) -> Vec<::std::process::Child> {
-    env_logger::init().unwrap_or_else(|e| println!("Error initialising logger: {:?}", e));
-    let mut processes = Vec::new();
-    let executable_path = match std::env::current_exe() {
-        Ok(mut exe_path) => {
-            exe_path.pop();
-            std::path::Path::new("./target").join(exe_path.iter().last().unwrap()).join("node")
-        }
-        Err(e) => panic!("Failed to get current integration test path: {}", e),
-    };
+use routing::event::Event;
+use routing::test_utils::mock_network::Network;
+use routing::types::RoutingConfig;
 
-    debug!("Expecting node executable at path {}", executable_path.to_path_buf().display());
+// Polls the receiver until an event satisfying the predicate arrives, returns false on timeout.
+fn wait_for_event<F>(receiver: &Receiver<Event>, predicate: F) -> bool where F: Fn(&Event) -> bool {
+    for _ in 0..1000 {
+        while let Ok(event) = receiver.try_recv() {
+            debug!("Test received {:?}", event);
+            if predicate(&event) { return true; };
+        }
+        ::std::thread::sleep_ms(10);
+    }
+    false
+}
 
+fn start_routing_nodes(network: &Network, number_of_nodes: usize, routing_config: RoutingConfig)
+        -> Vec<(::routing::routing::Routing, Receiver<Event>)> {
+    let group_size = routing_config.group_size();
+    let mut nodes = Vec::new();
     for i in 0..number_of_nodes {
-        processes.push(
-            match ::std::process::Command::new(
-                executable_path.to_path_buf()).stderr(::std::process::Stdio::piped()).spawn() {
-                    Err(e) => panic!("Failed to spawn process: {}", e.description()),
-                    Ok(process) => {
-                        debug!("Starting Node {:05}", process.id());
-                    	process
-                    }
-            });
-        ::std::thread::sleep_ms(1000 + i * 1000);
+        let (event_sender, event_receiver) = channel::<Event>();
+        let (transport, transport_receiver) = network.new_transport();
+        let routing = ::routing::routing::Routing::with_transport(event_sender,
+            routing_config.clone(), transport, transport_receiver);
+        nodes.push((routing, event_receiver));
+        if i == 0 {
+            continue;
+        }
+        if i <= group_size {
+            // wait until every node has all the others in its close group
+            for &(_, ref receiver) in nodes.iter() {
+                assert!(wait_for_event(receiver, |event| match *event {
+                    Event::Churn(ref close_group, _) => close_group.len() == i + 1,
+                    _ => false,
+                }));
+            }
+        } else {
+            // wait until the new node has filled its close group
+            let &(_, ref receiver) = nodes.last().unwrap();
+            assert!(wait_for_event(receiver, |event| match *event {
+                Event::Churn(ref close_group, _) => close_group.len() == group_size + 1,
+                _ => false,
+            }));
+        }
     }
-    ::std::thread::sleep_ms(number_of_nodes * 1000);
-    processes
+    nodes
 }
 
-fn stop_nodes(processes: &mut Vec<::std::process::Child>) {
-	while let Some(mut process) = processes.pop() {
-		debug!("Stopping Node {:05}", process.id());
-        let _ = process.kill();
+fn start_nodes(network: &Network, number_of_nodes: usize) -> Vec<Sender<Event>> {
+    env_logger::init().unwrap_or_else(|e| println!("Error initialising logger: {:?}", e));
+    let mut senders = Vec::new();
+    for _ in 0..number_of_nodes {
+        let mut node = ::routing::test_utils::node::Node::with_network(network);
+        senders.push(node.get_sender());
+        let _ = ::std::thread::spawn(move || node.run());
+        ::std::thread::sleep_ms(100);
+    }
+    senders
+}
+
+fn stop_nodes(senders: &mut Vec<Sender<Event>>) {
+    while let Some(sender) = senders.pop() {
+        let _ = sender.send(Event::Terminated);
     }
 }
 
@@ -80,19 +111,159 @@ fn calculate_key_name(key: &::std::string::String) -> ::routing::NameType {
 
 #[cfg(test)]
 mod test {
+    use routing::authority::Authority;
+    use routing::event::Event;
+    use routing::test_utils::mock_network::Network;
+    use routing::types::{RoutingConfig, ThresholdPolicy};
+    use routing::State;
+
+    #[test]
+    fn start_stop_nodes() {
+        let network = Network::new();
+        let mut nodes = super::start_nodes(&network, 3usize);
+        assert_eq!(network.listener_count(), 3usize);
+        super::stop_nodes(&mut nodes);
+    }
+
+    #[test]
+    fn churn_on_join_and_leave() {
+        let network = Network::new();
+        let mut nodes = super::start_routing_nodes(&network, 4usize,
+            RoutingConfig::default_config());
+
+        // a leaving node shrinks the close group of the remaining nodes
+        let (mut leaving, _) = nodes.pop().unwrap();
+        leaving.stop();
+        for &(_, ref receiver) in nodes.iter() {
+            assert!(super::wait_for_event(receiver, |event| match *event {
+                Event::Churn(ref close_group, _) => close_group.len() == 3usize,
+                _ => false,
+            }));
+        }
+
+        for (mut routing, _) in nodes {
+            routing.stop();
+        }
+    }
+
+    #[test]
+    fn snapshots_list_the_other_nodes() {
+        let network = Network::new();
+        let nodes = super::start_routing_nodes(&network, 4usize,
+            RoutingConfig::default_config());
+
+        // every node publishes the other three as connected members of its close group
+        let snapshots = nodes.iter().map(|&(ref routing, _)| {
+            let mut snapshot = routing.routing_table_snapshot();
+            for _ in 0..1000 {
+                if snapshot.close_group().len() == 3usize { break; };
+                ::std::thread::sleep_ms(10);
+                snapshot = routing.routing_table_snapshot();
+            }
+            snapshot
+        }).collect::<Vec<_>>();
+        let names = snapshots.iter().map(|snapshot| snapshot.our_name.clone()).collect::<Vec<_>>();
+        for snapshot in snapshots.iter() {
+            assert_eq!(snapshot.state, State::Connected);
+            assert_eq!(snapshot.close_group().len(), 3usize);
+            assert!(snapshot.contacts.iter().all(|contact| contact.is_connected() &&
+                contact.name != snapshot.our_name && names.contains(&contact.name)));
+        }
 
-	#[test]
-	fn start_stop_nodes() {
-        let mut nodes = super::start_nodes(3u32);
-		super::stop_nodes(&mut nodes);
-	}
+        for (mut routing, _) in nodes {
+            routing.stop();
+        }
+    }
+
+    #[test]
+    fn refresh_reaches_group() {
+        let network = Network::new();
+        let nodes = super::start_routing_nodes(&network, 4usize,
+            RoutingConfig::default_config());
+        let account = super::calculate_key_name(&::std::string::String::from("account"));
+        let authority = Authority::ClientManager(account.clone());
+
+        for &(ref routing, _) in nodes.iter() {
+            routing.refresh_request(1u64, authority.clone(),
+                ::routing::utils::encode(&1u64).unwrap(), account.clone());
+        }
+
+        let refreshed = nodes.iter().any(|&(_, ref receiver)| {
+            super::wait_for_event(receiver, |event| match *event {
+                Event::Refresh(1u64, ref refresh_authority, _) => *refresh_authority == authority,
+                _ => false,
+            })
+        });
+        assert!(refreshed);
+
+        for (mut routing, _) in nodes {
+            routing.stop();
+        }
+    }
+
+    #[test]
+    fn small_groups_fill_and_refresh() {
+        let network = Network::new();
+        let routing_config = RoutingConfig::new(3usize, 2usize, ThresholdPolicy::Quorum).unwrap();
+        let nodes = super::start_routing_nodes(&network, 6usize, routing_config);
+        let account = super::calculate_key_name(&::std::string::String::from("small"));
+        let authority = Authority::ClientManager(account.clone());
+
+        for &(ref routing, _) in nodes.iter() {
+            routing.refresh_request(1u64, authority.clone(),
+                ::routing::utils::encode(&1u64).unwrap(), account.clone());
+        }
+
+        let refreshed = nodes.iter().any(|&(_, ref receiver)| {
+            super::wait_for_event(receiver, |event| match *event {
+                Event::Refresh(1u64, ref refresh_authority, ref payloads) =>
+                    *refresh_authority == authority && payloads.len() == 2usize,
+                _ => false,
+            })
+        });
+        assert!(refreshed);
+
+        for (mut routing, _) in nodes {
+            routing.stop();
+        }
+    }
+
+    #[test]
+    fn simulated_churn_with_loss() {
+        use routing::test_utils::simulator::{Simulator, SimulatorConfig};
+
+        let seed = Simulator::seed_from_env();
+        let config = SimulatorConfig {
+            initial_nodes: 6usize,
+            min_nodes: 4usize,
+            loss_probability: 0.01f64,
+            ..SimulatorConfig::default()
+        };
+        let mut simulator = Simulator::new(seed, config);
+        simulator.run_for(20000u64);
+        simulator.stop_churn();
+        simulator.run_for(5000u64);
+        let churn_events = simulator.events().iter()
+            .filter(|&&(_, _, ref event)| match *event {
+                ::routing::event::Event::Churn(_, _) => true,
+                _ => false,
+            })
+            .count();
+        assert!(churn_events > 0usize, "Rerun with ROUTING_SIMULATION_SEED={}", simulator.seed());
+        // despite churn and loss, the nodes still running have found each other
+        assert!(2 * simulator.connected_node_count() > simulator.node_count(),
+                "Rerun with ROUTING_SIMULATION_SEED={}", simulator.seed());
+        simulator.stop();
+    }
 
     #[test]
-    #[ignore]
     fn client_put_get() {
+        let network = Network::new();
+        let mut nodes = super::start_nodes(&network, 4usize);
+
         debug!("Starting client");
-        let mut client = ::routing::test_utils::client::Client::new();
-        ::std::thread::sleep_ms(2000);
+        let mut client = ::routing::test_utils::client::Client::with_network(&network);
+        assert!(client.wait_for_bootstrap());
 
         let key = ::std::string::String::from("key");
         let value = ::std::string::String::from("value");
@@ -104,8 +275,6 @@ mod test {
         debug!("Putting data {:?}", data);
         client.put(data.clone());
 
-        ::std::thread::sleep_ms(5000);
-
         let recovered_data = match client.get(::routing::data::DataRequest::PlainData(name)) {
             Some(data) => data,
             None => panic!("Failed to recover stored data: {}.", name),
@@ -113,5 +282,6 @@ mod test {
 
         debug!("Recovered data {:?}", recovered_data);
         assert_eq!(recovered_data, data);
+        super::stop_nodes(&mut nodes);
     }
 }
@@ -21,8 +21,27 @@ extern crate log;
 extern crate env_logger;
 extern crate routing;
 
-/// Run a routing node.
+/// Run a routing node, optionally with the configuration file given as first argument.
 pub fn main () {
-    ::env_logger::init().unwrap_or_else(|e| println!("Error initialising logger: {:?}", e));
-    ::routing::test_utils::node::Node::new().run();
+    let mut node = match ::std::env::args().nth(1) {
+        Some(path) => {
+            let config_file = match ::routing::config_file::ConfigFile::read_file(
+                    ::std::path::Path::new(&path)) {
+                Ok(config_file) => config_file,
+                Err(error) => panic!("Unable to read configuration file {}: {}", path, error),
+            };
+            if let Err(error) = config_file.init_logging() {
+                panic!("Unable to initialise logging: {:?}", error);
+            }
+            match ::routing::test_utils::node::Node::with_config_file(&config_file) {
+                Ok(node) => node,
+                Err(error) => panic!("Invalid configuration file {}: {}", path, error),
+            }
+        },
+        None => {
+            ::env_logger::init().unwrap_or_else(|e| println!("Error initialising logger: {:?}", e));
+            ::routing::test_utils::node::Node::new()
+        },
+    };
+    node.run();
 }